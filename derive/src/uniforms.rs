@@ -0,0 +1,82 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{DeriveInput, Expr, Type};
+
+/// Element type and array size of a uniform field.
+///
+/// `[T; N]` is an array of `N` uniforms of type `T`, unless `T` is a scalar,
+/// in which case the whole array is a vector or matrix, e.g. `[f32; 4]`.
+fn split_array(ty: &Type) -> (&Type, Option<&Expr>) {
+    if let Type::Array(array) = ty {
+        let scalar = matches!(&*array.elem, Type::Path(path)
+            if path.path.is_ident("f32") || path.path.is_ident("i32"));
+        if !scalar {
+            return (&array.elem, Some(&array.len));
+        }
+    }
+    (ty, None)
+}
+
+pub fn derive(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let fields = crate::repr_c_fields(input, "MiniquadUniforms")?;
+    let ident = &input.ident;
+    let krate = quote!(::bevy_miniquad);
+
+    let mut descs = Vec::new();
+    let mut checks = Vec::new();
+    for field in fields.named.iter() {
+        let name = field.ident.as_ref().unwrap();
+        let name_str = name.to_string();
+        let ty = &field.ty;
+        let (element, count) = split_array(ty);
+        let count = match count {
+            Some(count) => quote!(#count),
+            None => quote!(1),
+        };
+        let uniform_type = quote!(<#element as #krate::uniforms::UniformElement>::UNIFORM_TYPE);
+
+        descs.push(quote! {
+            #krate::miniquad::UniformDesc::new(#name_str, #uniform_type).array(#count)
+        });
+
+        let size_message = format!(
+            "uniform `{}` has a different size than its uniform type",
+            name_str
+        );
+        let packed_message = format!(
+            "uniform `{}` does not directly follow the previous field, \
+             miniquad reads uniforms without padding",
+            name_str
+        );
+        let align_message = format!(
+            "uniform `{}` is not aligned for its type, \
+             order the fields by decreasing alignment",
+            name_str
+        );
+        checks.push(quote! {
+            let uniform_type = #uniform_type;
+            let size = #krate::uniforms::uniform_size(uniform_type) * (#count);
+            assert!(::core::mem::size_of::<#ty>() == size, #size_message);
+            assert!(::core::mem::offset_of!(#ident, #name) == offset, #packed_message);
+            assert!(
+                offset % #krate::uniforms::uniform_alignment(uniform_type) == 0,
+                #align_message
+            );
+            offset += size;
+        });
+    }
+
+    Ok(quote! {
+        impl #krate::uniforms::MiniquadUniforms for #ident {
+            fn uniforms() -> ::std::vec::Vec<#krate::miniquad::UniformDesc> {
+                ::std::vec![#(#descs),*]
+            }
+        }
+
+        const _: () = {
+            let mut offset = 0usize;
+            #({ #checks })*
+            let _ = offset;
+        };
+    })
+}
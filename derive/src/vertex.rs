@@ -0,0 +1,74 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{DeriveInput, LitStr};
+
+/// Attribute name of a field, its name unless renamed with `#[vertex(name = "...")]`.
+fn attribute_name(field: &syn::Field) -> syn::Result<String> {
+    let mut name = field.ident.as_ref().unwrap().to_string();
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("vertex"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else {
+                Err(meta.error("expected `name = \"...\"`"))
+            }
+        })?;
+    }
+    Ok(name)
+}
+
+pub fn derive(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let fields = crate::repr_c_fields(input, "MiniquadVertex")?;
+    let ident = &input.ident;
+    let krate = quote!(::bevy_miniquad);
+
+    let mut attributes = Vec::new();
+    let mut checks = Vec::new();
+    for field in fields.named.iter() {
+        let name = field.ident.as_ref().unwrap();
+        let attribute_name = attribute_name(field)?;
+        let ty = &field.ty;
+        let format = quote!(<#ty as #krate::vertex::VertexElement>::VERTEX_FORMAT);
+
+        attributes.push(quote! {
+            #krate::miniquad::VertexAttribute::new(#attribute_name, #format)
+        });
+
+        let size_message = format!(
+            "vertex field `{}` has a different size than its vertex format",
+            name
+        );
+        let offset_message = format!(
+            "vertex field `{}` does not directly follow the previous field, \
+             miniquad reads attributes without padding",
+            name
+        );
+        checks.push(quote! {
+            let size = #krate::vertex::vertex_format_size(#format);
+            assert!(::core::mem::size_of::<#ty>() == size, #size_message);
+            assert!(::core::mem::offset_of!(#ident, #name) == offset, #offset_message);
+            offset += size;
+        });
+    }
+
+    Ok(quote! {
+        impl #krate::vertex::MiniquadVertex for #ident {
+            const ATTRIBUTES: &'static [#krate::miniquad::VertexAttribute] = &[#(#attributes),*];
+        }
+
+        const _: () = {
+            let mut offset = 0usize;
+            #({ #checks })*
+            assert!(
+                ::core::mem::size_of::<#ident>() <= 255,
+                "vertex stride is limited to 255 bytes by WebGL 1"
+            );
+            let _ = offset;
+        };
+    })
+}
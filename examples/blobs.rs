@@ -4,7 +4,11 @@ use bevy::{
     input::{mouse::MouseButtonInput, ButtonState},
     prelude::*,
 };
-use bevy_miniquad::{miniquad as mq, DrawFnHandle, MiniquadContext, MiniquadPlugin, Window};
+use bevy_miniquad::{
+    gpu::{GpuBuffer, GpuDeletionQueue, GpuPipeline},
+    miniquad as mq, DrawFnHandle, MiniquadContext, MiniquadPlugin, MiniquadUniforms,
+    MiniquadVertex, Window,
+};
 use bevy_window::CursorMoved;
 use std::sync::Arc;
 
@@ -25,17 +29,14 @@ pub fn main() {
 }
 
 #[repr(C)]
-struct Vec2 {
-    x: f32,
-    y: f32,
-}
-#[repr(C)]
+#[derive(MiniquadVertex)]
 struct Vertex {
     pos: Vec2,
     uv: Vec2,
 }
 
 fn configure_stage(world: &mut World) {
+    let deletion_queue = world.resource::<GpuDeletionQueue>().clone();
     let renderer = {
         let mut ctx = world
             .get_non_send_resource_mut::<MiniquadContext>()
@@ -44,10 +45,10 @@ fn configure_stage(world: &mut World) {
 
         #[rustfmt::skip]
         let vertices: [Vertex; 4] = [
-            Vertex { pos : Vec2 { x: -1.0, y: -1.0 }, uv: Vec2 { x: 0., y: 0. } },
-            Vertex { pos : Vec2 { x:  1.0, y: -1.0 }, uv: Vec2 { x: 1., y: 0. } },
-            Vertex { pos : Vec2 { x:  1.0, y:  1.0 }, uv: Vec2 { x: 1., y: 1. } },
-            Vertex { pos : Vec2 { x: -1.0, y:  1.0 }, uv: Vec2 { x: 0., y: 1. } },
+            Vertex { pos : Vec2::new(-1.0, -1.0), uv: Vec2::new(0., 0.) },
+            Vertex { pos : Vec2::new(1.0, -1.0), uv: Vec2::new(1., 0.) },
+            Vertex { pos : Vec2::new(1.0, 1.0), uv: Vec2::new(1., 1.) },
+            Vertex { pos : Vec2::new(-1.0, 1.0), uv: Vec2::new(0., 1.) },
         ];
         let vertex_buffer = ctx.new_buffer(
             mq::BufferType::VertexBuffer,
@@ -79,11 +80,8 @@ fn configure_stage(world: &mut World) {
             .unwrap();
 
         let pipeline = ctx.new_pipeline(
-            &[mq::BufferLayout::default()],
-            &[
-                mq::VertexAttribute::new("pos", mq::VertexFormat::Float2),
-                mq::VertexAttribute::new("uv", mq::VertexFormat::Float2),
-            ],
+            &[Vertex::buffer_layout()],
+            Vertex::ATTRIBUTES,
             shader,
             mq::PipelineParams::default(),
         );
@@ -95,7 +93,11 @@ fn configure_stage(world: &mut World) {
         };
 
         Renderer {
-            pipeline,
+            pipeline: GpuPipeline::with_shader(&deletion_queue, pipeline, shader),
+            _buffers: [
+                GpuBuffer::new(&deletion_queue, vertex_buffer),
+                GpuBuffer::new(&deletion_queue, index_buffer),
+            ],
             bindings,
             uniforms,
             blobs_velocities: [(0., 0.); 32],
@@ -123,7 +125,9 @@ pub fn close_on_esc(
 
 #[derive(Resource)]
 struct Renderer {
-    pipeline: mq::Pipeline,
+    // Deleted with the renderer resource.
+    pipeline: GpuPipeline,
+    _buffers: [GpuBuffer; 2],
     bindings: mq::Bindings,
     uniforms: shader::Uniforms,
     blobs_velocities: [(f32, f32); 32],
@@ -144,11 +148,9 @@ fn draw(app: &mut App) {
         ctx.begin_default_pass(Default::default());
         ctx.apply_pipeline(&renderer.pipeline);
         ctx.apply_bindings(&renderer.bindings);
-        ctx.apply_uniforms(mq::UniformsSource::table(&renderer.uniforms));
+        ctx.apply_uniforms(renderer.uniforms.as_uniforms_source());
         ctx.draw(0, 6, 1);
         ctx.end_render_pass();
-
-        ctx.commit_frame();
     });
 }
 
@@ -211,7 +213,7 @@ fn mouse_handler(
 
 // based on: https://www.shadertoy.com/view/XsS3DV
 mod shader {
-    use bevy_miniquad::miniquad::*;
+    use bevy_miniquad::{miniquad::*, MiniquadUniforms};
 
     pub const VERTEX: &str = r#"#version 100
     attribute vec2 pos;
@@ -282,19 +284,11 @@ mod shader {
     }"#;
 
     pub fn meta() -> ShaderMeta {
-        ShaderMeta {
-            images: vec![],
-            uniforms: UniformBlockLayout {
-                uniforms: vec![
-                    UniformDesc::new("time", UniformType::Float1),
-                    UniformDesc::new("blobs_count", UniformType::Int1),
-                    UniformDesc::new("blobs_positions", UniformType::Float2).array(32),
-                ],
-            },
-        }
+        Uniforms::shader_meta(&[])
     }
 
     #[repr(C)]
+    #[derive(MiniquadUniforms)]
     pub struct Uniforms {
         pub time: f32,
         pub blobs_count: i32,
@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+use bevy_color::Color;
+use bevy_miniquad::{
+    camera::MiniquadCamera2d,
+    debug_draw::{DebugDraw, DebugDrawConfig, DebugDrawPlugin},
+    MiniquadPlugin,
+};
+use bevy_window::CursorMoved;
+
+pub fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins((MiniquadPlugin, DebugDrawPlugin))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (draw_shapes, draw_cursor, toggle))
+        .run();
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn(MiniquadCamera2d::default());
+}
+
+fn draw_shapes(time: Res<Time>, mut debug_draw: DebugDraw) {
+    let angle = time.elapsed_secs();
+    debug_draw.rect_2d(Vec2::ZERO, Vec2::new(400., 300.), Color::WHITE);
+    debug_draw.circle_2d(Vec2::ZERO, 100., Color::srgb(0.2, 0.8, 0.3));
+    debug_draw.arrow_2d(
+        Vec2::ZERO,
+        Vec2::from_angle(angle) * 140.,
+        Color::srgb(1., 0.3, 0.2),
+    );
+    for i in 0..8 {
+        let x = -175. + i as f32 * 50.;
+        let height = (angle * 2. + i as f32).sin() * 40.;
+        debug_draw.line_2d(
+            Vec2::new(x, -120.),
+            Vec2::new(x, -120. + height),
+            Color::srgb(0.3, 0.5, 1.),
+        );
+    }
+}
+
+// Screen space shapes follow the cursor regardless of the camera.
+fn draw_cursor(
+    mut cursor: Local<Vec2>,
+    mut cursor_moved: EventReader<CursorMoved>,
+    mut debug_draw: DebugDraw,
+) {
+    if let Some(event) = cursor_moved.read().last() {
+        *cursor = event.position;
+    }
+    debug_draw.screen_circle(*cursor, 12., Color::srgb(1., 1., 0.));
+    debug_draw.screen_rect(*cursor, Vec2::splat(32.), Color::srgb(1., 1., 0.));
+}
+
+fn toggle(input: Res<ButtonInput<KeyCode>>, mut config: ResMut<DebugDrawConfig>) {
+    if input.just_pressed(KeyCode::Space) {
+        config.enabled = !config.enabled;
+    }
+}
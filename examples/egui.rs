@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+use bevy_color::Color;
+use bevy_input::mouse::MouseButtonInput;
+use bevy_miniquad::{
+    egui::{self, EguiContext, EguiPlugin},
+    ClearColor, MiniquadPlugin,
+};
+
+pub fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins((MiniquadPlugin, EguiPlugin))
+        .init_resource::<Settings>()
+        .add_systems(Update, (settings_window, clicks))
+        .run();
+}
+
+#[derive(Resource)]
+struct Settings {
+    name: String,
+    background: [f32; 3],
+    clicks: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            name: "miniquad".to_string(),
+            background: [0.1, 0.1, 0.15],
+            clicks: 0,
+        }
+    }
+}
+
+fn settings_window(
+    egui: Res<EguiContext>,
+    mut settings: ResMut<Settings>,
+    mut clear_color: ResMut<ClearColor>,
+) {
+    egui::Window::new("Settings").show(egui.get(), |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut settings.name);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Background");
+            ui.color_edit_button_rgb(&mut settings.background);
+        });
+        ui.label(format!(
+            "Hello {}, you clicked the game {} times.",
+            settings.name, settings.clicks
+        ));
+    });
+    let [red, green, blue] = settings.background;
+    clear_color.0 = Color::srgb(red, green, blue);
+}
+
+// Clicks on the settings window are not counted.
+fn clicks(
+    egui: Res<EguiContext>,
+    mut settings: ResMut<Settings>,
+    mut mouse_button_input: EventReader<MouseButtonInput>,
+) {
+    for input in mouse_button_input.read() {
+        if input.state.is_pressed() && !egui.wants_pointer_input() {
+            settings.clicks += 1;
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use bevy::prelude::*;
+use bevy_asset::{AssetPlugin, Assets, Handle, RenderAssetUsages};
+use bevy_image::{Image, ImageSampler};
+use bevy_miniquad::{
+    camera::MiniquadCamera2d,
+    sprite::{Sprite, SpritePlugin},
+    texture::MiniquadImagePlugin,
+    MiniquadPlugin,
+};
+use wgpu_types::{Extent3d, TextureDimension, TextureFormat};
+
+const SIZE: u32 = 32;
+
+pub fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(AssetPlugin::default())
+        .add_plugins((MiniquadPlugin, MiniquadImagePlugin::default(), SpritePlugin))
+        .add_systems(Startup, setup)
+        .add_systems(Update, animate_image)
+        .run();
+}
+
+#[derive(Resource)]
+struct Plasma(Handle<Image>);
+
+fn plasma(time: f32) -> Vec<u8> {
+    let mut data = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let (x, y) = (x as f32 / 4., y as f32 / 4.);
+            let value = ((x + time).sin() + (y - time).cos() + ((x + y) / 2.).sin()) / 3.;
+            let value = ((value + 1.) * 127.5) as u8;
+            data.extend_from_slice(&[value, 255 - value, 128, 255]);
+        }
+    }
+    data
+}
+
+fn setup(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    commands.spawn(MiniquadCamera2d::default());
+
+    let mut image = Image::new(
+        Extent3d {
+            width: SIZE,
+            height: SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        plasma(0.),
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.sampler = ImageSampler::nearest();
+    let image = images.add(image);
+
+    commands.spawn(Sprite {
+        custom_size: Some(Vec2::splat(512.)),
+        ..Sprite::from_image(image.clone())
+    });
+    commands.insert_resource(Plasma(image));
+}
+
+fn animate_image(time: Res<Time>, plasma_image: Res<Plasma>, mut images: ResMut<Assets<Image>>) {
+    if let Some(image) = images.get_mut(&plasma_image.0) {
+        image.data = plasma(time.elapsed_secs());
+    }
+}
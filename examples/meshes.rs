@@ -0,0 +1,104 @@
+use bevy::prelude::*;
+use bevy_asset::{AssetPlugin, Assets};
+use bevy_color::Color;
+use bevy_miniquad::{
+    camera::MiniquadCamera3d,
+    mesh::{
+        DirectionalLight, Mesh, Mesh3d, MeshMaterial, MeshMaterial3d, Meshable, MiniquadMeshPlugin,
+        PointLight,
+    },
+    MiniquadPlugin,
+};
+
+pub fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, AssetPlugin::default()))
+        .add_plugins((MiniquadPlugin, MiniquadMeshPlugin))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (rotate, orbit))
+        .run();
+}
+
+#[derive(Component)]
+struct Rotating;
+
+#[derive(Component)]
+struct Orbiting;
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<MeshMaterial>>,
+) {
+    commands.spawn((
+        MiniquadCamera3d::default(),
+        Transform::from_xyz(0., 3., 8.).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+
+    commands.spawn((
+        Mesh3d(meshes.add(Plane3d::default().mesh().size(10., 10.))),
+        MeshMaterial3d(materials.add(Color::srgb(0.3, 0.5, 0.3))),
+    ));
+    commands.spawn((
+        Mesh3d(meshes.add(Cuboid::default())),
+        MeshMaterial3d(materials.add(Color::srgb(0.8, 0.7, 0.6))),
+        Transform::from_xyz(-1.5, 1., 0.),
+        Rotating,
+    ));
+    commands.spawn((
+        Mesh3d(meshes.add(Sphere::new(0.75).mesh().uv(32, 18))),
+        MeshMaterial3d(materials.add(MeshMaterial {
+            base_color: Color::srgb(0.2, 0.4, 0.9),
+            shininess: 64.,
+            specular_strength: 1.,
+            ..Default::default()
+        })),
+        Transform::from_xyz(1.5, 1., 0.),
+    ));
+    commands.spawn((
+        Mesh3d(meshes.add(Cuboid::from_length(1.5))),
+        MeshMaterial3d(materials.add(MeshMaterial {
+            base_color: Color::srgba(1., 1., 1., 0.4),
+            alpha_blend: true,
+            ..Default::default()
+        })),
+        Transform::from_xyz(0., 1., 2.),
+    ));
+
+    commands.spawn((
+        DirectionalLight {
+            illuminance: 0.6,
+            ..Default::default()
+        },
+        Transform::from_xyz(1., 4., 2.).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+    // The light source is drawn as a small unlit sphere.
+    commands.spawn((
+        PointLight {
+            color: Color::srgb(1., 0.6, 0.2),
+            intensity: 2.,
+            range: 6.,
+        },
+        Mesh3d(meshes.add(Sphere::new(0.1))),
+        MeshMaterial3d(materials.add(MeshMaterial {
+            base_color: Color::srgb(1., 0.6, 0.2),
+            unlit: true,
+            ..Default::default()
+        })),
+        Orbiting,
+    ));
+}
+
+fn rotate(time: Res<Time>, mut query: Query<&mut Transform, With<Rotating>>) {
+    for mut transform in &mut query {
+        transform.rotate_y(time.delta_secs());
+        transform.rotate_x(time.delta_secs() * 0.5);
+    }
+}
+
+fn orbit(time: Res<Time>, mut query: Query<&mut Transform, With<Orbiting>>) {
+    let angle = time.elapsed_secs();
+    for mut transform in &mut query {
+        transform.translation = Vec3::new(angle.cos() * 3., 1.5, angle.sin() * 3.);
+    }
+}
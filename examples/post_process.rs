@@ -0,0 +1,85 @@
+use bevy::prelude::*;
+use bevy_color::Color;
+use bevy_miniquad::{
+    camera::MiniquadCamera2d,
+    post_process::{Bloom, ColorGrading, Crt, PostProcessPlugin, Vignette},
+    sprite::{Sprite, SpritePlugin},
+    MiniquadPlugin,
+};
+
+// Press 1 to 4 to toggle bloom, color grading, vignette and CRT effects.
+pub fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins((MiniquadPlugin, SpritePlugin, PostProcessPlugin))
+        .insert_resource(Bloom::default())
+        .insert_resource(Vignette::default())
+        .add_systems(Startup, setup)
+        .add_systems(Update, (spin, toggle_effects))
+        .run();
+}
+
+#[derive(Component)]
+struct Spinning(f32);
+
+fn setup(mut commands: Commands) {
+    commands.spawn(MiniquadCamera2d::default());
+
+    for i in 0..16 {
+        let angle = i as f32 / 16. * std::f32::consts::TAU;
+        let lightness = if i % 4 == 0 { 0.9 } else { 0.5 };
+        commands.spawn((
+            Sprite::from_color(
+                Color::hsl(i as f32 * 22.5, 0.9, lightness),
+                Vec2::splat(40.),
+            ),
+            Transform::from_xyz(angle.cos() * 200., angle.sin() * 200., 0.),
+            Spinning(1. + i as f32 * 0.1),
+        ));
+    }
+}
+
+fn spin(time: Res<Time>, mut sprites: Query<(&Spinning, &mut Transform)>) {
+    for (spinning, mut transform) in sprites.iter_mut() {
+        transform.rotate_z(spinning.0 * time.delta_secs());
+        transform.rotate_around(Vec3::ZERO, Quat::from_rotation_z(0.2 * time.delta_secs()));
+    }
+}
+
+fn toggle<E: Resource + Default>(commands: &mut Commands, enabled: bool) {
+    if enabled {
+        commands.remove_resource::<E>();
+    } else {
+        commands.insert_resource(E::default());
+    }
+}
+
+fn toggle_effects(
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    bloom: Option<Res<Bloom>>,
+    color_grading: Option<Res<ColorGrading>>,
+    vignette: Option<Res<Vignette>>,
+    crt: Option<Res<Crt>>,
+) {
+    if input.just_pressed(KeyCode::Digit1) {
+        toggle::<Bloom>(&mut commands, bloom.is_some());
+    }
+    if input.just_pressed(KeyCode::Digit2) {
+        if color_grading.is_some() {
+            commands.remove_resource::<ColorGrading>();
+        } else {
+            commands.insert_resource(ColorGrading {
+                contrast: 1.2,
+                saturation: 0.4,
+                ..Default::default()
+            });
+        }
+    }
+    if input.just_pressed(KeyCode::Digit3) {
+        toggle::<Vignette>(&mut commands, vignette.is_some());
+    }
+    if input.just_pressed(KeyCode::Digit4) {
+        toggle::<Crt>(&mut commands, crt.is_some());
+    }
+}
@@ -0,0 +1,64 @@
+use bevy::prelude::*;
+use bevy_color::Color;
+use bevy_miniquad::{
+    camera::{ClearColorConfig, MiniquadCamera2d, OrthographicProjection},
+    render_target::RenderTarget,
+    sprite::{Anchor, Sprite, SpritePlugin},
+    MiniquadPlugin,
+};
+
+pub fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins((MiniquadPlugin, SpritePlugin))
+        .add_systems(Startup, setup)
+        .add_systems(Update, orbit)
+        .run();
+}
+
+#[derive(Component)]
+struct Orbiting(f32);
+
+fn setup(mut commands: Commands) {
+    commands.spawn(MiniquadCamera2d::default());
+
+    // A zoomed out view of the scene, drawn offscreen before the window.
+    let minimap = commands.spawn(RenderTarget::new(UVec2::splat(256))).id();
+    commands.spawn(MiniquadCamera2d {
+        projection: OrthographicProjection {
+            scale: 4.,
+            ..Default::default()
+        },
+        clear_color: ClearColorConfig::Custom(Color::srgb(0.1, 0.1, 0.2)),
+        target: Some(minimap),
+        ..Default::default()
+    });
+
+    for i in 0..12 {
+        let hue = i as f32 * 30.;
+        commands.spawn((
+            Sprite::from_color(Color::hsl(hue, 0.8, 0.6), Vec2::splat(48.)),
+            Transform::from_xyz(0., 0., i as f32),
+            Orbiting(i as f32 * 0.5),
+        ));
+    }
+
+    // The minimap texture drawn in the top right corner of the window.
+    commands.spawn((
+        Sprite {
+            custom_size: Some(Vec2::splat(192.)),
+            anchor: Anchor::TopRight,
+            ..Sprite::from_render_target(minimap)
+        },
+        Transform::from_xyz(380., 280., 100.),
+    ));
+}
+
+fn orbit(time: Res<Time>, mut sprites: Query<(&Orbiting, &mut Transform)>) {
+    for (orbiting, mut transform) in sprites.iter_mut() {
+        let angle = time.elapsed_secs() * 0.5 + orbiting.0;
+        let radius = 150. + orbiting.0 * 60.;
+        transform.translation.x = angle.cos() * radius;
+        transform.translation.y = angle.sin() * radius;
+    }
+}
@@ -0,0 +1,109 @@
+use bevy::prelude::*;
+use bevy_asset::{AssetPlugin, AssetServer};
+use bevy_miniquad::{
+    miniquad as mq,
+    shader::{
+        MiniquadShaderPlugin, ShaderCompileError, ShaderPipelineDescriptor, ShaderPipelineId,
+        ShaderPipelines,
+    },
+    Draw, DrawSet, MiniquadContext, MiniquadPlugin, Window,
+};
+
+// Run with `--features file_watcher` and edit `assets/shaders/waves.mqshader`
+// while the example is running to see it reloaded.
+pub fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(AssetPlugin {
+            watch_for_changes_override: Some(cfg!(feature = "file_watcher")),
+            ..default()
+        })
+        .add_plugins((MiniquadPlugin, MiniquadShaderPlugin))
+        .add_systems(Startup, setup)
+        .add_systems(Update, report_errors)
+        .add_systems(Draw, draw.in_set(DrawSet::Main))
+        .run();
+}
+
+#[repr(C)]
+struct Uniforms {
+    time: f32,
+    resolution: (f32, f32),
+}
+
+#[derive(Resource)]
+struct Waves {
+    pipeline: ShaderPipelineId,
+    bindings: mq::Bindings,
+}
+
+fn setup(
+    mut commands: Commands,
+    mut ctx: NonSendMut<MiniquadContext>,
+    asset_server: Res<AssetServer>,
+    mut pipelines: ResMut<ShaderPipelines>,
+) {
+    let ctx = &mut *ctx.0;
+
+    let vertices: [[f32; 2]; 4] = [[-1., -1.], [1., -1.], [1., 1.], [-1., 1.]];
+    let vertex_buffer = ctx.new_buffer(
+        mq::BufferType::VertexBuffer,
+        mq::BufferUsage::Immutable,
+        mq::BufferSource::slice(&vertices),
+    );
+    let indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
+    let index_buffer = ctx.new_buffer(
+        mq::BufferType::IndexBuffer,
+        mq::BufferUsage::Immutable,
+        mq::BufferSource::slice(&indices),
+    );
+
+    let pipeline = pipelines.add(ShaderPipelineDescriptor {
+        shader: asset_server.load("shaders/waves.mqshader"),
+        buffer_layouts: vec![mq::BufferLayout::default()],
+        attributes: vec![mq::VertexAttribute::new("pos", mq::VertexFormat::Float2)],
+        params: mq::PipelineParams::default(),
+    });
+
+    commands.insert_resource(Waves {
+        pipeline,
+        bindings: mq::Bindings {
+            vertex_buffers: vec![vertex_buffer],
+            index_buffer,
+            images: vec![],
+        },
+    });
+}
+
+fn report_errors(mut errors: EventReader<ShaderCompileError>) {
+    for error in errors.read() {
+        eprintln!(
+            "Shader error, keeping the previous version: {}",
+            error.error
+        );
+    }
+}
+
+fn draw(
+    mut ctx: NonSendMut<MiniquadContext>,
+    time: Res<Time>,
+    window: Res<Window>,
+    pipelines: Res<ShaderPipelines>,
+    waves: Res<Waves>,
+) {
+    // Nothing to draw until the shader is loaded and compiled.
+    let Some(pipeline) = pipelines.get(waves.pipeline) else {
+        return;
+    };
+    let ctx = &mut *ctx.0;
+
+    ctx.begin_default_pass(mq::PassAction::Nothing);
+    ctx.apply_pipeline(&pipeline);
+    ctx.apply_bindings(&waves.bindings);
+    ctx.apply_uniforms(mq::UniformsSource::table(&Uniforms {
+        time: time.elapsed_secs(),
+        resolution: (window.width, window.height),
+    }));
+    ctx.draw(0, 6, 1);
+    ctx.end_render_pass();
+}
@@ -0,0 +1,126 @@
+use bevy::prelude::*;
+use bevy_color::Color;
+use bevy_miniquad::{
+    camera::{CameraView, MiniquadCamera2d},
+    miniquad as mq,
+    screenshot::{ScreenshotCaptured, TakeScreenshot},
+    sprite::{Anchor, Sprite, SpritePlugin, TextureAtlas, TextureAtlasLayout},
+    MiniquadContext, MiniquadPlugin,
+};
+use bevy_window::CursorMoved;
+use std::sync::Arc;
+
+pub fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins((MiniquadPlugin, SpritePlugin))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (rotate, follow_cursor, screenshot))
+        .run();
+}
+
+#[derive(Component)]
+struct Rotating(f32);
+
+#[derive(Component)]
+struct Cursor;
+
+fn setup(mut commands: Commands, mut ctx: NonSendMut<MiniquadContext>) {
+    commands.spawn(MiniquadCamera2d::default());
+
+    // 4x1 tiles of 16x16 pixels, each a checkerboard in a different color
+    let tile_colors = [[255, 80, 80], [80, 255, 80], [80, 80, 255], [255, 255, 80]];
+    let (width, height) = (64, 16);
+    let mut pixels = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let [r, g, b] = tile_colors[x / 16];
+            let shade = if (x / 4 + y / 4) % 2 == 0 { 255 } else { 128 };
+            pixels.extend_from_slice(&[
+                (r * shade / 255) as u8,
+                (g * shade / 255) as u8,
+                (b * shade / 255) as u8,
+                255,
+            ]);
+        }
+    }
+    let texture = ctx
+        .0
+        .new_texture_from_rgba8(width as u16, height as u16, &pixels);
+    ctx.0
+        .texture_set_filter(texture, mq::FilterMode::Nearest, mq::MipmapFilterMode::None);
+
+    let layout = Arc::new(TextureAtlasLayout::from_grid(
+        UVec2::splat(16),
+        4,
+        1,
+        None,
+        None,
+    ));
+
+    for i in 0..4 {
+        commands.spawn((
+            Sprite {
+                custom_size: Some(Vec2::splat(96.)),
+                flip_x: i % 2 == 1,
+                ..Sprite::from_atlas_texture(texture, TextureAtlas::new(layout.clone(), i))
+            },
+            Transform::from_xyz(-180. + 120. * i as f32, 0., i as f32),
+            Rotating(0.5 + i as f32 * 0.25),
+        ));
+    }
+
+    commands.spawn((
+        Sprite {
+            anchor: Anchor::BottomLeft,
+            ..Sprite::from_color(Color::srgba(1., 1., 1., 0.25), Vec2::new(400., 200.))
+        },
+        Transform::from_xyz(-200., -100., 10.),
+    ));
+
+    commands.spawn((
+        Sprite::from_color(Color::srgb(1., 1., 1.), Vec2::splat(8.)),
+        Transform::from_xyz(0., 0., 20.),
+        Cursor,
+    ));
+}
+
+fn follow_cursor(
+    mut cursor_moved_events: EventReader<CursorMoved>,
+    cameras: Query<&CameraView>,
+    mut cursor: Query<&mut Transform, With<Cursor>>,
+) {
+    let Ok(camera) = cameras.get_single() else {
+        return;
+    };
+    for event in cursor_moved_events.read() {
+        if let Some(position) = camera.viewport_to_world_2d(event.position) {
+            for mut transform in cursor.iter_mut() {
+                transform.translation = position.extend(transform.translation.z);
+            }
+        }
+    }
+}
+
+fn rotate(time: Res<Time>, mut sprites: Query<(&Rotating, &mut Transform)>) {
+    for (rotating, mut transform) in sprites.iter_mut() {
+        transform.rotate_z(rotating.0 * time.delta_secs());
+    }
+}
+
+// Press S to save a screenshot, written to a file with the `png` feature.
+fn screenshot(
+    input: Res<ButtonInput<KeyCode>>,
+    mut take_screenshot: EventWriter<TakeScreenshot>,
+    mut captured: EventReader<ScreenshotCaptured>,
+) {
+    if input.just_pressed(KeyCode::KeyS) {
+        take_screenshot.send(TakeScreenshot::window().with_path("screenshot.png"));
+    }
+    for screenshot in captured.read() {
+        println!(
+            "Captured a {}x{} screenshot",
+            screenshot.width, screenshot.height
+        );
+    }
+}
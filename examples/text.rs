@@ -0,0 +1,85 @@
+use bevy::prelude::*;
+use bevy_asset::{AssetPlugin, AssetServer};
+use bevy_color::Color;
+use bevy_miniquad::{
+    anchor::Anchor,
+    camera::MiniquadCamera2d,
+    text::{JustifyText, Text2d, TextLabel, TextPlugin, TextStyle},
+    MiniquadPlugin,
+};
+
+pub fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, AssetPlugin::default()))
+        .add_plugins((MiniquadPlugin, TextPlugin))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (spin, update_clock))
+        .run();
+}
+
+#[derive(Component)]
+struct Spinning;
+
+#[derive(Component)]
+struct Clock;
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn(MiniquadCamera2d::default());
+
+    let font = asset_server.load("fonts/DejaVuSans.ttf");
+    commands.spawn((
+        Text2d::new(
+            "Hello, miniquad!",
+            TextStyle {
+                font: font.clone(),
+                font_size: 48.,
+                color: Color::srgb(1., 0.8, 0.3),
+            },
+        ),
+        Spinning,
+    ));
+    commands.spawn((
+        Text2d {
+            justify: JustifyText::Center,
+            max_width: Some(300.),
+            anchor: Anchor::TopCenter,
+            ..Text2d::new(
+                "Long lines are wrapped on spaces past the maximum width, and centered.",
+                TextStyle {
+                    font: font.clone(),
+                    ..Default::default()
+                },
+            )
+        },
+        Transform::from_xyz(0., -100., 0.),
+    ));
+
+    commands.spawn((
+        TextLabel::new(
+            "",
+            TextStyle {
+                font,
+                font_size: 18.,
+                color: Color::srgb(0.6, 1., 0.6),
+            },
+            Vec2::new(10., 10.),
+        ),
+        Clock,
+    ));
+}
+
+fn spin(time: Res<Time>, mut texts: Query<&mut Transform, With<Spinning>>) {
+    for mut transform in texts.iter_mut() {
+        transform.rotation = Quat::from_rotation_z(time.elapsed_secs().sin() * 0.3);
+    }
+}
+
+fn update_clock(time: Res<Time>, mut labels: Query<&mut TextLabel, With<Clock>>) {
+    for mut label in labels.iter_mut() {
+        label.text = format!(
+            "Elapsed: {:.1}s\nFrame time: {:.1}ms",
+            time.elapsed_secs(),
+            time.delta_secs() * 1000.
+        );
+    }
+}
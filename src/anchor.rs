@@ -0,0 +1,35 @@
+use bevy_math::Vec2;
+
+/// Point of a sprite or text placed at its transform translation.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Anchor {
+    #[default]
+    Center,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+    CenterLeft,
+    CenterRight,
+    TopLeft,
+    TopCenter,
+    TopRight,
+    /// Custom anchor, `(-0.5, -0.5)` is the bottom left and `(0.5, 0.5)` the top right corner.
+    Custom(Vec2),
+}
+
+impl Anchor {
+    pub fn as_vec(&self) -> Vec2 {
+        match self {
+            Anchor::Center => Vec2::ZERO,
+            Anchor::BottomLeft => Vec2::new(-0.5, -0.5),
+            Anchor::BottomCenter => Vec2::new(0.0, -0.5),
+            Anchor::BottomRight => Vec2::new(0.5, -0.5),
+            Anchor::CenterLeft => Vec2::new(-0.5, 0.0),
+            Anchor::CenterRight => Vec2::new(0.5, 0.0),
+            Anchor::TopLeft => Vec2::new(-0.5, 0.5),
+            Anchor::TopCenter => Vec2::new(0.0, 0.5),
+            Anchor::TopRight => Vec2::new(0.5, 0.5),
+            Anchor::Custom(point) => *point,
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! miniquad gives no access to the contents of `BufferSource::Slice`. Sources
+//! created with `slice` keep their size, and a copy of their bytes for the
+//! backends reading them, like the `SoftRenderingBackend`.
+//!
+//! ```
+//! # use bevy_miniquad::{buffer_source, miniquad as mq};
+//! fn upload(ctx: &mut mq::Context, indices: &[u16]) -> mq::BufferId {
+//!     ctx.new_buffer(
+//!         mq::BufferType::IndexBuffer,
+//!         mq::BufferUsage::Immutable,
+//!         buffer_source::slice(indices),
+//!     )
+//! }
+//! ```
+
+use miniquad as mq;
+use std::cell::{Cell, RefCell};
+
+/// Types whose values are initialized bytes only, so slices of them can be copied.
+///
+/// # Safety
+///
+/// The type must have no padding, as `#[repr(C)]` vertex structs of floats and
+/// `[u8; 4]` colors usually don't.
+pub unsafe trait BufferData {}
+
+macro_rules! buffer_data {
+    ($($ty:ty),*) => {
+        $(
+            // Safety: primitive numbers have no padding.
+            unsafe impl BufferData for $ty {}
+        )*
+    };
+}
+
+buffer_data!(u8, u16, u32, i8, i16, i32, f32);
+
+// Safety: array elements follow each other without padding.
+unsafe impl<T: BufferData, const N: usize> BufferData for [T; N] {}
+
+/// Bytes of a `slice` source, with the size of its elements.
+#[cfg_attr(not(feature = "soft-render"), allow(dead_code))]
+pub(crate) struct BufferCopy {
+    pub bytes: Vec<u8>,
+    pub element_size: usize,
+}
+
+/// Source made by the last `slice` call, its bytes copied only for backends
+/// reading them.
+struct LastSlice {
+    size: usize,
+    copy: Option<BufferCopy>,
+}
+
+thread_local! {
+    /// Backends alive on the thread that read the size of `slice` sources.
+    static SIZE_READERS: Cell<usize> = const { Cell::new(0) };
+    /// Backends alive on the thread that read the bytes of `slice` sources.
+    static COPY_READERS: Cell<usize> = const { Cell::new(0) };
+    static LAST_SLICE: RefCell<Option<LastSlice>> = const { RefCell::new(None) };
+}
+
+/// Same as `BufferSource::slice`, keeping its size, and a copy of the data
+/// while a backend reading it exists.
+///
+/// They go with the next `new_buffer` or `buffer_update` call, so the source
+/// should be passed on right away. A source that was not passed on is forgotten
+/// by the next `slice` call. Sources created with `BufferSource::slice` have no
+/// size, and panic in backends reading their bytes.
+pub fn slice<T: BufferData>(data: &[T]) -> mq::BufferSource<'_> {
+    let last = (SIZE_READERS.get() + COPY_READERS.get() > 0).then(|| LastSlice {
+        size: size_of_val(data),
+        copy: (COPY_READERS.get() > 0).then(|| {
+            // Safety: `BufferData` values have no padding, all their bytes are
+            // initialized.
+            let bytes = unsafe {
+                std::slice::from_raw_parts(data.as_ptr() as *const u8, size_of_val(data))
+            };
+            BufferCopy {
+                bytes: bytes.to_vec(),
+                element_size: size_of::<T>(),
+            }
+        }),
+    });
+    LAST_SLICE.set(last);
+    mq::BufferSource::slice(data)
+}
+
+/// Backend reading buffer sources, `slice` keeps their size, or copies their
+/// data, while it is alive.
+pub(crate) struct SliceReader {
+    copies: bool,
+}
+
+impl SliceReader {
+    /// Reader of the bytes of sources.
+    #[cfg_attr(not(feature = "soft-render"), allow(dead_code))]
+    pub fn copies() -> Self {
+        COPY_READERS.set(COPY_READERS.get() + 1);
+        SliceReader { copies: true }
+    }
+
+    /// Reader of the size of sources only, `slice` copies nothing for it.
+    pub fn sizes() -> Self {
+        SIZE_READERS.set(SIZE_READERS.get() + 1);
+        SliceReader { copies: false }
+    }
+
+    /// Bytes of a source passed to `new_buffer` or `buffer_update`, zeros for
+    /// empty sources.
+    ///
+    /// # Panics
+    ///
+    /// If the source was created with `BufferSource::slice`.
+    #[cfg_attr(not(feature = "soft-render"), allow(dead_code))]
+    pub fn take(&self, source: &mq::BufferSource) -> BufferCopy {
+        let last = LAST_SLICE.take();
+        match source {
+            mq::BufferSource::Empty { size, element_size } => BufferCopy {
+                bytes: vec![0; *size],
+                element_size: *element_size,
+            },
+            mq::BufferSource::Slice(_) => last.and_then(|last| last.copy).expect(
+                "Buffer data created with BufferSource::slice cannot be read, \
+                 use buffer_source::slice",
+            ),
+        }
+    }
+
+    /// Size in bytes of a source, `None` for slices not created with `slice`.
+    /// The size is kept for a backend the call is passed on to, until `clear`.
+    pub fn size(&self, source: &mq::BufferSource) -> Option<usize> {
+        match source {
+            mq::BufferSource::Empty { size, .. } => Some(*size),
+            mq::BufferSource::Slice(_) => {
+                LAST_SLICE.with_borrow(|last| last.as_ref().map(|last| last.size))
+            }
+        }
+    }
+
+    /// Forgets the last `slice` source once the call it was passed to is done,
+    /// so a later `BufferSource::slice` is not mistaken for it.
+    pub fn clear(&self) {
+        LAST_SLICE.take();
+    }
+}
+
+impl Drop for SliceReader {
+    fn drop(&mut self) {
+        let readers = if self.copies {
+            &COPY_READERS
+        } else {
+            &SIZE_READERS
+        };
+        readers.set(readers.get() - 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_readers_get_no_copy() {
+        let reader = SliceReader::sizes();
+        let source = slice(&[1u16, 2, 3]);
+        assert_eq!(reader.size(&source), Some(6));
+        assert!(LAST_SLICE.with_borrow(|last| last.as_ref().unwrap().copy.is_none()));
+    }
+
+    #[test]
+    fn plain_slices_are_not_sized_after_clear() {
+        let reader = SliceReader::sizes();
+        let source = slice(&[0f32; 4]);
+        assert_eq!(reader.size(&source), Some(16));
+        reader.clear();
+
+        let plain = [0u8; 3];
+        assert_eq!(reader.size(&mq::BufferSource::slice(&plain)), None);
+    }
+
+    #[test]
+    fn slice_forgets_the_unconsumed_source() {
+        let reader = SliceReader::sizes();
+        let _unused = slice(&[0u32; 8]);
+        drop(reader);
+        let _unread = slice(&[0u8; 2]);
+        assert!(LAST_SLICE.with_borrow(Option::is_none));
+    }
+}
@@ -0,0 +1,373 @@
+use bevy_app::{App, Plugin, PostUpdate};
+use bevy_color::Color;
+use bevy_ecs::prelude::*;
+use bevy_math::{Dir3, Mat4, Ray3d, URect, UVec2, Vec2, Vec3};
+use bevy_transform::{
+    components::{GlobalTransform, Transform},
+    TransformSystem,
+};
+use bevy_window::WindowResized;
+use miniquad as mq;
+
+use crate::{render_target::RenderTarget, Window};
+
+/// Keeps `CameraView` of the cameras up to date.
+#[derive(Default)]
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            (
+                update_camera_view::<MiniquadCamera2d>,
+                update_camera_view::<MiniquadCamera3d>,
+            )
+                .after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// How a camera clears its viewport before drawing.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ClearColorConfig {
+    /// Clear with the `ClearColor` resource.
+    #[default]
+    Default,
+    Custom(Color),
+    /// Draw over the frame, cleared only by `ClearColor` before any camera.
+    None,
+}
+
+/// Region of the window a camera draws to, in physical pixels from the top left.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Viewport {
+    pub physical_position: UVec2,
+    pub physical_size: UVec2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrthographicProjection {
+    pub near: f32,
+    pub far: f32,
+    /// World units per physical pixel.
+    pub scale: f32,
+    /// Point of the viewport the camera is looking at, `(0.5, 0.5)` is the center.
+    pub viewport_origin: Vec2,
+}
+
+impl Default for OrthographicProjection {
+    fn default() -> Self {
+        OrthographicProjection {
+            near: -1000.,
+            far: 1000.,
+            scale: 1.,
+            viewport_origin: Vec2::new(0.5, 0.5),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PerspectiveProjection {
+    /// Vertical field of view, in radians.
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for PerspectiveProjection {
+    fn default() -> Self {
+        PerspectiveProjection {
+            fov: std::f32::consts::PI / 4.,
+            near: 0.1,
+            far: 1000.,
+        }
+    }
+}
+
+/// Settings shared by the camera kinds.
+pub trait MiniquadCamera: Component {
+    fn projection_matrix(&self, viewport_size: Vec2) -> Mat4;
+    fn viewport(&self) -> Option<Viewport>;
+    fn clear_color(&self) -> ClearColorConfig;
+    fn order(&self) -> isize;
+    fn is_active(&self) -> bool;
+    fn target(&self) -> Option<Entity>;
+}
+
+/// Camera for 2D drawing, with y up and one world unit per pixel by default.
+#[derive(Component, Clone, Debug)]
+#[require(Transform, CameraView)]
+pub struct MiniquadCamera2d {
+    pub projection: OrthographicProjection,
+    /// Whole window or render target when `None`.
+    pub viewport: Option<Viewport>,
+    pub clear_color: ClearColorConfig,
+    /// Cameras with a higher order draw later.
+    pub order: isize,
+    pub is_active: bool,
+    /// Entity with a `RenderTarget` to draw to, instead of the window.
+    pub target: Option<Entity>,
+}
+
+impl Default for MiniquadCamera2d {
+    fn default() -> Self {
+        MiniquadCamera2d {
+            projection: Default::default(),
+            viewport: None,
+            clear_color: Default::default(),
+            order: 0,
+            is_active: true,
+            target: None,
+        }
+    }
+}
+
+impl MiniquadCamera for MiniquadCamera2d {
+    fn projection_matrix(&self, viewport_size: Vec2) -> Mat4 {
+        let projection = &self.projection;
+        let size = viewport_size * projection.scale;
+        let origin = size * projection.viewport_origin;
+        Mat4::orthographic_rh_gl(
+            -origin.x,
+            size.x - origin.x,
+            -origin.y,
+            size.y - origin.y,
+            projection.near,
+            projection.far,
+        )
+    }
+
+    fn viewport(&self) -> Option<Viewport> {
+        self.viewport
+    }
+
+    fn clear_color(&self) -> ClearColorConfig {
+        self.clear_color
+    }
+
+    fn order(&self) -> isize {
+        self.order
+    }
+
+    fn is_active(&self) -> bool {
+        self.is_active
+    }
+
+    fn target(&self) -> Option<Entity> {
+        self.target
+    }
+}
+
+/// Camera for 3D drawing, looking down its local -z axis.
+#[derive(Component, Clone, Debug)]
+#[require(Transform, CameraView)]
+pub struct MiniquadCamera3d {
+    pub projection: PerspectiveProjection,
+    /// Whole window or render target when `None`.
+    pub viewport: Option<Viewport>,
+    pub clear_color: ClearColorConfig,
+    /// Cameras with a higher order draw later.
+    pub order: isize,
+    pub is_active: bool,
+    /// Entity with a `RenderTarget` to draw to, instead of the window.
+    pub target: Option<Entity>,
+}
+
+impl Default for MiniquadCamera3d {
+    fn default() -> Self {
+        MiniquadCamera3d {
+            projection: Default::default(),
+            viewport: None,
+            clear_color: Default::default(),
+            order: 0,
+            is_active: true,
+            target: None,
+        }
+    }
+}
+
+impl MiniquadCamera for MiniquadCamera3d {
+    fn projection_matrix(&self, viewport_size: Vec2) -> Mat4 {
+        let projection = &self.projection;
+        Mat4::perspective_rh_gl(
+            projection.fov,
+            viewport_size.x / viewport_size.y.max(1.),
+            projection.near,
+            projection.far,
+        )
+    }
+
+    fn viewport(&self) -> Option<Viewport> {
+        self.viewport
+    }
+
+    fn clear_color(&self) -> ClearColorConfig {
+        self.clear_color
+    }
+
+    fn order(&self) -> isize {
+        self.order
+    }
+
+    fn is_active(&self) -> bool {
+        self.is_active
+    }
+
+    fn target(&self) -> Option<Entity> {
+        self.target
+    }
+}
+
+/// Matrices and viewport of a camera, computed from its settings,
+/// `GlobalTransform` and the window size.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct CameraView {
+    /// World to view space, the inverse of the camera transform.
+    pub view: Mat4,
+    pub projection: Mat4,
+    pub view_projection: Mat4,
+    /// Viewport in physical pixels from the top left of the target.
+    pub viewport: URect,
+    /// Size of the window or render target the camera draws to.
+    pub target_size: UVec2,
+    pub clear_color: ClearColorConfig,
+    pub order: isize,
+    pub is_active: bool,
+    /// `RenderTarget` entity the camera draws to, the window when `None`.
+    pub target: Option<Entity>,
+    /// Pass of `target`, set in `DrawSet::Prepare` once its textures exist,
+    /// or the `MainPass` for the window.
+    pub target_pass: Option<mq::RenderPass>,
+    pub target_order: isize,
+}
+
+impl CameraView {
+    fn viewport_to_ndc(&self, viewport_position: Vec2) -> Option<Vec2> {
+        let viewport = self.viewport.as_rect();
+        let size = viewport.size();
+        if size.x <= 0. || size.y <= 0. {
+            return None;
+        }
+        let ndc = (viewport_position - viewport.min) / size * 2. - Vec2::ONE;
+        Some(Vec2::new(ndc.x, -ndc.y))
+    }
+
+    /// World position under a window position, e.g. of `CursorMoved`,
+    /// on the z = 0 plane of a 2D camera.
+    pub fn viewport_to_world_2d(&self, viewport_position: Vec2) -> Option<Vec2> {
+        let ndc = self.viewport_to_ndc(viewport_position)?;
+        let world = self
+            .view_projection
+            .inverse()
+            .project_point3(ndc.extend(0.));
+        world.is_finite().then(|| world.truncate())
+    }
+
+    /// Ray going from the camera through a window position, e.g. of `CursorMoved`.
+    pub fn viewport_to_world(&self, viewport_position: Vec2) -> Option<Ray3d> {
+        let ndc = self.viewport_to_ndc(viewport_position)?;
+        let inverse = self.view_projection.inverse();
+        let near = inverse.project_point3(ndc.extend(-1.));
+        let far = inverse.project_point3(ndc.extend(1.));
+        let direction = Dir3::new(far - near).ok()?;
+        Some(Ray3d {
+            origin: near,
+            direction,
+        })
+    }
+
+    /// Window position of a world position, `None` when outside of the clip volume.
+    pub fn world_to_viewport(&self, world_position: Vec3) -> Option<Vec2> {
+        let ndc = self.view_projection.project_point3(world_position);
+        if !ndc.is_finite() || ndc.z < -1. || ndc.z > 1. {
+            return None;
+        }
+        let viewport = self.viewport.as_rect();
+        let position = (Vec2::new(ndc.x, -ndc.y) + Vec2::ONE) / 2. * viewport.size();
+        Some(viewport.min + position)
+    }
+
+    /// Whether the camera draws to a `RenderTarget` rather than the window.
+    pub fn is_offscreen(&self) -> bool {
+        self.target.is_some()
+    }
+
+    /// Key to sort cameras in drawing order: render targets by their order first,
+    /// then the window, each by camera order.
+    pub fn draw_order(&self) -> (bool, isize, isize) {
+        (!self.is_offscreen(), self.target_order, self.order)
+    }
+
+    /// Begins the pass of the camera target, `false` if the target is not created yet.
+    pub fn begin_pass(&self, ctx: &mut mq::Context, action: mq::PassAction) -> bool {
+        match (self.target, self.target_pass) {
+            (_, Some(pass)) => ctx.begin_pass(Some(pass), action),
+            (None, None) => ctx.begin_default_pass(action),
+            (Some(_), None) => return false,
+        }
+        true
+    }
+
+    /// Restricts drawing in the current pass to the camera viewport.
+    pub fn apply_viewport(&self, ctx: &mut mq::Context) {
+        let size = self.viewport.size();
+        // miniquad viewports start at the bottom left.
+        let y = self.target_size.y as i32 - self.viewport.max.y as i32;
+        ctx.apply_viewport(self.viewport.min.x as i32, y, size.x as i32, size.y as i32);
+        ctx.apply_scissor_rect(self.viewport.min.x as i32, y, size.x as i32, size.y as i32);
+    }
+}
+
+fn update_camera_view<C: MiniquadCamera>(
+    window: Res<Window>,
+    mut resized: EventReader<WindowResized>,
+    mut cameras: Query<(Ref<C>, Ref<GlobalTransform>, &mut CameraView)>,
+    targets: Query<Ref<RenderTarget>>,
+) {
+    let resized = resized.read().count() > 0;
+    let window_size = UVec2::new(window.width as u32, window.height as u32);
+
+    for (camera, transform, mut view) in cameras.iter_mut() {
+        let target = camera.target().and_then(|entity| targets.get(entity).ok());
+        let target_changed = target.as_ref().is_some_and(|target| target.is_changed());
+        if !resized
+            && !target_changed
+            && !camera.is_changed()
+            && !transform.is_changed()
+            && !view.is_added()
+        {
+            continue;
+        }
+
+        let target_size = match &target {
+            Some(target) => target.size.max(UVec2::ONE),
+            None => window_size,
+        };
+
+        let viewport = match camera.viewport() {
+            Some(viewport) => URect::from_corners(
+                viewport.physical_position,
+                viewport.physical_position + viewport.physical_size,
+            ),
+            None => URect::from_corners(UVec2::ZERO, target_size),
+        };
+        let projection = camera.projection_matrix(viewport.size().as_vec2());
+        let camera_view = transform.compute_matrix().inverse();
+
+        *view = CameraView {
+            view: camera_view,
+            projection,
+            view_projection: projection * camera_view,
+            viewport,
+            target_size,
+            clear_color: camera.clear_color(),
+            order: camera.order(),
+            is_active: camera.is_active(),
+            target: camera.target(),
+            // Kept until the next `DrawSet::Prepare` updates them.
+            target_pass: view.target_pass.filter(|_| view.target == camera.target()),
+            target_order: view.target_order,
+        };
+    }
+}
@@ -1,3 +1,4 @@
+use bevy_color::{Color, Srgba};
 use bevy_input::{
     keyboard::{Key, KeyCode, NativeKey, NativeKeyCode},
     mouse::MouseButton,
@@ -13,6 +14,16 @@ pub fn convert_mouse_button(mouse_button: mq::MouseButton) -> MouseButton {
     }
 }
 
+pub fn convert_color(color: Color) -> (f32, f32, f32, f32) {
+    let Srgba {
+        red,
+        green,
+        blue,
+        alpha,
+    } = color.to_srgba();
+    (red, green, blue, alpha)
+}
+
 pub fn key_code_to_unprintable_logical_key(key_code: KeyCode) -> Option<Key> {
     return Some(match key_code {
         KeyCode::Unidentified(native) => Key::Unidentified(match native {
@@ -273,6 +284,11 @@ pub fn key_code_is_printable(key_code: KeyCode) -> bool {
     }
 }
 
+/// Bevy key code of a miniquad one, unidentified for unknown keys.
+pub fn convert_key_code(key_code: mq::KeyCode) -> KeyCode {
+    convert_virtual_key_code(key_code).unwrap_or(KeyCode::Unidentified(NativeKeyCode::Unidentified))
+}
+
 pub fn convert_virtual_key_code(key_code: mq::KeyCode) -> Option<KeyCode> {
     match key_code {
         mq::KeyCode::Key1 => Some(KeyCode::Digit1),
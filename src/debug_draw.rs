@@ -0,0 +1,429 @@
+use bevy_app::{App, First, Plugin};
+use bevy_color::Color;
+use bevy_ecs::{prelude::*, system::SystemParam};
+use bevy_math::{Dir3, Mat4, URect, UVec2, Vec2, Vec3};
+use miniquad as mq;
+use std::f32::consts::TAU;
+
+use crate::{
+    buffer_source::{self, BufferData},
+    camera::CameraView,
+    converters::convert_color,
+    draw::builtin_shader_source,
+    gpu::{GpuBuffer, GpuDeletionQueue, GpuPipeline},
+    screenshot::take_screenshots,
+    MiniquadContext, MiniquadUniforms, MiniquadVertex, PostDraw, Window,
+};
+
+/// Draws the `DebugDraw` shapes over the window at the end of each frame,
+/// after any post-processing.
+///
+/// World space shapes are drawn by every active camera drawing to the window,
+/// or in window pixels centered on the window without one.
+#[derive(Default)]
+pub struct DebugDrawPlugin;
+
+impl Plugin for DebugDrawPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugDrawConfig>()
+            .init_resource::<DebugDrawBuffer>()
+            .add_systems(First, clear_debug_draw)
+            .add_systems(PostDraw, draw_debug.before(take_screenshots));
+    }
+}
+
+/// Turns debug drawing on and off, shapes are ignored while disabled.
+#[derive(Clone, Debug, Resource)]
+pub struct DebugDrawConfig {
+    pub enabled: bool,
+    /// Segments of circles.
+    pub circle_segments: u32,
+}
+
+impl Default for DebugDrawConfig {
+    fn default() -> Self {
+        DebugDrawConfig {
+            enabled: true,
+            circle_segments: 32,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, MiniquadVertex)]
+struct DebugVertex {
+    #[vertex(name = "in_pos")]
+    pos: [f32; 3],
+    #[vertex(name = "in_color")]
+    color: [f32; 4],
+}
+
+// Safety: `repr(C)` struct of floats only, it has no padding.
+unsafe impl BufferData for DebugVertex {}
+
+/// Line vertices added this frame, in world and window space.
+#[derive(Default, Resource)]
+pub struct DebugDrawBuffer {
+    world: Vec<DebugVertex>,
+    screen: Vec<DebugVertex>,
+}
+
+impl DebugDrawBuffer {
+    pub fn clear(&mut self) {
+        self.world.clear();
+        self.screen.clear();
+    }
+
+    /// Number of lines to draw.
+    pub fn len(&self) -> usize {
+        (self.world.len() + self.screen.len()) / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.world.is_empty() && self.screen.is_empty()
+    }
+}
+
+fn push_line(lines: &mut Vec<DebugVertex>, start: Vec3, end: Vec3, color: Color) {
+    let (r, g, b, a) = convert_color(color);
+    let color = [r, g, b, a];
+    lines.push(DebugVertex {
+        pos: start.to_array(),
+        color,
+    });
+    lines.push(DebugVertex {
+        pos: end.to_array(),
+        color,
+    });
+}
+
+/// Points of a circle, closing back on the first one.
+fn circle_points(
+    center: Vec3,
+    x_axis: Vec3,
+    y_axis: Vec3,
+    radius: f32,
+    segments: u32,
+) -> impl Iterator<Item = Vec3> {
+    let segments = segments.max(3);
+    (0..=segments).map(move |i| {
+        let angle = i as f32 / segments as f32 * TAU;
+        center + (x_axis * angle.cos() + y_axis * angle.sin()) * radius
+    })
+}
+
+/// Immediate-mode shapes, drawn at the end of the frame they are added in.
+///
+/// World space shapes use the camera transforms, screen space ones are in
+/// window pixels from the top left, like `CursorMoved` positions.
+#[derive(SystemParam)]
+pub struct DebugDraw<'w> {
+    buffer: ResMut<'w, DebugDrawBuffer>,
+    config: Res<'w, DebugDrawConfig>,
+}
+
+impl DebugDraw<'_> {
+    fn lines(&mut self, screen: bool) -> Option<&mut Vec<DebugVertex>> {
+        if !self.config.enabled {
+            return None;
+        }
+        Some(if screen {
+            &mut self.buffer.screen
+        } else {
+            &mut self.buffer.world
+        })
+    }
+
+    fn polyline(&mut self, screen: bool, points: impl IntoIterator<Item = Vec3>, color: Color) {
+        let Some(lines) = self.lines(screen) else {
+            return;
+        };
+        let mut points = points.into_iter();
+        let Some(mut previous) = points.next() else {
+            return;
+        };
+        for point in points {
+            push_line(lines, previous, point, color);
+            previous = point;
+        }
+    }
+
+    fn arrow_lines(&mut self, screen: bool, start: Vec3, end: Vec3, side: Vec3, color: Color) {
+        let length = start.distance(end);
+        let back = (start - end).normalize_or_zero() * length * 0.2;
+        let side = side * length * 0.1;
+        self.polyline(screen, [start, end], color);
+        self.polyline(screen, [end + back + side, end, end + back - side], color);
+    }
+
+    pub fn line(&mut self, start: Vec3, end: Vec3, color: impl Into<Color>) {
+        self.polyline(false, [start, end], color.into());
+    }
+
+    pub fn ray(&mut self, origin: Vec3, vector: Vec3, color: impl Into<Color>) {
+        self.line(origin, origin + vector, color);
+    }
+
+    pub fn arrow(&mut self, start: Vec3, end: Vec3, color: impl Into<Color>) {
+        let side = (end - start).normalize_or_zero().any_orthonormal_vector();
+        self.arrow_lines(false, start, end, side, color.into());
+    }
+
+    /// Circle facing `normal`.
+    pub fn circle(&mut self, center: Vec3, normal: Dir3, radius: f32, color: impl Into<Color>) {
+        let (x_axis, y_axis) = normal.any_orthonormal_pair();
+        let segments = self.config.circle_segments;
+        let points = circle_points(center, x_axis, y_axis, radius, segments);
+        self.polyline(false, points, color.into());
+    }
+
+    pub fn line_2d(&mut self, start: Vec2, end: Vec2, color: impl Into<Color>) {
+        self.line(start.extend(0.), end.extend(0.), color);
+    }
+
+    pub fn arrow_2d(&mut self, start: Vec2, end: Vec2, color: impl Into<Color>) {
+        let side = (end - start).normalize_or_zero().perp().extend(0.);
+        self.arrow_lines(false, start.extend(0.), end.extend(0.), side, color.into());
+    }
+
+    pub fn rect_2d(&mut self, center: Vec2, size: Vec2, color: impl Into<Color>) {
+        let points = rect_points(center, size).map(|point| point.extend(0.));
+        self.polyline(false, points, color.into());
+    }
+
+    pub fn circle_2d(&mut self, center: Vec2, radius: f32, color: impl Into<Color>) {
+        let segments = self.config.circle_segments;
+        let points = circle_points(center.extend(0.), Vec3::X, Vec3::Y, radius, segments);
+        self.polyline(false, points, color.into());
+    }
+
+    pub fn screen_line(&mut self, start: Vec2, end: Vec2, color: impl Into<Color>) {
+        self.polyline(true, [start.extend(0.), end.extend(0.)], color.into());
+    }
+
+    pub fn screen_arrow(&mut self, start: Vec2, end: Vec2, color: impl Into<Color>) {
+        let side = (end - start).normalize_or_zero().perp().extend(0.);
+        self.arrow_lines(true, start.extend(0.), end.extend(0.), side, color.into());
+    }
+
+    pub fn screen_rect(&mut self, center: Vec2, size: Vec2, color: impl Into<Color>) {
+        let points = rect_points(center, size).map(|point| point.extend(0.));
+        self.polyline(true, points, color.into());
+    }
+
+    pub fn screen_circle(&mut self, center: Vec2, radius: f32, color: impl Into<Color>) {
+        let segments = self.config.circle_segments;
+        let points = circle_points(center.extend(0.), Vec3::X, Vec3::Y, radius, segments);
+        self.polyline(true, points, color.into());
+    }
+}
+
+fn rect_points(center: Vec2, size: Vec2) -> [Vec2; 5] {
+    let half = size / 2.;
+    [
+        center + Vec2::new(-half.x, -half.y),
+        center + Vec2::new(half.x, -half.y),
+        center + Vec2::new(half.x, half.y),
+        center + Vec2::new(-half.x, half.y),
+        center + Vec2::new(-half.x, -half.y),
+    ]
+}
+
+/// Vertices drawn per draw call, the most a `u16` index buffer can address.
+const MAX_VERTICES: usize = 1 << 16;
+
+pub(crate) struct DebugRenderer {
+    pipeline: GpuPipeline,
+    vertex_buffer: GpuBuffer,
+    index_buffer: GpuBuffer,
+}
+
+impl DebugRenderer {
+    fn new(ctx: &mut mq::Context, deletion_queue: &GpuDeletionQueue) -> Self {
+        let vertex_buffer = ctx.new_buffer(
+            mq::BufferType::VertexBuffer,
+            mq::BufferUsage::Stream,
+            mq::BufferSource::empty::<DebugVertex>(MAX_VERTICES),
+        );
+        let indices: Vec<u16> = (0..MAX_VERTICES).map(|i| i as u16).collect();
+        let index_buffer = ctx.new_buffer(
+            mq::BufferType::IndexBuffer,
+            mq::BufferUsage::Immutable,
+            buffer_source::slice(&indices),
+        );
+
+        let shader = ctx
+            .new_shader(
+                builtin_shader_source(
+                    ctx.info().backend,
+                    shader::VERTEX,
+                    shader::FRAGMENT,
+                    shader::METAL,
+                ),
+                shader::Uniforms::shader_meta(&[]),
+            )
+            .expect("Cannot compile debug draw shader");
+        let pipeline = ctx.new_pipeline(
+            &[DebugVertex::buffer_layout()],
+            DebugVertex::ATTRIBUTES,
+            shader,
+            mq::PipelineParams {
+                primitive_type: mq::PrimitiveType::Lines,
+                color_blend: Some(mq::BlendState::new(
+                    mq::Equation::Add,
+                    mq::BlendFactor::Value(mq::BlendValue::SourceAlpha),
+                    mq::BlendFactor::OneMinusValue(mq::BlendValue::SourceAlpha),
+                )),
+                ..Default::default()
+            },
+        );
+
+        DebugRenderer {
+            pipeline: GpuPipeline::with_shader(deletion_queue, pipeline, shader),
+            vertex_buffer: GpuBuffer::new(deletion_queue, vertex_buffer),
+            index_buffer: GpuBuffer::new(deletion_queue, index_buffer),
+        }
+    }
+
+    fn draw(&self, ctx: &mut mq::Context, view_projection: Mat4, vertices: &[DebugVertex]) {
+        ctx.apply_uniforms(
+            shader::Uniforms {
+                view_proj: view_projection,
+            }
+            .as_uniforms_source(),
+        );
+        for chunk in vertices.chunks(MAX_VERTICES) {
+            ctx.buffer_update(*self.vertex_buffer, buffer_source::slice(chunk));
+            ctx.apply_bindings(&mq::Bindings {
+                vertex_buffers: vec![*self.vertex_buffer],
+                index_buffer: *self.index_buffer,
+                images: vec![],
+            });
+            ctx.draw(0, chunk.len() as i32, 1);
+        }
+    }
+}
+
+pub(crate) fn draw_debug(
+    mut ctx: NonSendMut<MiniquadContext>,
+    deletion_queue: Res<GpuDeletionQueue>,
+    mut renderer: Local<Option<DebugRenderer>>,
+    buffer: Res<DebugDrawBuffer>,
+    window: Res<Window>,
+    cameras: Query<&CameraView>,
+) {
+    if buffer.is_empty() {
+        return;
+    }
+    let ctx = &mut *ctx.0;
+    let renderer = renderer.get_or_insert_with(|| DebugRenderer::new(ctx, &deletion_queue));
+    let window_size = UVec2::new(window.width as u32, window.height as u32);
+
+    ctx.begin_default_pass(mq::PassAction::Nothing);
+    ctx.apply_pipeline(&renderer.pipeline);
+
+    if !buffer.world.is_empty() {
+        // Shapes are drawn over the final image, offscreen targets are done already.
+        let mut views: Vec<&CameraView> = cameras
+            .iter()
+            .filter(|view| view.is_active && !view.is_offscreen())
+            .collect();
+        views.sort_by_key(|view| view.draw_order());
+        let fallback_view;
+        if views.is_empty() {
+            let half_size = window_size.as_vec2() / 2.;
+            fallback_view = CameraView {
+                view_projection: Mat4::orthographic_rh_gl(
+                    -half_size.x,
+                    half_size.x,
+                    -half_size.y,
+                    half_size.y,
+                    -1000.,
+                    1000.,
+                ),
+                viewport: URect::from_corners(UVec2::ZERO, window_size),
+                target_size: window_size,
+                is_active: true,
+                ..Default::default()
+            };
+            views.push(&fallback_view);
+        }
+
+        for view in views {
+            view.apply_viewport(ctx);
+            renderer.draw(ctx, view.view_projection, &buffer.world);
+        }
+    }
+
+    if !buffer.screen.is_empty() {
+        let size = window_size.as_vec2();
+        ctx.apply_viewport(0, 0, window_size.x as i32, window_size.y as i32);
+        ctx.apply_scissor_rect(0, 0, window_size.x as i32, window_size.y as i32);
+        let projection = Mat4::orthographic_rh_gl(0., size.x, size.y, 0., -1., 1.);
+        renderer.draw(ctx, projection, &buffer.screen);
+    }
+
+    ctx.end_render_pass();
+}
+
+/// Clears the shapes of the previous frame, whether they were drawn or not.
+fn clear_debug_draw(mut buffer: ResMut<DebugDrawBuffer>) {
+    buffer.clear();
+}
+
+mod shader {
+    use bevy_math::Mat4;
+
+    use crate::MiniquadUniforms;
+
+    pub const VERTEX: &str = r#"#version 100
+    attribute vec3 in_pos;
+    attribute vec4 in_color;
+    varying lowp vec4 color;
+    uniform mat4 view_proj;
+    void main() {
+        gl_Position = view_proj * vec4(in_pos, 1.0);
+        color = in_color;
+    }"#;
+
+    pub const FRAGMENT: &str = r#"#version 100
+    varying lowp vec4 color;
+    void main() {
+        gl_FragColor = color;
+    }"#;
+
+    pub const METAL: &str = r#"
+    #include <metal_stdlib>
+    using namespace metal;
+
+    struct Uniforms {
+        float4x4 view_proj;
+    };
+
+    struct Vertex {
+        float3 in_pos [[attribute(0)]];
+        float4 in_color [[attribute(1)]];
+    };
+
+    struct RasterizerData {
+        float4 position [[position]];
+        float4 color [[user(locn0)]];
+    };
+
+    vertex RasterizerData vertexShader(Vertex v [[stage_in]], constant Uniforms& uniforms [[buffer(0)]]) {
+        RasterizerData out;
+        out.position = uniforms.view_proj * float4(v.in_pos, 1.0);
+        out.color = v.in_color;
+        return out;
+    }
+
+    fragment float4 fragmentShader(RasterizerData in [[stage_in]]) {
+        return in.color;
+    }"#;
+
+    #[repr(C)]
+    #[derive(MiniquadUniforms)]
+    pub struct Uniforms {
+        pub view_proj: Mat4,
+    }
+}
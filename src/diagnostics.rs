@@ -0,0 +1,457 @@
+use bevy_app::{App, First, Plugin};
+use bevy_diagnostic::{
+    Diagnostic, DiagnosticPath, Diagnostics, DiagnosticsPlugin, RegisterDiagnostic,
+};
+use bevy_ecs::prelude::*;
+use miniquad as mq;
+use std::{cell::Cell, rc::Rc};
+
+use crate::{buffer_source::SliceReader, MiniquadContext};
+
+/// Adds `DiagnosticsStore` entries for the last frame, timed by the runner
+/// around its update and draw, and counted by wrapping the `MiniquadContext`.
+///
+/// With the `text` feature, spawn a `TextLabel` with `DiagnosticsOverlay` to show
+/// them over the window.
+#[derive(Default)]
+pub struct MiniquadDiagnosticsPlugin;
+
+impl MiniquadDiagnosticsPlugin {
+    /// Time between the starts of the last two updates, in milliseconds.
+    pub const FRAME_TIME: DiagnosticPath = DiagnosticPath::const_new("miniquad/frame_time");
+    /// Time spent in `App::update`, in milliseconds.
+    pub const UPDATE_TIME: DiagnosticPath = DiagnosticPath::const_new("miniquad/update_time");
+    /// Time spent running the `Draw` and `PostDraw` schedules and committing
+    /// the frame, in milliseconds.
+    pub const DRAW_TIME: DiagnosticPath = DiagnosticPath::const_new("miniquad/draw_time");
+    pub const DRAW_CALLS: DiagnosticPath = DiagnosticPath::const_new("miniquad/draw_calls");
+    /// Buffers alive at the end of the frame.
+    pub const BUFFERS: DiagnosticPath = DiagnosticPath::const_new("miniquad/buffers");
+    /// Textures alive at the end of the frame.
+    pub const TEXTURES: DiagnosticPath = DiagnosticPath::const_new("miniquad/textures");
+    /// Buffer and texture bytes sent to the GPU during the frame.
+    pub const UPLOADED_BYTES: DiagnosticPath = DiagnosticPath::const_new("miniquad/uploaded_bytes");
+}
+
+impl Plugin for MiniquadDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<DiagnosticsPlugin>() {
+            app.add_plugins(DiagnosticsPlugin);
+        }
+        app.register_diagnostic(Diagnostic::new(Self::FRAME_TIME).with_suffix("ms"))
+            .register_diagnostic(Diagnostic::new(Self::UPDATE_TIME).with_suffix("ms"))
+            .register_diagnostic(Diagnostic::new(Self::DRAW_TIME).with_suffix("ms"))
+            .register_diagnostic(Diagnostic::new(Self::DRAW_CALLS))
+            .register_diagnostic(Diagnostic::new(Self::BUFFERS))
+            .register_diagnostic(Diagnostic::new(Self::TEXTURES))
+            .register_diagnostic(Diagnostic::new(Self::UPLOADED_BYTES).with_suffix("B"))
+            .init_resource::<RunnerTimings>()
+            .add_systems(First, record_diagnostics);
+        #[cfg(feature = "text")]
+        app.add_systems(bevy_app::Update, update_overlays);
+    }
+
+    // The runner inserts the context right before finishing the app.
+    fn finish(&self, app: &mut App) {
+        let Some(MiniquadContext(ctx)) = app
+            .world_mut()
+            .remove_non_send_resource::<MiniquadContext>()
+        else {
+            log::warn!("No MiniquadContext to count draw calls and uploads of");
+            return;
+        };
+        let counters = Rc::new(BackendCounters::default());
+        app.insert_non_send_resource(MiniquadContext(Box::new(CountingBackend {
+            inner: ctx,
+            counters: counters.clone(),
+            slices: SliceReader::sizes(),
+            pass_attachments: Vec::new(),
+        })))
+        .insert_non_send_resource(counters);
+    }
+}
+
+/// Durations of the last frame in seconds, set by the runner.
+#[derive(Default, Resource)]
+pub(crate) struct RunnerTimings {
+    frame: f64,
+    update: f64,
+    draw: f64,
+    last_update_start: Option<f64>,
+}
+
+impl RunnerTimings {
+    pub(crate) fn record_update(world: &mut World, start: f64) {
+        if let Some(mut timings) = world.get_resource_mut::<RunnerTimings>() {
+            timings.frame = timings
+                .last_update_start
+                .map_or(0., |last_start| start - last_start);
+            timings.last_update_start = Some(start);
+            timings.update = mq::date::now() - start;
+        }
+    }
+
+    pub(crate) fn record_draw(world: &mut World, start: f64) {
+        if let Some(mut timings) = world.get_resource_mut::<RunnerTimings>() {
+            timings.draw = mq::date::now() - start;
+        }
+    }
+}
+
+#[derive(Default)]
+struct BackendCounters {
+    draw_calls: Cell<u64>,
+    buffers: Cell<u64>,
+    textures: Cell<u64>,
+    uploaded_bytes: Cell<u64>,
+}
+
+impl BackendCounters {
+    fn upload(&self, bytes: usize) {
+        self.uploaded_bytes
+            .set(self.uploaded_bytes.get() + bytes as u64);
+    }
+}
+
+fn record_diagnostics(
+    mut diagnostics: Diagnostics,
+    timings: Res<RunnerTimings>,
+    counters: Option<NonSend<Rc<BackendCounters>>>,
+) {
+    if timings.last_update_start.is_none() {
+        return;
+    }
+    diagnostics.add_measurement(&MiniquadDiagnosticsPlugin::FRAME_TIME, || {
+        timings.frame * 1000.
+    });
+    diagnostics.add_measurement(&MiniquadDiagnosticsPlugin::UPDATE_TIME, || {
+        timings.update * 1000.
+    });
+    diagnostics.add_measurement(&MiniquadDiagnosticsPlugin::DRAW_TIME, || {
+        timings.draw * 1000.
+    });
+    let Some(counters) = counters else {
+        return;
+    };
+    diagnostics.add_measurement(&MiniquadDiagnosticsPlugin::DRAW_CALLS, || {
+        counters.draw_calls.take() as f64
+    });
+    diagnostics.add_measurement(&MiniquadDiagnosticsPlugin::BUFFERS, || {
+        counters.buffers.get() as f64
+    });
+    diagnostics.add_measurement(&MiniquadDiagnosticsPlugin::TEXTURES, || {
+        counters.textures.get() as f64
+    });
+    diagnostics.add_measurement(&MiniquadDiagnosticsPlugin::UPLOADED_BYTES, || {
+        counters.uploaded_bytes.take() as f64
+    });
+}
+
+/// Marks `TextLabel`s showing the diagnostics of the `MiniquadDiagnosticsPlugin`.
+#[cfg(feature = "text")]
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct DiagnosticsOverlay;
+
+#[cfg(feature = "text")]
+fn update_overlays(
+    store: Res<bevy_diagnostic::DiagnosticsStore>,
+    mut labels: Query<&mut crate::text::TextLabel, With<DiagnosticsOverlay>>,
+) {
+    if labels.is_empty() {
+        return;
+    }
+    let smoothed = |path: &DiagnosticPath| {
+        store
+            .get(path)
+            .and_then(|diagnostic| diagnostic.smoothed())
+            .unwrap_or_default()
+    };
+    let value = |path: &DiagnosticPath| {
+        store
+            .get(path)
+            .and_then(|diagnostic| diagnostic.value())
+            .unwrap_or_default()
+    };
+    let frame_time = smoothed(&MiniquadDiagnosticsPlugin::FRAME_TIME);
+    let text = format!(
+        "frame {:.2} ms ({:.0} fps)\nupdate {:.2} ms\ndraw {:.2} ms\ndraw calls {}\nbuffers {}, textures {}\nuploaded {:.1} KiB",
+        frame_time,
+        if frame_time > 0. { 1000. / frame_time } else { 0. },
+        smoothed(&MiniquadDiagnosticsPlugin::UPDATE_TIME),
+        smoothed(&MiniquadDiagnosticsPlugin::DRAW_TIME),
+        value(&MiniquadDiagnosticsPlugin::DRAW_CALLS),
+        value(&MiniquadDiagnosticsPlugin::BUFFERS),
+        value(&MiniquadDiagnosticsPlugin::TEXTURES),
+        value(&MiniquadDiagnosticsPlugin::UPLOADED_BYTES) / 1024.,
+    );
+    for mut label in &mut labels {
+        if label.text != text {
+            label.text.clone_from(&text);
+        }
+    }
+}
+
+/// Forwards every call to the runner's context, counting draw calls, live
+/// buffers and textures, and uploaded bytes.
+///
+/// Buffer updates from `BufferSource::slice` instead of `buffer_source::slice`
+/// are counted as the size of the whole buffer. Sizes are read without copying
+/// the data.
+struct CountingBackend {
+    inner: Box<mq::Context>,
+    counters: Rc<BackendCounters>,
+    slices: SliceReader,
+    /// Attachments of the render passes, which GL deletes with them.
+    pass_attachments: Vec<(mq::RenderPass, u64)>,
+}
+
+impl mq::RenderingBackend for CountingBackend {
+    fn info(&self) -> mq::ContextInfo {
+        self.inner.info()
+    }
+
+    fn new_shader(
+        &mut self,
+        shader: mq::ShaderSource,
+        meta: mq::ShaderMeta,
+    ) -> Result<mq::ShaderId, mq::ShaderError> {
+        self.inner.new_shader(shader, meta)
+    }
+
+    fn new_texture(
+        &mut self,
+        access: mq::TextureAccess,
+        data: mq::TextureSource,
+        params: mq::TextureParams,
+    ) -> mq::TextureId {
+        let bytes = match &data {
+            mq::TextureSource::Empty => 0,
+            mq::TextureSource::Bytes(bytes) => bytes.len(),
+            mq::TextureSource::Array(faces) => faces
+                .iter()
+                .flat_map(|levels| levels.iter())
+                .map(|level| level.len())
+                .sum(),
+        };
+        self.counters.upload(bytes);
+        self.counters.textures.set(self.counters.textures.get() + 1);
+        self.inner.new_texture(access, data, params)
+    }
+
+    fn texture_params(&self, texture: mq::TextureId) -> mq::TextureParams {
+        self.inner.texture_params(texture)
+    }
+
+    unsafe fn texture_raw_id(&self, texture: mq::TextureId) -> mq::RawId {
+        self.inner.texture_raw_id(texture)
+    }
+
+    fn texture_set_min_filter(
+        &mut self,
+        texture: mq::TextureId,
+        filter: mq::FilterMode,
+        mipmap_filter: mq::MipmapFilterMode,
+    ) {
+        self.inner
+            .texture_set_min_filter(texture, filter, mipmap_filter);
+    }
+
+    fn texture_set_mag_filter(&mut self, texture: mq::TextureId, filter: mq::FilterMode) {
+        self.inner.texture_set_mag_filter(texture, filter);
+    }
+
+    fn texture_set_wrap(
+        &mut self,
+        texture: mq::TextureId,
+        wrap_x: mq::TextureWrap,
+        wrap_y: mq::TextureWrap,
+    ) {
+        self.inner.texture_set_wrap(texture, wrap_x, wrap_y);
+    }
+
+    fn texture_generate_mipmaps(&mut self, texture: mq::TextureId) {
+        self.inner.texture_generate_mipmaps(texture);
+    }
+
+    fn texture_resize(
+        &mut self,
+        texture: mq::TextureId,
+        width: u32,
+        height: u32,
+        bytes: Option<&[u8]>,
+    ) {
+        self.counters.upload(bytes.map_or(0, <[u8]>::len));
+        self.inner.texture_resize(texture, width, height, bytes);
+    }
+
+    fn texture_read_pixels(&mut self, texture: mq::TextureId, bytes: &mut [u8]) {
+        self.inner.texture_read_pixels(texture, bytes);
+    }
+
+    fn texture_update_part(
+        &mut self,
+        texture: mq::TextureId,
+        x_offset: i32,
+        y_offset: i32,
+        width: i32,
+        height: i32,
+        bytes: &[u8],
+    ) {
+        self.counters.upload(bytes.len());
+        self.inner
+            .texture_update_part(texture, x_offset, y_offset, width, height, bytes);
+    }
+
+    fn new_render_pass_mrt(
+        &mut self,
+        color_img: &[mq::TextureId],
+        resolve_img: Option<&[mq::TextureId]>,
+        depth_img: Option<mq::TextureId>,
+    ) -> mq::RenderPass {
+        let pass = self
+            .inner
+            .new_render_pass_mrt(color_img, resolve_img, depth_img);
+        let attachments =
+            color_img.len() + resolve_img.map_or(0, <[_]>::len) + depth_img.iter().count();
+        self.pass_attachments.push((pass, attachments as u64));
+        pass
+    }
+
+    fn render_pass_color_attachments(&self, render_pass: mq::RenderPass) -> &[mq::TextureId] {
+        self.inner.render_pass_color_attachments(render_pass)
+    }
+
+    fn delete_render_pass(&mut self, render_pass: mq::RenderPass) {
+        if let Some(index) = self
+            .pass_attachments
+            .iter()
+            .position(|(pass, _)| *pass == render_pass)
+        {
+            let (_, attachments) = self.pass_attachments.swap_remove(index);
+            if self.inner.info().backend == mq::Backend::OpenGl {
+                let textures = &self.counters.textures;
+                textures.set(textures.get().saturating_sub(attachments));
+            }
+        }
+        self.inner.delete_render_pass(render_pass);
+    }
+
+    fn new_pipeline(
+        &mut self,
+        buffer_layout: &[mq::BufferLayout],
+        attributes: &[mq::VertexAttribute],
+        shader: mq::ShaderId,
+        params: mq::PipelineParams,
+    ) -> mq::Pipeline {
+        self.inner
+            .new_pipeline(buffer_layout, attributes, shader, params)
+    }
+
+    fn apply_pipeline(&mut self, pipeline: &mq::Pipeline) {
+        self.inner.apply_pipeline(pipeline);
+    }
+
+    fn delete_pipeline(&mut self, pipeline: mq::Pipeline) {
+        self.inner.delete_pipeline(pipeline);
+    }
+
+    fn new_buffer(
+        &mut self,
+        type_: mq::BufferType,
+        usage: mq::BufferUsage,
+        data: mq::BufferSource,
+    ) -> mq::BufferId {
+        let uploaded = matches!(data, mq::BufferSource::Slice(_));
+        let buffer = self.inner.new_buffer(type_, usage, data);
+        if uploaded {
+            let size = self.inner.buffer_size(buffer);
+            self.counters.upload(size);
+        }
+        self.slices.clear();
+        self.counters.buffers.set(self.counters.buffers.get() + 1);
+        buffer
+    }
+
+    fn buffer_update(&mut self, buffer: mq::BufferId, data: mq::BufferSource) {
+        let size = match self.slices.size(&data) {
+            Some(size) => size,
+            None => self.inner.buffer_size(buffer),
+        };
+        self.counters.upload(size);
+        self.inner.buffer_update(buffer, data);
+        self.slices.clear();
+    }
+
+    fn buffer_size(&mut self, buffer: mq::BufferId) -> usize {
+        self.inner.buffer_size(buffer)
+    }
+
+    fn delete_buffer(&mut self, buffer: mq::BufferId) {
+        let buffers = &self.counters.buffers;
+        buffers.set(buffers.get().saturating_sub(1));
+        self.inner.delete_buffer(buffer);
+    }
+
+    fn delete_texture(&mut self, texture: mq::TextureId) {
+        let textures = &self.counters.textures;
+        textures.set(textures.get().saturating_sub(1));
+        self.inner.delete_texture(texture);
+    }
+
+    fn delete_shader(&mut self, program: mq::ShaderId) {
+        self.inner.delete_shader(program);
+    }
+
+    fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.inner.apply_viewport(x, y, w, h);
+    }
+
+    fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.inner.apply_scissor_rect(x, y, w, h);
+    }
+
+    fn apply_bindings_from_slice(
+        &mut self,
+        vertex_buffers: &[mq::BufferId],
+        index_buffer: mq::BufferId,
+        textures: &[mq::TextureId],
+    ) {
+        self.inner
+            .apply_bindings_from_slice(vertex_buffers, index_buffer, textures);
+    }
+
+    fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
+        self.inner.apply_uniforms_from_bytes(uniform_ptr, size);
+    }
+
+    fn clear(
+        &mut self,
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) {
+        self.inner.clear(color, depth, stencil);
+    }
+
+    fn begin_default_pass(&mut self, action: mq::PassAction) {
+        self.inner.begin_default_pass(action);
+    }
+
+    fn begin_pass(&mut self, pass: Option<mq::RenderPass>, action: mq::PassAction) {
+        self.inner.begin_pass(pass, action);
+    }
+
+    fn end_render_pass(&mut self) {
+        self.inner.end_render_pass();
+    }
+
+    fn commit_frame(&mut self) {
+        self.inner.commit_frame();
+    }
+
+    fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
+        let draw_calls = &self.counters.draw_calls;
+        draw_calls.set(draw_calls.get() + 1);
+        self.inner.draw(base_element, num_elements, num_instances);
+    }
+}
@@ -0,0 +1,96 @@
+use bevy_color::Color;
+use bevy_ecs::{prelude::*, schedule::ScheduleLabel};
+use miniquad::PassAction;
+
+use crate::{
+    camera::{CameraView, ClearColorConfig},
+    converters::convert_color,
+    render_target::MainPass,
+    MiniquadContext,
+};
+
+/// Schedule run by the runner on every miniquad `draw` callback,
+/// before the `DrawFnHandle` function.
+#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Draw;
+
+/// Schedule run after `Draw` and the `DrawFnHandle` function, once the window
+/// holds the whole frame, right before it is committed.
+#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PostDraw;
+
+/// Ordering of the systems in the `Draw` schedule.
+#[derive(SystemSet, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DrawSet {
+    /// Creating and updating GPU resources.
+    Prepare,
+    /// Clearing the default pass and the camera viewports.
+    Clear,
+    /// Drawing cameras with a `RenderTarget`, before the window.
+    Offscreen,
+    /// Drawing to the `MainPass`, the window unless post-processed.
+    Main,
+    /// Drawing the `MainPass` to the window with effects.
+    PostProcess,
+}
+
+/// Color the default pass is cleared with at the start of each frame.
+#[derive(Clone, Copy, Debug, Resource)]
+pub struct ClearColor(pub Color);
+
+impl Default for ClearColor {
+    fn default() -> Self {
+        ClearColor(Color::srgb_u8(43, 44, 47))
+    }
+}
+
+/// Clears the frame, then the viewports of the active cameras in their
+/// window or render target, all before any camera draws.
+pub(crate) fn clear_system(
+    mut ctx: NonSendMut<MiniquadContext>,
+    clear_color: Res<ClearColor>,
+    main_pass: Res<MainPass>,
+    cameras: Query<&CameraView>,
+) {
+    let ctx = &mut *ctx.0;
+    let (r, g, b, a) = convert_color(clear_color.0);
+    main_pass.begin(ctx, PassAction::clear_color(r, g, b, a));
+    ctx.end_render_pass();
+
+    let mut cameras: Vec<&CameraView> = cameras.iter().filter(|view| view.is_active).collect();
+    cameras.sort_by_key(|view| view.draw_order());
+    for view in cameras {
+        let color = match view.clear_color {
+            ClearColorConfig::Default => clear_color.0,
+            ClearColorConfig::Custom(color) => color,
+            ClearColorConfig::None => continue,
+        };
+        if !view.begin_pass(ctx, PassAction::Nothing) {
+            continue;
+        }
+        let (r, g, b, a) = convert_color(color);
+        view.apply_viewport(ctx);
+        ctx.clear(Some((r, g, b, a)), Some(1.), None);
+        ctx.end_render_pass();
+    }
+}
+
+/// Source of a built-in shader for `backend`.
+#[cfg(any(
+    feature = "sprite",
+    feature = "debug-draw",
+    feature = "text",
+    feature = "egui",
+    feature = "mesh"
+))]
+pub(crate) fn builtin_shader_source(
+    backend: miniquad::Backend,
+    vertex: &'static str,
+    fragment: &'static str,
+    metal: &'static str,
+) -> miniquad::ShaderSource<'static> {
+    match backend {
+        miniquad::Backend::OpenGl => miniquad::ShaderSource::Glsl { vertex, fragment },
+        miniquad::Backend::Metal => miniquad::ShaderSource::Msl { program: metal },
+    }
+}
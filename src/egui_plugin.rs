@@ -0,0 +1,607 @@
+use bevy_app::{App, Plugin, PostUpdate, PreUpdate};
+use bevy_ecs::prelude::*;
+use bevy_input::{
+    keyboard::{Key, KeyCode, KeyboardInput},
+    mouse::{MouseButton, MouseButtonInput, MouseScrollUnit, MouseWheel},
+    ButtonState,
+};
+use bevy_math::Vec2;
+use bevy_window::CursorMoved;
+use egui::{epaint, ClippedPrimitive, TexturesDelta};
+use miniquad::{self as mq, window};
+use std::collections::HashMap;
+
+#[cfg(feature = "debug-draw")]
+use crate::debug_draw::draw_debug;
+#[cfg(feature = "text")]
+use crate::text::draw_text_labels;
+use crate::{
+    buffer_source::{self, BufferData},
+    draw::builtin_shader_source,
+    gpu::{GpuBuffer, GpuDeletionQueue, GpuPipeline, GpuTexture},
+    screenshot::take_screenshots,
+    MiniquadContext, MiniquadUniforms, MiniquadVertex, PostDraw, Window,
+};
+
+/// Runs an egui pass every frame, fed with the window input events, and paints
+/// its output over the window at the end of the frame, after any post-processing,
+/// text labels and debug shapes.
+///
+/// UI is built from `Update` systems with `EguiContext::get`.
+#[derive(Default)]
+pub struct EguiPlugin;
+
+impl Plugin for EguiPlugin {
+    fn build(&self, app: &mut App) {
+        let paint = paint_egui.before(take_screenshots);
+        #[cfg(feature = "text")]
+        let paint = paint.after(draw_text_labels);
+        #[cfg(feature = "debug-draw")]
+        let paint = paint.after(draw_debug);
+
+        app.init_resource::<EguiContext>()
+            .init_resource::<EguiOutput>()
+            .add_systems(PreUpdate, begin_egui_pass)
+            .add_systems(PostUpdate, end_egui_pass)
+            .add_systems(PostDraw, paint);
+    }
+}
+
+/// egui context of the window, between `PreUpdate` and `PostUpdate`.
+#[derive(Clone, Default, Resource)]
+pub struct EguiContext {
+    ctx: egui::Context,
+    wants_pointer_input: bool,
+    wants_keyboard_input: bool,
+}
+
+impl EguiContext {
+    pub fn get(&self) -> &egui::Context {
+        &self.ctx
+    }
+
+    /// Whether egui used the pointer in the last frame, e.g. it hovered or
+    /// dragged a window, game systems should then ignore mouse input.
+    pub fn wants_pointer_input(&self) -> bool {
+        self.wants_pointer_input
+    }
+
+    /// Whether a text field had the focus in the last frame, game systems
+    /// should then ignore key input.
+    pub fn wants_keyboard_input(&self) -> bool {
+        self.wants_keyboard_input
+    }
+}
+
+/// Output of the last egui pass, waiting to be painted.
+#[derive(Default, Resource)]
+struct EguiOutput {
+    primitives: Vec<ClippedPrimitive>,
+    textures_delta: TexturesDelta,
+    pixels_per_point: f32,
+}
+
+fn convert_key(key_code: KeyCode) -> Option<egui::Key> {
+    match key_code {
+        KeyCode::BracketLeft => Some(egui::Key::OpenBracket),
+        KeyCode::BracketRight => Some(egui::Key::CloseBracket),
+        // Other names match the ones egui parses, besides the `Key` prefix of letters.
+        _ => {
+            let name = format!("{:?}", key_code);
+            egui::Key::from_name(name.strip_prefix("Key").unwrap_or(&name))
+        }
+    }
+}
+
+fn convert_pointer_button(button: MouseButton) -> Option<egui::PointerButton> {
+    match button {
+        MouseButton::Left => Some(egui::PointerButton::Primary),
+        MouseButton::Right => Some(egui::PointerButton::Secondary),
+        MouseButton::Middle => Some(egui::PointerButton::Middle),
+        MouseButton::Back => Some(egui::PointerButton::Extra1),
+        MouseButton::Forward => Some(egui::PointerButton::Extra2),
+        MouseButton::Other(_) => None,
+    }
+}
+
+fn convert_cursor_icon(cursor_icon: egui::CursorIcon) -> mq::CursorIcon {
+    match cursor_icon {
+        egui::CursorIcon::Help => mq::CursorIcon::Help,
+        egui::CursorIcon::PointingHand => mq::CursorIcon::Pointer,
+        egui::CursorIcon::Wait | egui::CursorIcon::Progress => mq::CursorIcon::Wait,
+        egui::CursorIcon::Crosshair | egui::CursorIcon::Cell => mq::CursorIcon::Crosshair,
+        egui::CursorIcon::Text | egui::CursorIcon::VerticalText => mq::CursorIcon::Text,
+        egui::CursorIcon::Move
+        | egui::CursorIcon::AllScroll
+        | egui::CursorIcon::Grab
+        | egui::CursorIcon::Grabbing => mq::CursorIcon::Move,
+        egui::CursorIcon::NoDrop | egui::CursorIcon::NotAllowed => mq::CursorIcon::NotAllowed,
+        egui::CursorIcon::ResizeHorizontal
+        | egui::CursorIcon::ResizeEast
+        | egui::CursorIcon::ResizeWest
+        | egui::CursorIcon::ResizeColumn => mq::CursorIcon::EWResize,
+        egui::CursorIcon::ResizeVertical
+        | egui::CursorIcon::ResizeNorth
+        | egui::CursorIcon::ResizeSouth
+        | egui::CursorIcon::ResizeRow => mq::CursorIcon::NSResize,
+        egui::CursorIcon::ResizeNeSw
+        | egui::CursorIcon::ResizeNorthEast
+        | egui::CursorIcon::ResizeSouthWest => mq::CursorIcon::NESWResize,
+        egui::CursorIcon::ResizeNwSe
+        | egui::CursorIcon::ResizeNorthWest
+        | egui::CursorIcon::ResizeSouthEast => mq::CursorIcon::NWSEResize,
+        _ => mq::CursorIcon::Default,
+    }
+}
+
+fn update_modifiers(modifiers: &mut egui::Modifiers, key_code: KeyCode, pressed: bool) {
+    match key_code {
+        KeyCode::ShiftLeft | KeyCode::ShiftRight => modifiers.shift = pressed,
+        KeyCode::AltLeft | KeyCode::AltRight => modifiers.alt = pressed,
+        KeyCode::ControlLeft | KeyCode::ControlRight => {
+            modifiers.ctrl = pressed;
+            if !cfg!(target_os = "macos") {
+                modifiers.command = pressed;
+            }
+        }
+        KeyCode::SuperLeft | KeyCode::SuperRight if cfg!(target_os = "macos") => {
+            modifiers.mac_cmd = pressed;
+            modifiers.command = pressed;
+        }
+        _ => {}
+    }
+}
+
+fn begin_egui_pass(
+    egui: Res<EguiContext>,
+    window: Res<Window>,
+    mut modifiers: Local<egui::Modifiers>,
+    mut cursor_moved: EventReader<CursorMoved>,
+    mut keyboard_input: EventReader<KeyboardInput>,
+    mut mouse_button_input: EventReader<MouseButtonInput>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+) {
+    let pixels_per_point = window::dpi_scale();
+    let pointer = egui::pos2(window.cursor_x, window.cursor_y) / pixels_per_point;
+    let mut events = Vec::new();
+    if cursor_moved.read().last().is_some() {
+        events.push(egui::Event::PointerMoved(pointer));
+    }
+
+    for input in keyboard_input.read() {
+        let pressed = input.state == ButtonState::Pressed;
+        update_modifiers(&mut modifiers, input.key_code, pressed);
+
+        let physical_key = convert_key(input.key_code);
+        let key = match &input.logical_key {
+            Key::Character(text) => egui::Key::from_name(text).or(physical_key),
+            _ => physical_key,
+        };
+        if let Some(key) = key {
+            if pressed && modifiers.command {
+                match key {
+                    egui::Key::C => events.push(egui::Event::Copy),
+                    egui::Key::X => events.push(egui::Event::Cut),
+                    egui::Key::V => {
+                        if let Some(text) = window::clipboard_get() {
+                            events.push(egui::Event::Paste(text));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            events.push(egui::Event::Key {
+                key,
+                physical_key,
+                pressed,
+                repeat: input.repeat,
+                modifiers: *modifiers,
+            });
+        }
+
+        if let Key::Character(text) = &input.logical_key {
+            let printable = !text.chars().any(char::is_control);
+            if pressed && printable && !modifiers.ctrl && !modifiers.mac_cmd {
+                events.push(egui::Event::Text(text.to_string()));
+            }
+        }
+    }
+
+    for input in mouse_button_input.read() {
+        if let Some(button) = convert_pointer_button(input.button) {
+            events.push(egui::Event::PointerButton {
+                pos: pointer,
+                button,
+                pressed: input.state == ButtonState::Pressed,
+                modifiers: *modifiers,
+            });
+        }
+    }
+
+    for wheel in mouse_wheel.read() {
+        let unit = match wheel.unit {
+            MouseScrollUnit::Line => egui::MouseWheelUnit::Line,
+            MouseScrollUnit::Pixel => egui::MouseWheelUnit::Point,
+        };
+        events.push(egui::Event::MouseWheel {
+            unit,
+            delta: egui::vec2(wheel.x, wheel.y),
+            modifiers: *modifiers,
+        });
+    }
+
+    let mut raw_input = egui::RawInput {
+        screen_rect: Some(egui::Rect::from_min_size(
+            egui::Pos2::ZERO,
+            egui::vec2(window.width, window.height) / pixels_per_point,
+        )),
+        time: Some(mq::date::now()),
+        modifiers: *modifiers,
+        events,
+        focused: true,
+        ..Default::default()
+    };
+    raw_input
+        .viewports
+        .entry(egui::ViewportId::ROOT)
+        .or_default()
+        .native_pixels_per_point = Some(pixels_per_point);
+
+    egui.ctx.begin_pass(raw_input);
+}
+
+fn end_egui_pass(
+    mut egui: ResMut<EguiContext>,
+    mut output: ResMut<EguiOutput>,
+    mut cursor_icon: Local<Option<egui::CursorIcon>>,
+) {
+    let full_output = egui.ctx.end_pass();
+    egui.wants_pointer_input = egui.ctx.wants_pointer_input();
+    egui.wants_keyboard_input = egui.ctx.wants_keyboard_input();
+
+    let platform_output = full_output.platform_output;
+    if !platform_output.copied_text.is_empty() {
+        window::clipboard_set(&platform_output.copied_text);
+    }
+    if *cursor_icon != Some(platform_output.cursor_icon) {
+        *cursor_icon = Some(platform_output.cursor_icon);
+        window::show_mouse(platform_output.cursor_icon != egui::CursorIcon::None);
+        window::set_mouse_cursor(convert_cursor_icon(platform_output.cursor_icon));
+    }
+
+    // Texture changes are kept until painted, in case no frame was drawn.
+    output.textures_delta.append(full_output.textures_delta);
+    output.primitives = egui
+        .ctx
+        .tessellate(full_output.shapes, full_output.pixels_per_point);
+    output.pixels_per_point = full_output.pixels_per_point;
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, MiniquadVertex)]
+struct Vertex {
+    #[vertex(name = "in_pos")]
+    pos: [f32; 2],
+    #[vertex(name = "in_uv")]
+    uv: [f32; 2],
+    #[vertex(name = "in_color")]
+    color: [u8; 4],
+}
+
+// Safety: `repr(C)`, the 4 byte color follows the floats without padding.
+unsafe impl BufferData for Vertex {}
+
+struct EguiRenderer {
+    pipeline: GpuPipeline,
+    /// Buffers and the number of vertices and indices they hold.
+    buffers: Option<(GpuBuffer, GpuBuffer, usize, usize)>,
+    textures: HashMap<egui::TextureId, GpuTexture>,
+    vertices: Vec<Vertex>,
+    indices: Vec<u16>,
+    deletion_queue: GpuDeletionQueue,
+}
+
+impl EguiRenderer {
+    fn new(ctx: &mut mq::Context, deletion_queue: &GpuDeletionQueue) -> Self {
+        let shader = ctx
+            .new_shader(
+                builtin_shader_source(
+                    ctx.info().backend,
+                    shader::VERTEX,
+                    shader::FRAGMENT,
+                    shader::METAL,
+                ),
+                shader::Uniforms::shader_meta(&["tex"]),
+            )
+            .expect("Cannot compile egui shader");
+        // egui colors are premultiplied by alpha.
+        let pipeline = ctx.new_pipeline(
+            &[Vertex::buffer_layout()],
+            Vertex::ATTRIBUTES,
+            shader,
+            mq::PipelineParams {
+                color_blend: Some(mq::BlendState::new(
+                    mq::Equation::Add,
+                    mq::BlendFactor::One,
+                    mq::BlendFactor::OneMinusValue(mq::BlendValue::SourceAlpha),
+                )),
+                ..Default::default()
+            },
+        );
+
+        EguiRenderer {
+            pipeline: GpuPipeline::with_shader(deletion_queue, pipeline, shader),
+            buffers: None,
+            textures: HashMap::new(),
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            deletion_queue: deletion_queue.clone(),
+        }
+    }
+
+    fn set_texture(
+        &mut self,
+        ctx: &mut mq::Context,
+        id: egui::TextureId,
+        delta: &epaint::ImageDelta,
+    ) {
+        let [width, height] = delta.image.size();
+        let pixels: Vec<u8> = match &delta.image {
+            epaint::ImageData::Color(image) => image
+                .pixels
+                .iter()
+                .flat_map(|color| color.to_array())
+                .collect(),
+            epaint::ImageData::Font(image) => image
+                .srgba_pixels(None)
+                .flat_map(|color| color.to_array())
+                .collect(),
+        };
+
+        match (delta.pos, self.textures.get(&id)) {
+            (Some([x, y]), Some(texture)) => {
+                ctx.texture_update_part(
+                    **texture,
+                    x as i32,
+                    y as i32,
+                    width as i32,
+                    height as i32,
+                    &pixels,
+                );
+            }
+            _ => {
+                let filter = match delta.options.magnification {
+                    egui::TextureFilter::Nearest => mq::FilterMode::Nearest,
+                    egui::TextureFilter::Linear => mq::FilterMode::Linear,
+                };
+                let texture = ctx.new_texture_from_data_and_format(
+                    &pixels,
+                    mq::TextureParams {
+                        kind: mq::TextureKind::Texture2D,
+                        format: mq::TextureFormat::RGBA8,
+                        wrap: mq::TextureWrap::Clamp,
+                        min_filter: filter,
+                        mag_filter: filter,
+                        mipmap_filter: mq::MipmapFilterMode::None,
+                        width: width as u32,
+                        height: height as u32,
+                        allocate_mipmaps: false,
+                        sample_count: 1,
+                    },
+                );
+                // The previous texture, if any, is deleted on the next frame.
+                self.textures
+                    .insert(id, GpuTexture::new(&self.deletion_queue, texture));
+            }
+        }
+    }
+
+    /// Vertex and index buffers holding at least the given counts.
+    fn buffers(
+        &mut self,
+        ctx: &mut mq::Context,
+        vertices: usize,
+        indices: usize,
+    ) -> (mq::BufferId, mq::BufferId) {
+        let (vertex_capacity, index_capacity) = self
+            .buffers
+            .as_ref()
+            .map(|(_, _, vertex_capacity, index_capacity)| (*vertex_capacity, *index_capacity))
+            .unwrap_or((0, 0));
+        if self.buffers.is_none() || vertices > vertex_capacity || indices > index_capacity {
+            // Previous buffers are deleted on the next frame.
+            let vertex_capacity = vertices.max(vertex_capacity).max(1024).next_power_of_two();
+            let index_capacity = indices.max(index_capacity).max(4096).next_power_of_two();
+            let vertex_buffer = ctx.new_buffer(
+                mq::BufferType::VertexBuffer,
+                mq::BufferUsage::Stream,
+                mq::BufferSource::empty::<Vertex>(vertex_capacity),
+            );
+            let index_buffer = ctx.new_buffer(
+                mq::BufferType::IndexBuffer,
+                mq::BufferUsage::Stream,
+                mq::BufferSource::empty::<u16>(index_capacity),
+            );
+            self.buffers = Some((
+                GpuBuffer::new(&self.deletion_queue, vertex_buffer),
+                GpuBuffer::new(&self.deletion_queue, index_buffer),
+                vertex_capacity,
+                index_capacity,
+            ));
+        }
+        let (vertex_buffer, index_buffer, _, _) = self.buffers.as_ref().unwrap();
+        (**vertex_buffer, **index_buffer)
+    }
+
+    /// Draws a mesh of at most 65536 vertices, with 16 bit indices as 32 bit
+    /// ones need OES_element_index_uint on WebGL 1.
+    fn draw_mesh(
+        &mut self,
+        ctx: &mut mq::Context,
+        vertices: &[epaint::Vertex],
+        indices: impl Iterator<Item = u16>,
+        texture: mq::TextureId,
+    ) {
+        self.vertices.clear();
+        self.vertices.extend(vertices.iter().map(|vertex| Vertex {
+            pos: [vertex.pos.x, vertex.pos.y],
+            uv: [vertex.uv.x, vertex.uv.y],
+            color: vertex.color.to_array(),
+        }));
+        self.indices.clear();
+        self.indices.extend(indices);
+        let (vertex_buffer, index_buffer) = self.buffers(ctx, vertices.len(), self.indices.len());
+        ctx.buffer_update(vertex_buffer, buffer_source::slice(&self.vertices));
+        ctx.buffer_update(index_buffer, buffer_source::slice(&self.indices));
+        ctx.apply_bindings(&mq::Bindings {
+            vertex_buffers: vec![vertex_buffer],
+            index_buffer,
+            images: vec![texture],
+        });
+        ctx.draw(0, self.indices.len() as i32, 1);
+    }
+}
+
+fn paint_egui(
+    mut ctx: NonSendMut<MiniquadContext>,
+    deletion_queue: Res<GpuDeletionQueue>,
+    mut renderer: Local<Option<EguiRenderer>>,
+    mut output: ResMut<EguiOutput>,
+    window: Res<Window>,
+) {
+    let ctx = &mut *ctx.0;
+    let renderer = renderer.get_or_insert_with(|| EguiRenderer::new(ctx, &deletion_queue));
+    let textures_delta = std::mem::take(&mut output.textures_delta);
+    for (id, delta) in &textures_delta.set {
+        renderer.set_texture(ctx, *id, delta);
+    }
+
+    let (width, height) = (window.width, window.height);
+    let pixels_per_point = output.pixels_per_point;
+    if !output.primitives.is_empty() {
+        ctx.begin_default_pass(mq::PassAction::Nothing);
+        ctx.apply_pipeline(&renderer.pipeline);
+        ctx.apply_viewport(0, 0, width as i32, height as i32);
+        ctx.apply_uniforms(
+            shader::Uniforms {
+                screen_size: Vec2::new(width, height) / pixels_per_point,
+            }
+            .as_uniforms_source(),
+        );
+
+        for primitive in &output.primitives {
+            let epaint::Primitive::Mesh(mesh) = &primitive.primitive else {
+                continue;
+            };
+            let Some(texture) = renderer
+                .textures
+                .get(&mesh.texture_id)
+                .map(|texture| **texture)
+            else {
+                continue;
+            };
+
+            // Clip rect in pixels from the bottom left, as miniquad expects.
+            let clip = primitive.clip_rect;
+            let min_x = (clip.min.x * pixels_per_point).round().clamp(0., width) as i32;
+            let min_y = (clip.min.y * pixels_per_point).round().clamp(0., height) as i32;
+            let max_x = (clip.max.x * pixels_per_point).round().clamp(0., width) as i32;
+            let max_y = (clip.max.y * pixels_per_point).round().clamp(0., height) as i32;
+            if max_x <= min_x || max_y <= min_y {
+                continue;
+            }
+            ctx.apply_scissor_rect(min_x, height as i32 - max_y, max_x - min_x, max_y - min_y);
+
+            if mesh.vertices.len() <= 1 << 16 {
+                let indices = mesh.indices.iter().map(|index| *index as u16);
+                renderer.draw_mesh(ctx, &mesh.vertices, indices, texture);
+            } else {
+                for part in mesh.clone().split_to_u16() {
+                    let indices = part.indices.iter().copied();
+                    renderer.draw_mesh(ctx, &part.vertices, indices, texture);
+                }
+            }
+        }
+
+        ctx.end_render_pass();
+    }
+
+    for id in &textures_delta.free {
+        renderer.textures.remove(id);
+    }
+}
+
+mod shader {
+    use bevy_math::Vec2;
+
+    use crate::MiniquadUniforms;
+
+    pub const VERTEX: &str = r#"#version 100
+    attribute vec2 in_pos;
+    attribute vec2 in_uv;
+    attribute vec4 in_color;
+    uniform vec2 screen_size;
+    varying lowp vec4 color;
+    varying mediump vec2 uv;
+    void main() {
+        gl_Position = vec4(
+            2.0 * in_pos.x / screen_size.x - 1.0,
+            1.0 - 2.0 * in_pos.y / screen_size.y,
+            0.0,
+            1.0
+        );
+        color = in_color / 255.0;
+        uv = in_uv;
+    }"#;
+
+    pub const FRAGMENT: &str = r#"#version 100
+    varying lowp vec4 color;
+    varying mediump vec2 uv;
+    uniform sampler2D tex;
+    void main() {
+        gl_FragColor = color * texture2D(tex, uv);
+    }"#;
+
+    pub const METAL: &str = r#"
+    #include <metal_stdlib>
+    using namespace metal;
+
+    struct Uniforms {
+        float2 screen_size;
+    };
+
+    struct Vertex {
+        float2 in_pos [[attribute(0)]];
+        float2 in_uv [[attribute(1)]];
+        uchar4 in_color [[attribute(2)]];
+    };
+
+    struct RasterizerData {
+        float4 position [[position]];
+        float4 color [[user(locn0)]];
+        float2 uv [[user(locn1)]];
+    };
+
+    vertex RasterizerData vertexShader(Vertex v [[stage_in]], constant Uniforms& uniforms [[buffer(0)]]) {
+        RasterizerData out;
+        out.position = float4(
+            2.0 * v.in_pos.x / uniforms.screen_size.x - 1.0,
+            1.0 - 2.0 * v.in_pos.y / uniforms.screen_size.y,
+            0.0,
+            1.0
+        );
+        out.color = float4(v.in_color) / 255.0;
+        out.uv = v.in_uv;
+        return out;
+    }
+
+    fragment float4 fragmentShader(RasterizerData in [[stage_in]], texture2d<float> tex [[texture(0)]], sampler texSmplr [[sampler(0)]]) {
+        return in.color * tex.sample(texSmplr, in.uv);
+    }"#;
+
+    #[repr(C)]
+    #[derive(MiniquadUniforms)]
+    pub struct Uniforms {
+        pub screen_size: Vec2,
+    }
+}
@@ -0,0 +1,257 @@
+use bevy_ecs::prelude::*;
+use miniquad as mq;
+use std::{
+    ops::Deref,
+    sync::{Arc, Mutex},
+};
+
+use crate::MiniquadContext;
+
+/// GPU objects of dropped wrappers, deleted in the next `DrawSet::Prepare`.
+///
+/// Each App has its own queue, next to its `MiniquadContext`, and the wrappers keep
+/// a handle to the queue they were created with, so their objects are deleted by
+/// the context that created them.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct GpuDeletionQueue(Arc<Mutex<Vec<GpuObject>>>);
+
+impl GpuDeletionQueue {
+    fn push(&self, objects: impl IntoIterator<Item = GpuObject>) {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .extend(objects);
+    }
+
+    fn take(&self) -> Vec<GpuObject> {
+        std::mem::take(
+            &mut *self
+                .0
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        )
+    }
+}
+
+#[derive(Debug)]
+enum GpuObject {
+    Buffer(mq::BufferId),
+    Texture(mq::TextureId),
+    Pipeline(mq::Pipeline),
+    Shader(mq::ShaderId),
+    RenderPass(mq::RenderPass, mq::TextureId, Option<mq::TextureId>),
+}
+
+pub(crate) fn delete_dropped_gpu_objects(
+    mut ctx: NonSendMut<MiniquadContext>,
+    queue: Res<GpuDeletionQueue>,
+) {
+    let ctx = &mut *ctx.0;
+    for object in queue.take() {
+        match object {
+            GpuObject::Buffer(buffer) => ctx.delete_buffer(buffer),
+            GpuObject::Texture(texture) => ctx.delete_texture(texture),
+            GpuObject::Pipeline(pipeline) => ctx.delete_pipeline(pipeline),
+            GpuObject::Shader(shader) => ctx.delete_shader(shader),
+            GpuObject::RenderPass(pass, color, depth) => {
+                ctx.delete_render_pass(pass);
+                // The GL backend deletes the attachments with the pass, Metal does not.
+                if ctx.info().backend == mq::Backend::Metal {
+                    ctx.delete_texture(color);
+                    if let Some(depth) = depth {
+                        ctx.delete_texture(depth);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// miniquad buffer deleted when dropped, e.g. when its entity is despawned
+/// or the resource holding it is removed.
+#[derive(Component, Resource, Debug)]
+pub struct GpuBuffer {
+    buffer: mq::BufferId,
+    queue: Option<GpuDeletionQueue>,
+}
+
+impl GpuBuffer {
+    pub fn new(queue: &GpuDeletionQueue, buffer: mq::BufferId) -> Self {
+        GpuBuffer {
+            buffer,
+            queue: Some(queue.clone()),
+        }
+    }
+
+    /// Gives up ownership, the buffer is not deleted anymore.
+    pub fn into_inner(mut self) -> mq::BufferId {
+        self.queue = None;
+        self.buffer
+    }
+}
+
+impl Deref for GpuBuffer {
+    type Target = mq::BufferId;
+
+    fn deref(&self) -> &mq::BufferId {
+        &self.buffer
+    }
+}
+
+impl Drop for GpuBuffer {
+    fn drop(&mut self) {
+        if let Some(queue) = &self.queue {
+            queue.push([GpuObject::Buffer(self.buffer)]);
+        }
+    }
+}
+
+/// miniquad texture deleted when dropped, e.g. when its entity is despawned
+/// or the resource holding it is removed.
+#[derive(Component, Resource, Debug)]
+pub struct GpuTexture {
+    texture: mq::TextureId,
+    queue: Option<GpuDeletionQueue>,
+}
+
+impl GpuTexture {
+    pub fn new(queue: &GpuDeletionQueue, texture: mq::TextureId) -> Self {
+        GpuTexture {
+            texture,
+            queue: Some(queue.clone()),
+        }
+    }
+
+    /// Gives up ownership, the texture is not deleted anymore.
+    pub fn into_inner(mut self) -> mq::TextureId {
+        self.queue = None;
+        self.texture
+    }
+}
+
+impl Deref for GpuTexture {
+    type Target = mq::TextureId;
+
+    fn deref(&self) -> &mq::TextureId {
+        &self.texture
+    }
+}
+
+impl Drop for GpuTexture {
+    fn drop(&mut self) {
+        if let Some(queue) = &self.queue {
+            queue.push([GpuObject::Texture(self.texture)]);
+        }
+    }
+}
+
+/// miniquad pipeline deleted when dropped, together with its shader if owned,
+/// e.g. when its entity is despawned or the resource holding it is removed.
+#[derive(Component, Resource, Debug)]
+pub struct GpuPipeline {
+    pipeline: mq::Pipeline,
+    shader: Option<mq::ShaderId>,
+    queue: Option<GpuDeletionQueue>,
+}
+
+impl GpuPipeline {
+    pub fn new(queue: &GpuDeletionQueue, pipeline: mq::Pipeline) -> Self {
+        GpuPipeline {
+            pipeline,
+            shader: None,
+            queue: Some(queue.clone()),
+        }
+    }
+
+    /// Pipeline owning its shader, deleted with it. The shader must not be
+    /// used by other pipelines.
+    pub fn with_shader(
+        queue: &GpuDeletionQueue,
+        pipeline: mq::Pipeline,
+        shader: mq::ShaderId,
+    ) -> Self {
+        GpuPipeline {
+            pipeline,
+            shader: Some(shader),
+            queue: Some(queue.clone()),
+        }
+    }
+
+    pub fn shader(&self) -> Option<mq::ShaderId> {
+        self.shader
+    }
+
+    /// Gives up ownership, the pipeline and shader are not deleted anymore.
+    pub fn into_inner(mut self) -> (mq::Pipeline, Option<mq::ShaderId>) {
+        self.queue = None;
+        (self.pipeline, self.shader)
+    }
+}
+
+impl Deref for GpuPipeline {
+    type Target = mq::Pipeline;
+
+    fn deref(&self) -> &mq::Pipeline {
+        &self.pipeline
+    }
+}
+
+impl Drop for GpuPipeline {
+    fn drop(&mut self) {
+        if let Some(queue) = &self.queue {
+            queue.push(
+                std::iter::once(GpuObject::Pipeline(self.pipeline))
+                    .chain(self.shader.map(GpuObject::Shader)),
+            );
+        }
+    }
+}
+
+/// miniquad render pass deleted with its attachments when dropped.
+#[derive(Component, Resource, Debug)]
+pub struct GpuRenderPass {
+    pass: mq::RenderPass,
+    color: mq::TextureId,
+    depth: Option<mq::TextureId>,
+    queue: GpuDeletionQueue,
+}
+
+impl GpuRenderPass {
+    /// Creates the pass, owning the `color` and `depth` textures from now on.
+    pub fn new(
+        ctx: &mut mq::Context,
+        queue: &GpuDeletionQueue,
+        color: mq::TextureId,
+        depth: Option<mq::TextureId>,
+    ) -> Self {
+        GpuRenderPass {
+            pass: ctx.new_render_pass(color, depth),
+            color,
+            depth,
+            queue: queue.clone(),
+        }
+    }
+
+    pub fn texture(&self) -> mq::TextureId {
+        self.color
+    }
+
+    pub fn depth_texture(&self) -> Option<mq::TextureId> {
+        self.depth
+    }
+}
+
+impl Deref for GpuRenderPass {
+    type Target = mq::RenderPass;
+
+    fn deref(&self) -> &mq::RenderPass {
+        &self.pass
+    }
+}
+
+impl Drop for GpuRenderPass {
+    fn drop(&mut self) {
+        self.queue
+            .push([GpuObject::RenderPass(self.pass, self.color, self.depth)]);
+    }
+}
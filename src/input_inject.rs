@@ -0,0 +1,95 @@
+use bevy_ecs::prelude::*;
+use miniquad::{KeyCode, KeyMods, MouseButton, TouchPhase};
+
+use crate::input_record::InputEvent;
+
+/// Window events queued by systems, e.g. for automation or bots.
+///
+/// At the start of the next frame the runner handles them in order, before
+/// the `Update`, as if miniquad had sent them: they go through the same
+/// translation to bevy input events. An `InputRecorder` does not record them,
+/// the systems sending them send them again when the recording is replayed.
+#[derive(Debug, Default, Resource)]
+pub struct InputInjector {
+    events: Vec<InputEvent>,
+}
+
+impl InputInjector {
+    pub fn send(&mut self, event: InputEvent) {
+        self.events.push(event);
+    }
+
+    pub fn key_down(&mut self, key_code: KeyCode, modifiers: KeyMods) {
+        self.send(InputEvent::KeyDown {
+            key_code,
+            modifiers,
+            repeat: false,
+        });
+    }
+
+    pub fn key_up(&mut self, key_code: KeyCode, modifiers: KeyMods) {
+        self.send(InputEvent::KeyUp {
+            key_code,
+            modifiers,
+        });
+    }
+
+    /// Sends the character typed by the last key down, which printable keys
+    /// need to be reported.
+    pub fn char(&mut self, character: char, modifiers: KeyMods) {
+        self.send(InputEvent::Char {
+            character,
+            modifiers,
+            repeat: false,
+        });
+    }
+
+    /// Key down, then `character` if the key is printable, then key up.
+    pub fn key_press(&mut self, key_code: KeyCode, character: Option<char>, modifiers: KeyMods) {
+        self.key_down(key_code, modifiers);
+        if let Some(character) = character {
+            self.char(character, modifiers);
+        }
+        self.key_up(key_code, modifiers);
+    }
+
+    /// Moves the cursor to window pixels `x`, `y`.
+    pub fn mouse_move(&mut self, x: f32, y: f32) {
+        self.send(InputEvent::MouseMotion { x, y });
+    }
+
+    pub fn mouse_down(&mut self, button: MouseButton, x: f32, y: f32) {
+        self.send(InputEvent::MouseButtonDown { button, x, y });
+    }
+
+    pub fn mouse_up(&mut self, button: MouseButton, x: f32, y: f32) {
+        self.send(InputEvent::MouseButtonUp { button, x, y });
+    }
+
+    /// Moves the cursor to `x`, `y`, then presses and releases `button`.
+    pub fn click(&mut self, button: MouseButton, x: f32, y: f32) {
+        self.mouse_move(x, y);
+        self.mouse_down(button, x, y);
+        self.mouse_up(button, x, y);
+    }
+
+    pub fn mouse_wheel(&mut self, x: f32, y: f32) {
+        self.send(InputEvent::MouseWheel { x, y });
+    }
+
+    pub fn touch(&mut self, phase: TouchPhase, id: u64, x: f32, y: f32) {
+        self.send(InputEvent::Touch { phase, id, x, y });
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub(crate) fn take(&mut self) -> Vec<InputEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
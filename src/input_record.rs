@@ -0,0 +1,669 @@
+use bevy_ecs::prelude::*;
+use miniquad::{KeyCode, KeyMods, MouseButton, TouchPhase};
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Window event as received from miniquad by the runner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputEvent {
+    Char {
+        character: char,
+        modifiers: KeyMods,
+        repeat: bool,
+    },
+    KeyDown {
+        key_code: KeyCode,
+        modifiers: KeyMods,
+        repeat: bool,
+    },
+    KeyUp {
+        key_code: KeyCode,
+        modifiers: KeyMods,
+    },
+    MouseMotion {
+        x: f32,
+        y: f32,
+    },
+    MouseWheel {
+        x: f32,
+        y: f32,
+    },
+    MouseButtonDown {
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    },
+    MouseButtonUp {
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    },
+    RawMouseMotion {
+        dx: f32,
+        dy: f32,
+    },
+    Touch {
+        phase: TouchPhase,
+        id: u64,
+        x: f32,
+        y: f32,
+    },
+    Resize {
+        width: f32,
+        height: f32,
+    },
+}
+
+/// Event of an `InputRecording`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RecordedEvent {
+    /// Frame counted from the start of the recording, the event is handled
+    /// in its `Update`.
+    pub frame: u64,
+    /// Seconds since the start of the recording.
+    pub time: f64,
+    pub event: InputEvent,
+}
+
+/// Window events of a session, with the window size it started with.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputRecording {
+    pub width: f32,
+    pub height: f32,
+    pub dpi_scale: f32,
+    pub events: Vec<RecordedEvent>,
+}
+
+/// Recording files hold the header, then each event as its frame and microsecond
+/// deltas from the previous one, a tag byte and its fields.
+const MAGIC: &[u8; 4] = b"MQIN";
+const VERSION: u8 = 1;
+
+impl InputRecording {
+    /// Number of frames up to the last event.
+    pub fn frames(&self) -> u64 {
+        self.events.last().map_or(0, |event| event.frame + 1)
+    }
+
+    /// Fails with `InvalidInput`, before writing anything, if the frame or the
+    /// time of an event is before the previous one's.
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        let backwards = self.events.windows(2).find_map(|pair| {
+            if pair[1].frame < pair[0].frame {
+                Some("frame")
+            } else if pair[1].time < pair[0].time {
+                Some("time")
+            } else {
+                None
+            }
+        });
+        if let Some(field) = backwards {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("event {} goes backwards", field),
+            ));
+        }
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        write_f32(writer, self.width)?;
+        write_f32(writer, self.height)?;
+        write_f32(writer, self.dpi_scale)?;
+        write_varint(writer, self.events.len() as u64)?;
+        let (mut frame, mut micros) = (0, 0);
+        for recorded in &self.events {
+            let event_micros = (recorded.time * 1e6).round().max(0.) as u64;
+            write_varint(writer, recorded.frame - frame)?;
+            write_varint(writer, event_micros.saturating_sub(micros))?;
+            (frame, micros) = (recorded.frame, event_micros.max(micros));
+            write_event(writer, &recorded.event)?;
+        }
+        Ok(())
+    }
+
+    pub fn read(reader: &mut impl Read) -> Result<Self, InputRecordingError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(InputRecordingError::Format("not an input recording"));
+        }
+        let version = read_u8(reader)?;
+        if version != VERSION {
+            return Err(InputRecordingError::Version(version));
+        }
+        let mut recording = InputRecording {
+            width: read_f32(reader)?,
+            height: read_f32(reader)?,
+            dpi_scale: read_f32(reader)?,
+            events: Vec::new(),
+        };
+        let len = read_varint(reader)?;
+        let (mut frame, mut micros) = (0u64, 0u64);
+        for _ in 0..len {
+            frame = frame
+                .checked_add(read_varint(reader)?)
+                .ok_or(InputRecordingError::Format("frame out of range"))?;
+            micros = micros
+                .checked_add(read_varint(reader)?)
+                .ok_or(InputRecordingError::Format("time out of range"))?;
+            recording.events.push(RecordedEvent {
+                frame,
+                time: micros as f64 / 1e6,
+                event: read_event(reader)?,
+            });
+        }
+        Ok(recording)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = io::BufWriter::new(std::fs::File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, InputRecordingError> {
+        Self::read(&mut io::BufReader::new(std::fs::File::open(path)?))
+    }
+}
+
+#[derive(Debug)]
+pub enum InputRecordingError {
+    Io(io::Error),
+    Version(u8),
+    Format(&'static str),
+}
+
+impl From<io::Error> for InputRecordingError {
+    fn from(error: io::Error) -> Self {
+        InputRecordingError::Io(error)
+    }
+}
+
+impl fmt::Display for InputRecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputRecordingError::Io(error) => write!(f, "Cannot read input recording: {}", error),
+            InputRecordingError::Version(version) => {
+                write!(f, "Unsupported input recording version {}", version)
+            }
+            InputRecordingError::Format(error) => {
+                write!(f, "Cannot parse input recording: {}", error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InputRecordingError {}
+
+/// Records the window events while it exists, from the frame it is inserted on.
+#[derive(Debug, Default, Resource)]
+pub struct InputRecorder {
+    recording: InputRecording,
+    /// Runner frame and time the recording started at.
+    start: Option<(u64, f64)>,
+    path: Option<PathBuf>,
+}
+
+impl InputRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Saves the recording to `path` on `AppExit`.
+    pub fn with_path(path: impl Into<PathBuf>) -> Self {
+        InputRecorder {
+            path: Some(path.into()),
+            ..Self::default()
+        }
+    }
+
+    pub fn recording(&self) -> &InputRecording {
+        &self.recording
+    }
+
+    /// Takes the events recorded so far, keeping on recording.
+    pub fn take(&mut self) -> InputRecording {
+        let events = std::mem::take(&mut self.recording.events);
+        InputRecording {
+            events,
+            ..self.recording.clone()
+        }
+    }
+
+    pub(crate) fn start(&mut self, frame: u64, width: f32, height: f32, dpi_scale: f32) {
+        if self.start.is_none() {
+            self.start = Some((frame, miniquad::date::now()));
+            (
+                self.recording.width,
+                self.recording.height,
+                self.recording.dpi_scale,
+            ) = (width, height, dpi_scale);
+        }
+    }
+
+    pub(crate) fn push(&mut self, frame: u64, event: InputEvent) {
+        if let Some((start_frame, start_time)) = self.start {
+            self.recording.events.push(RecordedEvent {
+                frame: frame - start_frame,
+                time: miniquad::date::now() - start_time,
+                event,
+            });
+        }
+    }
+
+    pub(crate) fn save_to_path(&self) {
+        if let Some(path) = &self.path {
+            match self.recording.save(path) {
+                Ok(()) => log::info!("Input recording saved to {}", path.display()),
+                Err(error) => log::error!("Cannot save input recording: {}", error),
+            }
+        }
+    }
+}
+
+/// Makes `miniquad_runner` replay a recording without a window instead of
+/// opening one: each frame gets the events recorded for it, then is updated with
+/// a fixed `Time` step and drawn. The context is a `MockRenderingBackend` unless
+/// the app already has a `MiniquadContext`.
+///
+/// Plugins calling miniquad window functions themselves, like egui's clipboard,
+/// cannot run while replaying.
+#[derive(Clone, Debug, Resource)]
+pub struct InputReplay {
+    pub recording: InputRecording,
+    pub time_step: Duration,
+    /// Frames run after the last event, e.g. to let its effects show.
+    pub extra_frames: u64,
+}
+
+impl InputReplay {
+    pub fn new(recording: InputRecording) -> Self {
+        InputReplay {
+            recording,
+            time_step: Duration::from_secs_f64(1. / 60.),
+            extra_frames: 1,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, InputRecordingError> {
+        InputRecording::load(path).map(Self::new)
+    }
+
+    pub fn with_time_step(self, time_step: Duration) -> Self {
+        InputReplay { time_step, ..self }
+    }
+
+    pub fn with_extra_frames(self, extra_frames: u64) -> Self {
+        InputReplay {
+            extra_frames,
+            ..self
+        }
+    }
+}
+
+/// Marks apps run by the replay, whose systems must not call miniquad window functions.
+#[derive(Resource)]
+pub(crate) struct Headless;
+
+/// Every miniquad key code, to decode them.
+#[rustfmt::skip]
+const KEY_CODES: [KeyCode; 122] = {
+    use KeyCode::*;
+    [
+        Space, Apostrophe, Comma, Minus, Period, Slash, Key0, Key1, Key2, Key3, Key4, Key5, Key6,
+        Key7, Key8, Key9, Semicolon, Equal, A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R,
+        S, T, U, V, W, X, Y, Z, LeftBracket, Backslash, RightBracket, GraveAccent, World1, World2,
+        Escape, Enter, Tab, Backspace, Insert, Delete, Right, Left, Down, Up, PageUp, PageDown,
+        Home, End, CapsLock, ScrollLock, NumLock, PrintScreen, Pause, F1, F2, F3, F4, F5, F6, F7,
+        F8, F9, F10, F11, F12, F13, F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24, F25,
+        Kp0, Kp1, Kp2, Kp3, Kp4, Kp5, Kp6, Kp7, Kp8, Kp9, KpDecimal, KpDivide, KpMultiply,
+        KpSubtract, KpAdd, KpEnter, KpEqual, LeftShift, LeftControl, LeftAlt, LeftSuper,
+        RightShift, RightControl, RightAlt, RightSuper, Menu, Back, Unknown,
+    ]
+};
+
+fn write_event(writer: &mut impl Write, event: &InputEvent) -> io::Result<()> {
+    match *event {
+        InputEvent::Char {
+            character,
+            modifiers,
+            repeat,
+        } => {
+            writer.write_all(&[0, encode_modifiers(modifiers, repeat)])?;
+            write_varint(writer, character as u64)
+        }
+        InputEvent::KeyDown {
+            key_code,
+            modifiers,
+            repeat,
+        } => {
+            writer.write_all(&[1, encode_modifiers(modifiers, repeat)])?;
+            writer.write_all(&(key_code as u16).to_le_bytes())
+        }
+        InputEvent::KeyUp {
+            key_code,
+            modifiers,
+        } => {
+            writer.write_all(&[2, encode_modifiers(modifiers, false)])?;
+            writer.write_all(&(key_code as u16).to_le_bytes())
+        }
+        InputEvent::MouseMotion { x, y } => write_tagged_pair(writer, 3, x, y),
+        InputEvent::MouseWheel { x, y } => write_tagged_pair(writer, 4, x, y),
+        InputEvent::MouseButtonDown { button, x, y } => {
+            writer.write_all(&[5, button as u8])?;
+            write_f32(writer, x)?;
+            write_f32(writer, y)
+        }
+        InputEvent::MouseButtonUp { button, x, y } => {
+            writer.write_all(&[6, button as u8])?;
+            write_f32(writer, x)?;
+            write_f32(writer, y)
+        }
+        InputEvent::RawMouseMotion { dx, dy } => write_tagged_pair(writer, 7, dx, dy),
+        InputEvent::Touch { phase, id, x, y } => {
+            let phase = match phase {
+                TouchPhase::Started => 0,
+                TouchPhase::Moved => 1,
+                TouchPhase::Ended => 2,
+                TouchPhase::Cancelled => 3,
+            };
+            writer.write_all(&[8, phase])?;
+            write_varint(writer, id)?;
+            write_f32(writer, x)?;
+            write_f32(writer, y)
+        }
+        InputEvent::Resize { width, height } => write_tagged_pair(writer, 9, width, height),
+    }
+}
+
+fn read_event(reader: &mut impl Read) -> Result<InputEvent, InputRecordingError> {
+    Ok(match read_u8(reader)? {
+        0 => {
+            let (modifiers, repeat) = decode_modifiers(read_u8(reader)?);
+            let character = char::from_u32(read_varint(reader)? as u32)
+                .ok_or(InputRecordingError::Format("invalid character"))?;
+            InputEvent::Char {
+                character,
+                modifiers,
+                repeat,
+            }
+        }
+        tag @ (1 | 2) => {
+            let (modifiers, repeat) = decode_modifiers(read_u8(reader)?);
+            let mut bytes = [0; 2];
+            reader.read_exact(&mut bytes)?;
+            let code = u16::from_le_bytes(bytes);
+            let key_code = *KEY_CODES
+                .iter()
+                .find(|key_code| **key_code as u16 == code)
+                .ok_or(InputRecordingError::Format("invalid key code"))?;
+            match tag {
+                1 => InputEvent::KeyDown {
+                    key_code,
+                    modifiers,
+                    repeat,
+                },
+                _ => InputEvent::KeyUp {
+                    key_code,
+                    modifiers,
+                },
+            }
+        }
+        3 => InputEvent::MouseMotion {
+            x: read_f32(reader)?,
+            y: read_f32(reader)?,
+        },
+        4 => InputEvent::MouseWheel {
+            x: read_f32(reader)?,
+            y: read_f32(reader)?,
+        },
+        tag @ (5 | 6) => {
+            let button = match read_u8(reader)? {
+                0 => MouseButton::Left,
+                1 => MouseButton::Middle,
+                2 => MouseButton::Right,
+                _ => MouseButton::Unknown,
+            };
+            let (x, y) = (read_f32(reader)?, read_f32(reader)?);
+            match tag {
+                5 => InputEvent::MouseButtonDown { button, x, y },
+                _ => InputEvent::MouseButtonUp { button, x, y },
+            }
+        }
+        7 => InputEvent::RawMouseMotion {
+            dx: read_f32(reader)?,
+            dy: read_f32(reader)?,
+        },
+        8 => {
+            let phase = match read_u8(reader)? {
+                0 => TouchPhase::Started,
+                1 => TouchPhase::Moved,
+                2 => TouchPhase::Ended,
+                3 => TouchPhase::Cancelled,
+                _ => return Err(InputRecordingError::Format("invalid touch phase")),
+            };
+            InputEvent::Touch {
+                phase,
+                id: read_varint(reader)?,
+                x: read_f32(reader)?,
+                y: read_f32(reader)?,
+            }
+        }
+        9 => InputEvent::Resize {
+            width: read_f32(reader)?,
+            height: read_f32(reader)?,
+        },
+        _ => return Err(InputRecordingError::Format("invalid event")),
+    })
+}
+
+fn encode_modifiers(modifiers: KeyMods, repeat: bool) -> u8 {
+    modifiers.shift as u8
+        | (modifiers.ctrl as u8) << 1
+        | (modifiers.alt as u8) << 2
+        | (modifiers.logo as u8) << 3
+        | (repeat as u8) << 4
+}
+
+fn decode_modifiers(bits: u8) -> (KeyMods, bool) {
+    let modifiers = KeyMods {
+        shift: bits & 1 != 0,
+        ctrl: bits & 1 << 1 != 0,
+        alt: bits & 1 << 2 != 0,
+        logo: bits & 1 << 3 != 0,
+    };
+    (modifiers, bits & 1 << 4 != 0)
+}
+
+fn write_tagged_pair(writer: &mut impl Write, tag: u8, a: f32, b: f32) -> io::Result<()> {
+    writer.write_all(&[tag])?;
+    write_f32(writer, a)?;
+    write_f32(writer, b)
+}
+
+fn write_f32(writer: &mut impl Write, value: f32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+/// LEB128, 7 bits per byte with the high bit set on all bytes but the last.
+fn write_varint(writer: &mut impl Write, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_f32(reader: &mut impl Read) -> io::Result<f32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(f32::from_le_bytes(bytes))
+}
+
+fn read_varint(reader: &mut impl Read) -> Result<u64, InputRecordingError> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = read_u8(reader)?;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(InputRecordingError::Format("invalid varint"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(recording: &InputRecording) -> Result<InputRecording, InputRecordingError> {
+        let mut bytes = Vec::new();
+        recording.write(&mut bytes).unwrap();
+        InputRecording::read(&mut bytes.as_slice())
+    }
+
+    #[test]
+    fn every_event_round_trips() {
+        let modifiers = KeyMods {
+            shift: true,
+            ctrl: false,
+            alt: true,
+            logo: true,
+        };
+        let mut events = vec![
+            InputEvent::Char {
+                character: '\u{1f980}',
+                modifiers,
+                repeat: true,
+            },
+            InputEvent::MouseMotion { x: 1.5, y: -2. },
+            InputEvent::MouseWheel { x: 0., y: -1. },
+            InputEvent::RawMouseMotion { dx: -0.25, dy: 3. },
+            InputEvent::Resize {
+                width: 800.,
+                height: 600.,
+            },
+        ];
+        events.extend(KEY_CODES.iter().flat_map(|&key_code| {
+            [
+                InputEvent::KeyDown {
+                    key_code,
+                    modifiers,
+                    repeat: false,
+                },
+                InputEvent::KeyUp {
+                    key_code,
+                    modifiers: KeyMods::default(),
+                },
+            ]
+        }));
+        let buttons = [
+            MouseButton::Left,
+            MouseButton::Middle,
+            MouseButton::Right,
+            MouseButton::Unknown,
+        ];
+        events.extend(buttons.iter().flat_map(|&button| {
+            [
+                InputEvent::MouseButtonDown {
+                    button,
+                    x: 4.,
+                    y: 5.,
+                },
+                InputEvent::MouseButtonUp {
+                    button,
+                    x: 6.,
+                    y: 7.,
+                },
+            ]
+        }));
+        let phases = [
+            TouchPhase::Started,
+            TouchPhase::Moved,
+            TouchPhase::Ended,
+            TouchPhase::Cancelled,
+        ];
+        events.extend(phases.iter().map(|&phase| InputEvent::Touch {
+            phase,
+            id: u64::MAX,
+            x: 8.,
+            y: 9.,
+        }));
+        let recording = InputRecording {
+            width: 320.,
+            height: 240.,
+            dpi_scale: 2.,
+            events: events
+                .into_iter()
+                .enumerate()
+                .map(|(index, event)| RecordedEvent {
+                    frame: index as u64 / 3,
+                    time: index as f64 * 0.25,
+                    event,
+                })
+                .collect(),
+        };
+
+        assert_eq!(round_trip(&recording).unwrap(), recording);
+    }
+
+    #[test]
+    fn frames_past_u64_are_rejected() {
+        let event = |frame| RecordedEvent {
+            frame,
+            time: 0.,
+            event: InputEvent::MouseMotion { x: 0., y: 0. },
+        };
+        let recording = InputRecording {
+            events: vec![event(u64::MAX), event(u64::MAX)],
+            ..Default::default()
+        };
+        let mut bytes = Vec::new();
+        recording.write(&mut bytes).unwrap();
+        // Make the second event one frame after the first.
+        let second = bytes.len() - 11;
+        assert_eq!(bytes[second], 0);
+        bytes[second] = 1;
+
+        assert!(matches!(
+            InputRecording::read(&mut bytes.as_slice()),
+            Err(InputRecordingError::Format("frame out of range"))
+        ));
+    }
+
+    #[test]
+    fn events_going_backwards_are_not_written() {
+        let event = |frame, time| RecordedEvent {
+            frame,
+            time,
+            event: InputEvent::MouseMotion { x: 0., y: 0. },
+        };
+        for events in [
+            vec![event(2, 0.), event(1, 0.5)],
+            vec![event(1, 0.5), event(1, 0.25)],
+        ] {
+            let recording = InputRecording {
+                events,
+                ..Default::default()
+            };
+            let mut bytes = Vec::new();
+            let error = recording.write(&mut bytes).unwrap_err();
+
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+            assert!(bytes.is_empty());
+        }
+    }
+}
@@ -1,32 +1,129 @@
 use bevy_app::{App, AppExit, Plugin, Update};
-use bevy_ecs::{entity::Entity, event::EventReader, prelude::Resource};
+use bevy_ecs::{
+    entity::Entity,
+    event::EventReader,
+    prelude::{not, resource_exists, IntoSystemConfigs, IntoSystemSetConfigs, Res, Resource, With},
+};
 use bevy_input::{
     keyboard::{Key, KeyboardInput},
     mouse::{MouseButtonInput, MouseMotion, MouseScrollUnit, MouseWheel},
     ButtonState,
 };
 use bevy_math::Vec2;
+use bevy_time::TimeUpdateStrategy;
 use bevy_window::{
-    CursorMoved, Window as WindowComponent, WindowMode, WindowPlugin, WindowResized,
+    CursorMoved, PrimaryWindow, Window as WindowComponent, WindowPlugin, WindowResized,
     WindowResolution,
 };
-use miniquad::{conf, window, EventHandler, KeyCode, KeyMods, MouseButton};
+use miniquad::{conf, window, EventHandler, KeyCode, KeyMods, MouseButton, TouchPhase};
 use std::sync::Arc;
+use tracing::info_span;
+
+// Lets the derive macros refer to `::bevy_miniquad` from inside this crate too.
+extern crate self as bevy_miniquad;
 
 pub use ::miniquad::Context;
 pub mod miniquad {
     pub use miniquad::*;
 }
 
+#[cfg(feature = "log-impl")]
+mod log_plugin;
+
 #[cfg(feature = "log-impl")]
 pub mod log {
+    pub use crate::log_plugin::MiniquadLogPlugin;
     pub use miniquad::{debug, error, info, log, trace, warn};
 }
 
+pub mod uniforms;
+pub mod vertex;
+pub use bevy_miniquad_derive::{MiniquadUniforms, MiniquadVertex};
+pub use uniforms::MiniquadUniforms;
+pub use vertex::MiniquadVertex;
+
 mod converters;
 use converters::*;
 
-pub type DrawFn = Arc<Box<dyn Fn(&mut App) -> () + Send + Sync>>;
+pub mod anchor;
+
+pub mod camera;
+use camera::CameraPlugin;
+
+pub mod gpu;
+use gpu::{delete_dropped_gpu_objects, GpuDeletionQueue};
+
+pub mod pipeline_cache;
+use pipeline_cache::{create_queued_pipelines, PipelineCache};
+
+pub mod mock;
+
+#[cfg(feature = "soft-render")]
+pub mod soft_render;
+
+pub mod buffer_source;
+
+pub mod input_record;
+use input_record::{Headless, InputEvent, InputRecorder, InputReplay};
+
+pub mod input_inject;
+use input_inject::InputInjector;
+
+pub mod render_queue;
+use render_queue::{draw_render_queue, RenderQueue, RenderQueueStats};
+
+mod draw;
+use draw::*;
+pub use draw::{ClearColor, Draw, DrawSet, PostDraw};
+
+pub mod render_target;
+use render_target::RenderTargetPlugin;
+
+pub mod screenshot;
+use screenshot::ScreenshotPlugin;
+
+#[cfg(feature = "sprite")]
+pub mod sprite;
+
+#[cfg(feature = "debug-draw")]
+pub mod debug_draw;
+
+#[cfg(feature = "text")]
+pub mod text;
+
+#[cfg(feature = "mesh")]
+pub mod mesh;
+
+#[cfg(feature = "egui")]
+mod egui_plugin;
+
+/// egui, with the plugin drawing it, so apps don't need to depend on it.
+#[cfg(feature = "egui")]
+pub mod egui {
+    pub use crate::egui_plugin::{EguiContext, EguiPlugin};
+    pub use ::egui::*;
+}
+
+#[cfg(feature = "image")]
+pub mod texture;
+
+#[cfg(feature = "shader")]
+pub mod shader;
+
+#[cfg(feature = "post-process")]
+pub mod post_process;
+
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+
+mod window_config;
+use window_config::*;
+pub use window_config::{constrain_window_size, window_mode_fullscreen};
+
+/// Called on every frame between the `Draw` and `PostDraw` schedules.
+///
+/// The runner commits the frame afterwards, the function must not call `commit_frame`.
+pub type DrawFn = Arc<Box<dyn Fn(&mut App) + Send + Sync>>;
 
 pub struct DrawFnHandle(pub DrawFn);
 
@@ -48,13 +145,73 @@ pub struct MiniquadPlugin;
 impl Plugin for MiniquadPlugin {
     fn build(&self, app: &mut App) {
         app.set_runner(miniquad_runner);
-        app.add_plugins(WindowPlugin::default());
-        app.add_systems(Update, app_exit_system);
+        app.add_plugins((
+            WindowPlugin::default(),
+            CameraPlugin,
+            RenderTargetPlugin,
+            ScreenshotPlugin,
+        ));
+        app.init_schedule(Draw)
+            .init_schedule(PostDraw)
+            .configure_sets(
+                Draw,
+                (
+                    DrawSet::Prepare,
+                    DrawSet::Clear,
+                    DrawSet::Offscreen,
+                    DrawSet::Main,
+                    DrawSet::PostProcess,
+                )
+                    .chain(),
+            )
+            .init_resource::<ClearColor>()
+            .init_resource::<GpuDeletionQueue>()
+            .init_resource::<PipelineCache>()
+            .init_resource::<RenderQueue>()
+            .init_resource::<RenderQueueStats>()
+            .init_resource::<InputInjector>()
+            .add_systems(
+                Draw,
+                (
+                    (delete_dropped_gpu_objects, create_queued_pipelines)
+                        .chain()
+                        .in_set(DrawSet::Prepare),
+                    clear_system.in_set(DrawSet::Clear),
+                ),
+            );
+        let draw_render_queue = draw_render_queue.in_set(DrawSet::Main);
+        #[cfg(feature = "mesh")]
+        let draw_render_queue = draw_render_queue.after(mesh::draw_meshes::<false>);
+        #[cfg(feature = "sprite")]
+        let draw_render_queue = draw_render_queue.before(sprite::draw_sprites::<false>);
+        app.add_systems(Draw, draw_render_queue);
+        app.add_systems(
+            Update,
+            (
+                app_exit_system,
+                (
+                    window_mode_system,
+                    window_position_system,
+                    window_size_system,
+                )
+                    .run_if(not(resource_exists::<Headless>)),
+            ),
+        );
     }
 }
 
-fn app_exit_system(mut event_reader: EventReader<AppExit>) {
-    for _app_exit_event in event_reader.read() {
+fn app_exit_system(
+    mut event_reader: EventReader<AppExit>,
+    recorder: Option<Res<InputRecorder>>,
+    headless: Option<Res<Headless>>,
+) {
+    if event_reader.read().last().is_none() {
+        return;
+    }
+    if let Some(recorder) = recorder {
+        recorder.save_to_path();
+    }
+    if headless.is_none() {
         window::request_quit();
     }
 }
@@ -70,196 +227,458 @@ impl Window {
 }
 
 pub fn miniquad_runner(mut app: App) -> AppExit {
+    if let Some(replay) = app.world().get_resource::<InputReplay>().cloned() {
+        return replay_runner(app, replay);
+    }
+
     log::debug!("Entering miniquad event loop");
 
+    let primary_window = app
+        .world_mut()
+        .query_filtered::<Entity, With<PrimaryWindow>>()
+        .iter(app.world())
+        .next();
+
     let mut conf = conf::Conf::default();
-    let mut window_copy = None;
+    if let Some(window) =
+        primary_window.and_then(|entity| app.world().get::<WindowComponent>(entity))
     {
-        let window_settings = app.get_added_plugins::<WindowPlugin>();
-        for settings in &window_settings {
-            if let Some(ref window) = settings.primary_window {
-                window_copy = Some(window.clone());
-                conf.window_title = window.title.clone();
-                conf.window_width = window.resolution.width() as i32;
-                conf.window_height = window.resolution.height() as i32;
-                conf.fullscreen = match window.mode {
-                    WindowMode::Windowed => false,
-                    WindowMode::BorderlessFullscreen(_)
-                    | WindowMode::SizedFullscreen(_)
-                    | WindowMode::Fullscreen(_) => true,
-                };
-            }
-        }
+        configure(&mut conf, window);
     }
 
-    miniquad::start(conf, || {
+    miniquad::start(conf, move || {
         let ctx: Box<Context> = window::new_rendering_backend();
+        if ctx.info().backend == miniquad::Backend::OpenGl {
+            app.insert_non_send_resource(screenshot::WindowReadback::Gl);
+        }
         app.insert_non_send_resource(MiniquadContext(ctx));
 
         let (width, height) = window::screen_size();
-        let scale = window::dpi_scale();
-
-        let window = window_copy.unwrap_or_else(|| WindowComponent {
-            resolution: WindowResolution::new(width as f32, height as f32)
-                .with_scale_factor_override(scale),
-            ..Default::default()
-        });
-        let entity = app.world_mut().spawn(window).id();
-        app.world_mut().insert_resource(Window::new(width, height));
+        let entity = init_primary_window(&mut app, width, height, window::dpi_scale());
+        configure_created(app.world().get::<WindowComponent>(entity).unwrap());
 
         app.finish();
 
-        Box::new(Stage::new(app, entity))
+        Box::new(Stage::new(app, entity, false))
     });
 
     AppExit::Success
 }
 
+/// Runs the frames of an `InputReplay` without a window.
+fn replay_runner(mut app: App, replay: InputReplay) -> AppExit {
+    log::debug!("Replaying {} input events", replay.recording.events.len());
+
+    if !app.world().contains_non_send::<MiniquadContext>() {
+        app.insert_non_send_resource(MiniquadContext(Box::new(mock::MockRenderingBackend::new())));
+    }
+    let recording = &replay.recording;
+    let entity = init_primary_window(
+        &mut app,
+        recording.width,
+        recording.height,
+        recording.dpi_scale,
+    );
+    app.insert_resource(Headless)
+        .insert_resource(TimeUpdateStrategy::ManualDuration(replay.time_step));
+
+    app.finish();
+
+    let mut stage = Stage::new(app, entity, true);
+    let mut events = recording.events.iter().peekable();
+    for frame in 0..recording.frames() + replay.extra_frames {
+        while let Some(recorded) = events.next_if(|recorded| recorded.frame == frame) {
+            stage.input_event(recorded.event);
+        }
+        stage.update();
+        stage.draw();
+        if let Some(exit) = stage.app.should_exit() {
+            return exit;
+        }
+    }
+
+    AppExit::Success
+}
+
+/// Gets or spawns the primary window entity, sized as the miniquad window.
+fn init_primary_window(app: &mut App, width: f32, height: f32, scale: f32) -> Entity {
+    let primary_window = app
+        .world_mut()
+        .query_filtered::<Entity, With<PrimaryWindow>>()
+        .iter(app.world())
+        .next();
+    let entity = match primary_window {
+        Some(entity) => entity,
+        None => app
+            .world_mut()
+            .spawn((
+                WindowComponent {
+                    resolution: WindowResolution::new(width, height)
+                        .with_scale_factor_override(scale),
+                    ..Default::default()
+                },
+                PrimaryWindow,
+            ))
+            .id(),
+    };
+    let applied = {
+        let mut window = app.world_mut().get_mut::<WindowComponent>(entity).unwrap();
+        window
+            .resolution
+            .set_physical_resolution(width as u32, height as u32);
+        AppliedWindowSettings::new(&window)
+    };
+    app.world_mut().insert_resource(applied);
+    app.world_mut().insert_resource(Window::new(width, height));
+    entity
+}
+
 struct Stage {
     app: App,
     window_entity: Entity,
     last_printable_char: Option<char>,
     last_key_code: Option<KeyCode>,
+    /// Updates run so far.
+    frame: u64,
+    /// Replaying without a window, miniquad window functions must not be called.
+    headless: bool,
 }
 
 impl Stage {
-    pub fn new(app: App, window_entity: Entity) -> Self {
-        Stage {
+    pub fn new(app: App, window_entity: Entity, headless: bool) -> Self {
+        let mut stage = Stage {
             app,
             window_entity,
             last_printable_char: None,
             last_key_code: None,
-        }
+            frame: 0,
+            headless,
+        };
+        stage.start_recorder();
+        stage
     }
-}
 
-impl EventHandler for Stage {
-    fn char_event(&mut self, character: char, _keymods: KeyMods, repeat: bool) {
-        // println!("char_event");
-        let input_event = KeyboardInput {
-            logical_key: Key::Character(character.to_string().into()),
-            window: self.window_entity,
-            state: ButtonState::Pressed,
-            key_code: convert_virtual_key_code(self.last_key_code.unwrap()).unwrap(),
-            repeat,
+    /// Starts an `InputRecorder` inserted since the last frame.
+    fn start_recorder(&mut self) {
+        let world = self.app.world_mut();
+        let Some(scale) = world
+            .get::<WindowComponent>(self.window_entity)
+            .map(|window| window.resolution.scale_factor())
+        else {
+            return;
         };
-        //println!("{:?}", input_event);
-        self.app.world_mut().send_event(input_event);
-        self.last_printable_char = Some(character);
+        let (width, height) = {
+            let window = world.resource::<Window>();
+            (window.width, window.height)
+        };
+        if let Some(mut recorder) = world.get_resource_mut::<InputRecorder>() {
+            recorder.start(self.frame, width, height, scale);
+        }
     }
 
-    fn key_down_event(&mut self, keycode: KeyCode, _keymods: KeyMods, repeat: bool) {
-        // println!("key_down_event");
-        self.last_key_code = Some(keycode);
-        let key_code = convert_virtual_key_code(keycode).unwrap();
-        if key_code_is_printable(key_code) {
-            // Wait for the next char event instead.
+    /// Records the event, then translates it to bevy events.
+    fn input_event(&mut self, event: InputEvent) {
+        if let Some(mut recorder) = self.app.world_mut().get_resource_mut::<InputRecorder>() {
+            recorder.push(self.frame, event);
+        }
+        self.translate_input_event(event);
+    }
+
+    fn translate_input_event(&mut self, event: InputEvent) {
+        match event {
+            InputEvent::Char {
+                character, repeat, ..
+            } => {
+                // Injected chars may come without a key down.
+                let key_code = convert_key_code(self.last_key_code.unwrap_or(KeyCode::Unknown));
+                let input_event = KeyboardInput {
+                    logical_key: Key::Character(character.to_string().into()),
+                    window: self.window_entity,
+                    state: ButtonState::Pressed,
+                    key_code,
+                    repeat,
+                };
+                self.app.world_mut().send_event(input_event);
+                self.last_printable_char = Some(character);
+            }
+            InputEvent::KeyDown {
+                key_code: keycode,
+                repeat,
+                ..
+            } => {
+                self.last_key_code = Some(keycode);
+                let key_code = convert_key_code(keycode);
+                if key_code_is_printable(key_code) {
+                    // Wait for the next char event instead.
+                    return;
+                }
+                let Some(logical_key) = key_code_to_unprintable_logical_key(key_code) else {
+                    log::debug!("Skipping key down of {:?} without a logical key", keycode);
+                    return;
+                };
+
+                let input_event = KeyboardInput {
+                    logical_key,
+                    window: self.window_entity,
+                    state: ButtonState::Pressed,
+                    key_code,
+                    repeat,
+                };
+                self.app.world_mut().send_event(input_event);
+            }
+            InputEvent::KeyUp {
+                key_code: keycode, ..
+            } => {
+                let key_code = convert_key_code(keycode);
+                let logical_key = if key_code_is_printable(key_code) {
+                    self.last_printable_char
+                        .map(|character| Key::Character(character.to_string().into()))
+                } else {
+                    key_code_to_unprintable_logical_key(key_code)
+                };
+                let Some(logical_key) = logical_key else {
+                    log::debug!("Skipping key up of {:?} without a logical key", keycode);
+                    return;
+                };
+                let input_event = KeyboardInput {
+                    logical_key,
+                    window: self.window_entity,
+                    state: ButtonState::Released,
+                    key_code,
+                    repeat: false,
+                };
+                self.app.world_mut().send_event(input_event);
+            }
+            InputEvent::MouseMotion { x, y } => {
+                let mut window = self.app.world_mut().get_resource_mut::<Window>().unwrap();
+                let delta_x = x - window.cursor_x;
+                let delta_y = y - window.cursor_y;
+                window.cursor_x = x;
+                window.cursor_y = y;
+
+                self.app.world_mut().send_event(CursorMoved {
+                    window: self.window_entity,
+                    position: Vec2::new(x, y),
+                    delta: Some(Vec2::new(delta_x, delta_y)),
+                });
+            }
+            InputEvent::MouseWheel { x, y } => {
+                self.app.world_mut().send_event(MouseWheel {
+                    window: self.window_entity,
+                    unit: MouseScrollUnit::Line,
+                    x,
+                    y,
+                });
+            }
+            InputEvent::MouseButtonDown { button, .. } => {
+                self.app.world_mut().send_event(MouseButtonInput {
+                    window: self.window_entity,
+                    button: convert_mouse_button(button),
+                    state: ButtonState::Pressed,
+                });
+            }
+            InputEvent::MouseButtonUp { button, .. } => {
+                self.app.world_mut().send_event(MouseButtonInput {
+                    window: self.window_entity,
+                    button: convert_mouse_button(button),
+                    state: ButtonState::Released,
+                });
+            }
+            InputEvent::RawMouseMotion { dx, dy } => {
+                self.app.world_mut().send_event(MouseMotion {
+                    delta: Vec2::new(dx, dy),
+                });
+            }
+            // Emulates the mouse, as miniquad does by default.
+            InputEvent::Touch { phase, x, y, .. } => {
+                let event = match phase {
+                    TouchPhase::Started => InputEvent::MouseButtonDown {
+                        button: MouseButton::Left,
+                        x,
+                        y,
+                    },
+                    TouchPhase::Ended => InputEvent::MouseButtonUp {
+                        button: MouseButton::Left,
+                        x,
+                        y,
+                    },
+                    TouchPhase::Moved => InputEvent::MouseMotion { x, y },
+                    TouchPhase::Cancelled => return,
+                };
+                self.translate_input_event(event);
+            }
+            InputEvent::Resize { width, height } => self.resize(width, height),
+        }
+    }
+
+    fn resize(&mut self, width: f32, height: f32) {
+        let world = self.app.world_mut();
+        let mode = world.resource::<AppliedWindowSettings>().mode;
+
+        if !window_mode_fullscreen(mode) && !self.headless {
+            if let Some(window) = world.get::<WindowComponent>(self.window_entity) {
+                let (constrained_width, constrained_height) =
+                    constrain_window_size(window, width, height);
+                let constrained_size = (constrained_width as u32, constrained_height as u32);
+                let mut applied = world.resource_mut::<AppliedWindowSettings>();
+                if (constrained_width, constrained_height) != (width, height)
+                    && applied.constrained_size != Some(constrained_size)
+                {
+                    applied.constrained_size = Some(constrained_size);
+                    window::set_window_size(constrained_size.0, constrained_size.1);
+                    return;
+                }
+            }
+        }
+
+        let mut applied = world.resource_mut::<AppliedWindowSettings>();
+        applied.resize_pending = false;
+        applied.constrained_size = None;
+        if !window_mode_fullscreen(mode) {
+            applied.windowed_size = (width as u32, height as u32);
+        }
+
+        let mut window = world.resource_mut::<Window>();
+        if window.width == width && window.height == height {
             return;
         }
+        window.width = width;
+        window.height = height;
 
-        let input_event = KeyboardInput {
-            logical_key: key_code_to_unprintable_logical_key(key_code).unwrap(),
+        if let Some(mut window) = world.get_mut::<WindowComponent>(self.window_entity) {
+            window
+                .resolution
+                .set_physical_resolution(width as u32, height as u32);
+        }
+
+        world.send_event(WindowResized {
             window: self.window_entity,
-            state: ButtonState::Pressed,
-            key_code,
+            width,
+            height,
+        });
+    }
+}
+
+impl EventHandler for Stage {
+    fn char_event(&mut self, character: char, keymods: KeyMods, repeat: bool) {
+        let _span = info_span!("char_event").entered();
+        self.input_event(InputEvent::Char {
+            character,
+            modifiers: keymods,
             repeat,
-        };
-        //println!("{:?}", input_event);
-        self.app.world_mut().send_event(input_event);
+        });
     }
-    fn key_up_event(&mut self, keycode: KeyCode, _keymods: KeyMods) {
-        // println!("key_up_event");
-        let key_code = convert_virtual_key_code(keycode).unwrap();
-        let logical_key = if key_code_is_printable(key_code) {
-            Key::Character(self.last_printable_char.unwrap().to_string().into())
-        } else {
-            key_code_to_unprintable_logical_key(key_code).unwrap()
-        };
-        let input_event = KeyboardInput {
-            logical_key,
-            window: self.window_entity,
-            state: ButtonState::Released,
-            key_code,
-            repeat: false,
-        };
-        //println!("{:?}", input_event);
-        self.app.world_mut().send_event(input_event);
+
+    fn key_down_event(&mut self, keycode: KeyCode, keymods: KeyMods, repeat: bool) {
+        let _span = info_span!("key_down_event").entered();
+        self.input_event(InputEvent::KeyDown {
+            key_code: keycode,
+            modifiers: keymods,
+            repeat,
+        });
+    }
+    fn key_up_event(&mut self, keycode: KeyCode, keymods: KeyMods) {
+        let _span = info_span!("key_up_event").entered();
+        self.input_event(InputEvent::KeyUp {
+            key_code: keycode,
+            modifiers: keymods,
+        });
     }
 
     fn mouse_motion_event(&mut self, x: f32, y: f32) {
-        // println!("mouse_motion_event {} {}", x, y);
-        let mut window = self.app.world_mut().get_resource_mut::<Window>().unwrap();
-        let delta_x = x - window.cursor_x;
-        let delta_y = y - window.cursor_y;
-        window.cursor_x = x;
-        window.cursor_y = y;
-
-        self.app.world_mut().send_event(CursorMoved {
-            window: self.window_entity,
-            position: Vec2::new(x, y),
-            delta: Some(Vec2::new(delta_x, delta_y)),
-        });
+        let _span = info_span!("mouse_motion_event").entered();
+        self.input_event(InputEvent::MouseMotion { x, y });
     }
     fn mouse_wheel_event(&mut self, x: f32, y: f32) {
-        // println!("mouse_wheel_event {} {}", x, y);
-        self.app.world_mut().send_event(MouseWheel {
-            window: self.window_entity,
-            unit: MouseScrollUnit::Line,
-            x,
-            y,
-        });
+        let _span = info_span!("mouse_wheel_event").entered();
+        self.input_event(InputEvent::MouseWheel { x, y });
     }
-    fn mouse_button_down_event(&mut self, button: MouseButton, _x: f32, _y: f32) {
-        // println!("mouse_button_down_event");
-        self.app.world_mut().send_event(MouseButtonInput {
-            window: self.window_entity,
-            button: convert_mouse_button(button),
-            state: ButtonState::Pressed,
-        });
+    fn mouse_button_down_event(&mut self, button: MouseButton, x: f32, y: f32) {
+        let _span = info_span!("mouse_button_down_event").entered();
+        self.input_event(InputEvent::MouseButtonDown { button, x, y });
     }
-    fn mouse_button_up_event(&mut self, button: MouseButton, _x: f32, _y: f32) {
-        // println!("mouse_button_up_event");
-        self.app.world_mut().send_event(MouseButtonInput {
-            window: self.window_entity,
-            button: convert_mouse_button(button),
-            state: ButtonState::Released,
-        });
+    fn mouse_button_up_event(&mut self, button: MouseButton, x: f32, y: f32) {
+        let _span = info_span!("mouse_button_up_event").entered();
+        self.input_event(InputEvent::MouseButtonUp { button, x, y });
     }
     fn raw_mouse_motion(&mut self, dx: f32, dy: f32) {
-        // println!("raw_mouse_motion {} {}", dx, dy);
-        self.app.world_mut().send_event(MouseMotion {
-            delta: Vec2::new(dx, dy),
-        });
+        let _span = info_span!("raw_mouse_motion").entered();
+        self.input_event(InputEvent::RawMouseMotion { dx, dy });
+    }
+    fn touch_event(&mut self, phase: TouchPhase, id: u64, x: f32, y: f32) {
+        let _span = info_span!("touch_event").entered();
+        self.input_event(InputEvent::Touch { phase, id, x, y });
     }
 
     fn resize_event(&mut self, width: f32, height: f32) {
-        println!("resize_event {} {}", width, height);
-        let mut window = self.app.world_mut().get_resource_mut::<Window>().unwrap();
-        window.width = width;
-        window.height = height;
-
-        self.app.world_mut().send_event(WindowResized {
-            window: self.window_entity,
-            width: width,
-            height: height,
-        });
+        let _span = info_span!("resize_event", width, height).entered();
+        self.input_event(InputEvent::Resize { width, height });
     }
 
     fn update(&mut self) {
+        let _span = info_span!("update").entered();
+        #[cfg(feature = "diagnostics")]
+        let start = miniquad::date::now();
+
+        // Not recorded, the systems injecting them do it again on replay.
+        let injected = self.app.world_mut().resource_mut::<InputInjector>().take();
+        for event in injected {
+            self.translate_input_event(event);
+        }
         self.app.update();
+
+        #[cfg(feature = "diagnostics")]
+        diagnostics::RunnerTimings::record_update(self.app.world_mut(), start);
+        self.frame += 1;
+        self.start_recorder();
+
+        if self.headless {
+            return;
+        }
+
+        if self
+            .app
+            .world()
+            .resource::<AppliedWindowSettings>()
+            .resize_pending
+        {
+            let (width, height) = window::screen_size();
+            let window = self.app.world().resource::<Window>();
+            if window.width != width || window.height != height {
+                self.resize_event(width, height);
+            }
+        }
+
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
+        poll_window_position(self.app.world_mut(), self.window_entity);
     }
 
     fn draw(&mut self) {
-        // println!("draw");
-        let draw_function = {
-            let fn_ref = self
-                .app
-                .world()
-                .get_resource::<DrawFnHandle>()
-                .expect("Cannot find draw function resource");
-            (*fn_ref).0.clone()
-        };
+        let _span = info_span!("draw").entered();
+        #[cfg(feature = "diagnostics")]
+        let start = miniquad::date::now();
+
+        info_span!("draw_schedule").in_scope(|| self.app.world_mut().run_schedule(Draw));
+
+        let draw_function = self
+            .app
+            .world()
+            .get_resource::<DrawFnHandle>()
+            .map(|fn_ref| fn_ref.0.clone());
+        if let Some(draw_function) = draw_function {
+            info_span!("draw_function").in_scope(|| draw_function(&mut self.app));
+        }
+        info_span!("post_draw_schedule").in_scope(|| self.app.world_mut().run_schedule(PostDraw));
+
+        info_span!("commit_frame").in_scope(|| {
+            self.app
+                .world_mut()
+                .non_send_resource_mut::<MiniquadContext>()
+                .0
+                .commit_frame()
+        });
 
-        draw_function(&mut self.app);
+        #[cfg(feature = "diagnostics")]
+        diagnostics::RunnerTimings::record_draw(self.app.world_mut(), start);
     }
 }
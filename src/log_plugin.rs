@@ -0,0 +1,275 @@
+use bevy_app::{App, Plugin};
+use std::{
+    fmt::{self, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tracing::{
+    field::{Field, Visit},
+    level_filters::LevelFilter,
+    span,
+    subscriber::Interest,
+    Event, Level, Metadata, Subscriber,
+};
+
+/// Sends the records of the `log` and `tracing` facades, from bevy and other
+/// crates, to miniquad's platform logger: the browser console, logcat on Android,
+/// the system log on iOS and stderr elsewhere.
+///
+/// Replaces bevy's `LogPlugin`, only one of them can be added. Spans are not
+/// recorded, so no profiler layer like Tracy is installed.
+pub struct MiniquadLogPlugin {
+    /// Most verbose level logged, for targets without a filter.
+    pub level: Level,
+    /// Comma separated `target=level` directives, e.g. `"wgpu=error,my_game::ai=trace"`,
+    /// the longest matching target winning. `off` silences a target.
+    pub filter: String,
+}
+
+impl Default for MiniquadLogPlugin {
+    fn default() -> Self {
+        MiniquadLogPlugin {
+            level: Level::INFO,
+            filter: "wgpu=error,naga=warn".to_string(),
+        }
+    }
+}
+
+impl Plugin for MiniquadLogPlugin {
+    fn build(&self, _app: &mut App) {
+        let (filter, invalid_directives) = TargetFilter::new(self.level, &self.filter);
+        let filter = Arc::new(filter);
+
+        let max_level = match filter.max_level().into_level() {
+            None => log::LevelFilter::Off,
+            Some(Level::ERROR) => log::LevelFilter::Error,
+            Some(Level::WARN) => log::LevelFilter::Warn,
+            Some(Level::INFO) => log::LevelFilter::Info,
+            Some(Level::DEBUG) => log::LevelFilter::Debug,
+            Some(_) => log::LevelFilter::Trace,
+        };
+        let logger_set = log::set_boxed_logger(Box::new(MiniquadLogger(filter.clone()))).is_ok();
+        if logger_set {
+            log::set_max_level(max_level);
+        }
+        let subscriber_set = tracing::subscriber::set_global_default(MiniquadSubscriber {
+            filter,
+            next_span: AtomicU64::new(1),
+        })
+        .is_ok();
+
+        if !logger_set || !subscriber_set {
+            log::warn!("A logger is already set, MiniquadLogPlugin only replaces bevy's LogPlugin");
+        }
+        for directive in invalid_directives {
+            log::warn!("Ignoring invalid log filter directive {:?}", directive);
+        }
+    }
+}
+
+struct TargetFilter {
+    level: LevelFilter,
+    /// Sorted by decreasing target length, so the first match is the longest.
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl TargetFilter {
+    /// Filter of the directives, with the invalid ones it skipped.
+    fn new(level: Level, filter: &str) -> (Self, Vec<String>) {
+        let mut target_filter = TargetFilter {
+            level: LevelFilter::from_level(level),
+            targets: Vec::new(),
+        };
+        let mut invalid_directives = Vec::new();
+        for directive in filter_directives(filter) {
+            match directive {
+                Ok((Some(target), level)) => target_filter.targets.push((target, level)),
+                Ok((None, level)) => target_filter.level = level,
+                Err(directive) => invalid_directives.push(directive),
+            }
+        }
+        target_filter
+            .targets
+            .sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+        (target_filter, invalid_directives)
+    }
+
+    fn level(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .find(|(prefix, _)| {
+                target.starts_with(prefix.as_str())
+                    && (target.len() == prefix.len() || target[prefix.len()..].starts_with("::"))
+            })
+            .map_or(self.level, |(_, level)| *level)
+    }
+
+    fn enabled(&self, target: &str, level: Level) -> bool {
+        level <= self.level(target)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.level, LevelFilter::max)
+    }
+}
+
+/// `target=level` or `level` directives, invalid ones as errors.
+fn filter_directives(
+    filter: &str,
+) -> impl Iterator<Item = Result<(Option<String>, LevelFilter), String>> + '_ {
+    filter
+        .split(',')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .map(|directive| {
+            let (target, level) = match directive.split_once('=') {
+                Some((target, level)) => (Some(target.trim().to_string()), level.trim()),
+                None => (None, directive),
+            };
+            level
+                .parse::<LevelFilter>()
+                .map(|level| (target, level))
+                .map_err(|_| directive.to_string())
+        })
+}
+
+/// Line logged for a record. miniquad hands it to C on wasm and Android, where
+/// a NUL byte would panic, so they are escaped.
+fn log_line(level: Level, target: &str, message: &str) -> String {
+    format!("{:>5} {}: {}", level, target, message).replace('\0', "\\0")
+}
+
+fn send(level: Level, target: &str, message: &str) {
+    let miniquad_level = match level {
+        Level::ERROR => miniquad::log::Level::Error,
+        Level::WARN => miniquad::log::Level::Warn,
+        Level::INFO => miniquad::log::Level::Info,
+        Level::DEBUG => miniquad::log::Level::Debug,
+        _ => miniquad::log::Level::Trace,
+    };
+    miniquad::log!(
+        target: target,
+        miniquad_level,
+        "{}",
+        log_line(level, target, message)
+    );
+}
+
+struct MiniquadLogger(Arc<TargetFilter>);
+
+fn level_from_log(level: log::Level) -> Level {
+    match level {
+        log::Level::Error => Level::ERROR,
+        log::Level::Warn => Level::WARN,
+        log::Level::Info => Level::INFO,
+        log::Level::Debug => Level::DEBUG,
+        log::Level::Trace => Level::TRACE,
+    }
+}
+
+impl log::Log for MiniquadLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.0
+            .enabled(metadata.target(), level_from_log(metadata.level()))
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            send(
+                level_from_log(record.level()),
+                record.target(),
+                &record.args().to_string(),
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Logs events, spans only get ids.
+struct MiniquadSubscriber {
+    filter: Arc<TargetFilter>,
+    next_span: AtomicU64,
+}
+
+impl Subscriber for MiniquadSubscriber {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if self.enabled(metadata) {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.filter.enabled(metadata.target(), *metadata.level())
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(self.filter.max_level())
+    }
+
+    fn new_span(&self, _span: &span::Attributes<'_>) -> span::Id {
+        span::Id::from_u64(self.next_span.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut message = MessageVisitor(String::new());
+        event.record(&mut message);
+        let metadata = event.metadata();
+        send(*metadata.level(), metadata.target(), &message.0);
+    }
+
+    fn enter(&self, _span: &span::Id) {}
+
+    fn exit(&self, _span: &span::Id) {}
+}
+
+/// Formats the message of an event followed by its other fields.
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let separator = if self.0.is_empty() { "" } else { " " };
+        let _ = match field.name() {
+            "message" => write!(self.0, "{}{:?}", separator, value),
+            name => write!(self.0, "{}{}={:?}", separator, name, value),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_keeps_valid_directives() {
+        let (filter, invalid) =
+            TargetFilter::new(Level::INFO, "wgpu=error, my_game::ai=trace,loud,warn");
+
+        assert_eq!(invalid, ["loud"]);
+        assert_eq!(filter.level("my_game"), LevelFilter::WARN);
+        assert_eq!(filter.level("my_game::ai::path"), LevelFilter::TRACE);
+        assert_eq!(filter.level("my_game::air"), LevelFilter::WARN);
+        assert_eq!(filter.level("wgpu_core"), LevelFilter::WARN);
+        assert_eq!(filter.level("wgpu::device"), LevelFilter::ERROR);
+        assert_eq!(filter.max_level(), LevelFilter::TRACE);
+    }
+
+    #[test]
+    fn log_line_escapes_nul_bytes() {
+        let line = log_line(Level::WARN, "my\0game", "bad\0name");
+
+        assert_eq!(line, " WARN my\\0game: bad\\0name");
+        assert!(std::ffi::CString::new(line).is_ok());
+    }
+}
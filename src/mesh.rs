@@ -0,0 +1,783 @@
+use bevy_app::{App, Plugin};
+use bevy_asset::{Asset, AssetApp, AssetEvent, AssetId, Assets, Handle};
+use bevy_color::{Color, LinearRgba};
+use bevy_ecs::prelude::*;
+use bevy_math::{Mat3, Mat4, Vec3, Vec4};
+use bevy_mesh::{PrimitiveTopology, VertexAttributeValues};
+use bevy_reflect::TypePath;
+use bevy_transform::components::{GlobalTransform, Transform};
+use miniquad as mq;
+use std::collections::HashMap;
+
+pub use bevy_mesh::{Indices, Mesh, MeshBuilder, Meshable};
+
+#[cfg(feature = "sprite")]
+use crate::sprite::draw_sprites;
+#[cfg(feature = "image")]
+use crate::texture::GpuTextures;
+#[cfg(feature = "image")]
+use bevy_image::Image;
+
+use crate::{
+    buffer_source::{self, BufferData},
+    camera::{CameraView, MiniquadCamera3d},
+    draw::builtin_shader_source,
+    gpu::{GpuBuffer, GpuDeletionQueue, GpuPipeline, GpuTexture},
+    Draw, DrawSet, MiniquadContext, MiniquadUniforms, MiniquadVertex,
+};
+
+/// Uploads `Mesh` assets and draws entities with a `Mesh3d` and `GlobalTransform`
+/// with every active `MiniquadCamera3d`, lit by the `DirectionalLight`, `PointLight`
+/// and `AmbientLight` with Blinn-Phong shading.
+///
+/// Meshes are drawn before sprites, so 2D cameras can draw over 3D ones.
+/// Registers the `Mesh` asset when no other plugin did, bevy's `AssetPlugin`
+/// needs to be added first.
+#[derive(Default)]
+pub struct MiniquadMeshPlugin;
+
+impl Plugin for MiniquadMeshPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world().contains_resource::<Assets<Mesh>>() {
+            app.init_asset::<Mesh>();
+        }
+
+        let draw_offscreen = draw_meshes::<true>.in_set(DrawSet::Offscreen);
+        let draw_main = draw_meshes::<false>.in_set(DrawSet::Main);
+        #[cfg(feature = "sprite")]
+        let (draw_offscreen, draw_main) = (
+            draw_offscreen.before(draw_sprites::<true>),
+            draw_main.before(draw_sprites::<false>),
+        );
+
+        app.init_asset::<MeshMaterial>()
+            .init_resource::<GpuMeshes>()
+            .init_resource::<AmbientLight>()
+            .add_systems(
+                Draw,
+                (
+                    (prepare_mesh_renderer, prepare_meshes).in_set(DrawSet::Prepare),
+                    draw_offscreen,
+                    draw_main,
+                ),
+            );
+    }
+}
+
+/// Mesh drawn by the entity, with its `MeshMaterial3d` or a white material.
+#[derive(Component, Clone, Debug, Default)]
+#[require(Transform)]
+pub struct Mesh3d(pub Handle<Mesh>);
+
+#[derive(Component, Clone, Debug, Default)]
+pub struct MeshMaterial3d(pub Handle<MeshMaterial>);
+
+/// Blinn-Phong material of a `Mesh3d`.
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct MeshMaterial {
+    pub base_color: Color,
+    /// Multiplied with `base_color`, the image is skipped until it is uploaded.
+    #[cfg(feature = "image")]
+    pub base_color_texture: Option<Handle<Image>>,
+    /// Specular exponent, higher values give smaller highlights.
+    pub shininess: f32,
+    pub specular_strength: f32,
+    /// Ignores the lights and draws `base_color` as is.
+    pub unlit: bool,
+    /// Draws back faces too, which are culled otherwise.
+    pub double_sided: bool,
+    /// Blends with what is behind using the `base_color` alpha, drawn after
+    /// opaque meshes from back to front.
+    pub alpha_blend: bool,
+}
+
+impl Default for MeshMaterial {
+    fn default() -> Self {
+        MeshMaterial {
+            base_color: Color::WHITE,
+            #[cfg(feature = "image")]
+            base_color_texture: None,
+            shininess: 32.,
+            specular_strength: 0.5,
+            unlit: false,
+            double_sided: false,
+            alpha_blend: false,
+        }
+    }
+}
+
+impl From<Color> for MeshMaterial {
+    fn from(base_color: Color) -> Self {
+        MeshMaterial {
+            base_color,
+            ..Default::default()
+        }
+    }
+}
+
+/// Light coming from infinitely far away along the entity forward direction,
+/// like the sun.
+#[derive(Component, Clone, Copy, Debug)]
+#[require(Transform)]
+pub struct DirectionalLight {
+    pub color: Color,
+    pub illuminance: f32,
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        DirectionalLight {
+            color: Color::WHITE,
+            illuminance: 1.,
+        }
+    }
+}
+
+/// Light emitted in every direction from the entity position, fading out to
+/// nothing at `range`.
+#[derive(Component, Clone, Copy, Debug)]
+#[require(Transform)]
+pub struct PointLight {
+    pub color: Color,
+    pub intensity: f32,
+    pub range: f32,
+}
+
+impl Default for PointLight {
+    fn default() -> Self {
+        PointLight {
+            color: Color::WHITE,
+            intensity: 1.,
+            range: 20.,
+        }
+    }
+}
+
+/// Light reaching every surface, from every direction.
+#[derive(Clone, Copy, Debug, Resource)]
+pub struct AmbientLight {
+    pub color: Color,
+    pub brightness: f32,
+}
+
+impl Default for AmbientLight {
+    fn default() -> Self {
+        AmbientLight {
+            color: Color::WHITE,
+            brightness: 0.1,
+        }
+    }
+}
+
+/// Lights used by each camera, the first directional lights and the point
+/// lights closest to the camera.
+const MAX_DIRECTIONAL_LIGHTS: usize = 2;
+const MAX_POINT_LIGHTS: usize = 4;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, MiniquadVertex)]
+struct Vertex {
+    #[vertex(name = "in_position")]
+    position: [f32; 3],
+    #[vertex(name = "in_normal")]
+    normal: [f32; 3],
+    #[vertex(name = "in_uv")]
+    uv: [f32; 2],
+}
+
+// Safety: `repr(C)` struct of floats only, it has no padding.
+unsafe impl BufferData for Vertex {}
+
+/// Buffers of an uploaded `Mesh`.
+#[derive(Debug)]
+pub struct GpuMesh {
+    pub vertex_buffer: GpuBuffer,
+    pub index_buffer: GpuBuffer,
+    pub index_count: usize,
+}
+
+/// miniquad buffers of the loaded `Mesh` assets.
+///
+/// Buffers are created in `DrawSet::Prepare` of the frame a mesh is added,
+/// recreated when it is modified and deleted when it is removed.
+/// Only triangle lists are supported, normals are computed when missing.
+#[derive(Debug, Default, Resource)]
+pub struct GpuMeshes {
+    meshes: HashMap<AssetId<Mesh>, GpuMesh>,
+}
+
+impl GpuMeshes {
+    pub fn get(&self, id: impl Into<AssetId<Mesh>>) -> Option<&GpuMesh> {
+        self.meshes.get(&id.into())
+    }
+
+    pub fn len(&self) -> usize {
+        self.meshes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.meshes.is_empty()
+    }
+}
+
+fn upload_mesh(
+    ctx: &mut mq::Context,
+    deletion_queue: &GpuDeletionQueue,
+    mesh: &Mesh,
+) -> Option<GpuMesh> {
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        log::warn!(
+            "Mesh topology {:?} is not supported, only triangle lists are",
+            mesh.primitive_topology()
+        );
+        return None;
+    }
+    if !matches!(
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION),
+        Some(VertexAttributeValues::Float32x3(_))
+    ) {
+        log::warn!("Mesh has no Float32x3 positions");
+        return None;
+    }
+    let with_normals;
+    let mesh = if mesh.contains_attribute(Mesh::ATTRIBUTE_NORMAL) {
+        mesh
+    } else {
+        with_normals = {
+            let mut mesh = mesh.clone();
+            mesh.compute_normals();
+            mesh
+        };
+        &with_normals
+    };
+
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return None;
+    };
+    let normals = match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+        Some(VertexAttributeValues::Float32x3(normals)) => normals.as_slice(),
+        _ => &[],
+    };
+    let uvs = match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+        Some(VertexAttributeValues::Float32x2(uvs)) => uvs.as_slice(),
+        _ => &[],
+    };
+    let vertices: Vec<Vertex> = positions
+        .iter()
+        .enumerate()
+        .map(|(index, position)| Vertex {
+            position: *position,
+            normal: normals.get(index).copied().unwrap_or([0., 1., 0.]),
+            uv: uvs.get(index).copied().unwrap_or_default(),
+        })
+        .collect();
+
+    let vertex_buffer = ctx.new_buffer(
+        mq::BufferType::VertexBuffer,
+        mq::BufferUsage::Immutable,
+        buffer_source::slice(&vertices),
+    );
+    let (index_buffer, index_count) = match mesh.indices() {
+        Some(Indices::U16(indices)) => (
+            ctx.new_buffer(
+                mq::BufferType::IndexBuffer,
+                mq::BufferUsage::Immutable,
+                buffer_source::slice(indices),
+            ),
+            indices.len(),
+        ),
+        Some(Indices::U32(indices)) => (
+            ctx.new_buffer(
+                mq::BufferType::IndexBuffer,
+                mq::BufferUsage::Immutable,
+                buffer_source::slice(indices),
+            ),
+            indices.len(),
+        ),
+        // 32 bit indices need OES_element_index_uint on WebGL 1.
+        None if vertices.len() <= 1 << 16 => {
+            let indices: Vec<u16> = (0..vertices.len()).map(|index| index as u16).collect();
+            (
+                ctx.new_buffer(
+                    mq::BufferType::IndexBuffer,
+                    mq::BufferUsage::Immutable,
+                    buffer_source::slice(&indices),
+                ),
+                indices.len(),
+            )
+        }
+        None => {
+            let indices: Vec<u32> = (0..vertices.len() as u32).collect();
+            (
+                ctx.new_buffer(
+                    mq::BufferType::IndexBuffer,
+                    mq::BufferUsage::Immutable,
+                    buffer_source::slice(&indices),
+                ),
+                indices.len(),
+            )
+        }
+    };
+
+    Some(GpuMesh {
+        vertex_buffer: GpuBuffer::new(deletion_queue, vertex_buffer),
+        index_buffer: GpuBuffer::new(deletion_queue, index_buffer),
+        index_count,
+    })
+}
+
+fn prepare_meshes(
+    mut ctx: NonSendMut<MiniquadContext>,
+    deletion_queue: Res<GpuDeletionQueue>,
+    mut events: EventReader<AssetEvent<Mesh>>,
+    meshes: Res<Assets<Mesh>>,
+    mut gpu_meshes: ResMut<GpuMeshes>,
+) {
+    let ctx = &mut *ctx.0;
+
+    for event in events.read() {
+        match *event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } => {
+                let Some(mesh) = meshes.get(id) else {
+                    continue;
+                };
+                // Previous buffers are deleted on the next frame.
+                match upload_mesh(ctx, &deletion_queue, mesh) {
+                    Some(gpu_mesh) => gpu_meshes.meshes.insert(id, gpu_mesh),
+                    None => gpu_meshes.meshes.remove(&id),
+                };
+            }
+            AssetEvent::Removed { id } => {
+                gpu_meshes.meshes.remove(&id);
+            }
+            AssetEvent::Unused { .. } | AssetEvent::LoadedWithDependencies { .. } => {}
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct MeshRenderer {
+    /// Indexed by `pipeline_index`.
+    pipelines: [GpuPipeline; 4],
+    white_texture: GpuTexture,
+}
+
+/// Pipeline drawing with `material`, culling back faces unless double sided.
+fn pipeline_index(material: &MeshMaterial) -> usize {
+    material.double_sided as usize + material.alpha_blend as usize * 2
+}
+
+impl MeshRenderer {
+    fn new(ctx: &mut mq::Context, deletion_queue: &GpuDeletionQueue) -> Self {
+        let shader = ctx
+            .new_shader(
+                builtin_shader_source(
+                    ctx.info().backend,
+                    shader::VERTEX,
+                    shader::FRAGMENT,
+                    shader::METAL,
+                ),
+                shader::meta(),
+            )
+            .expect("Cannot compile mesh shader");
+
+        let mut new_pipeline = |index: usize| {
+            let (double_sided, alpha_blend) = (index % 2 == 1, index >= 2);
+            ctx.new_pipeline(
+                &[Vertex::buffer_layout()],
+                Vertex::ATTRIBUTES,
+                shader,
+                mq::PipelineParams {
+                    cull_face: if double_sided {
+                        mq::CullFace::Nothing
+                    } else {
+                        mq::CullFace::Back
+                    },
+                    depth_test: mq::Comparison::LessOrEqual,
+                    // miniquad only tests depth while writing it, so blended meshes
+                    // write it too, they are drawn back to front anyway.
+                    depth_write: true,
+                    color_blend: alpha_blend.then(|| {
+                        mq::BlendState::new(
+                            mq::Equation::Add,
+                            mq::BlendFactor::Value(mq::BlendValue::SourceAlpha),
+                            mq::BlendFactor::OneMinusValue(mq::BlendValue::SourceAlpha),
+                        )
+                    }),
+                    ..Default::default()
+                },
+            )
+        };
+        // Only one pipeline owns the shader, so it is deleted once.
+        let pipelines = [
+            GpuPipeline::with_shader(deletion_queue, new_pipeline(0), shader),
+            GpuPipeline::new(deletion_queue, new_pipeline(1)),
+            GpuPipeline::new(deletion_queue, new_pipeline(2)),
+            GpuPipeline::new(deletion_queue, new_pipeline(3)),
+        ];
+
+        let white_texture = ctx.new_texture_from_rgba8(1, 1, &[255, 255, 255, 255]);
+
+        MeshRenderer {
+            pipelines,
+            white_texture: GpuTexture::new(deletion_queue, white_texture),
+        }
+    }
+}
+
+fn prepare_mesh_renderer(world: &mut World) {
+    if world.contains_resource::<MeshRenderer>() {
+        return;
+    }
+
+    let deletion_queue = world.resource::<GpuDeletionQueue>().clone();
+    let renderer = MeshRenderer::new(
+        &mut *world.non_send_resource_mut::<MiniquadContext>().0,
+        &deletion_queue,
+    );
+    world.insert_resource(renderer);
+}
+
+fn linear_vec4(color: Color, intensity: f32) -> Vec4 {
+    let LinearRgba {
+        red,
+        green,
+        blue,
+        alpha,
+    } = color.to_linear();
+    Vec4::new(red * intensity, green * intensity, blue * intensity, alpha)
+}
+
+/// Draws the meshes with the cameras drawing to render targets when `OFFSCREEN`,
+/// with the ones drawing to the window otherwise.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn draw_meshes<const OFFSCREEN: bool>(
+    mut ctx: NonSendMut<MiniquadContext>,
+    renderer: Option<Res<MeshRenderer>>,
+    gpu_meshes: Res<GpuMeshes>,
+    materials: Res<Assets<MeshMaterial>>,
+    ambient_light: Res<AmbientLight>,
+    entities: Query<(&Mesh3d, Option<&MeshMaterial3d>, &GlobalTransform)>,
+    directional_lights: Query<(&DirectionalLight, &GlobalTransform)>,
+    point_lights: Query<(&PointLight, &GlobalTransform)>,
+    cameras: Query<&CameraView, With<MiniquadCamera3d>>,
+    #[cfg(feature = "image")] gpu_textures: Option<Res<GpuTextures>>,
+) {
+    let Some(renderer) = renderer else {
+        return;
+    };
+    let ctx = &mut *ctx.0;
+
+    let mut views: Vec<&CameraView> = cameras
+        .iter()
+        .filter(|view| view.is_active && view.is_offscreen() == OFFSCREEN)
+        .collect();
+    if views.is_empty() || entities.is_empty() {
+        return;
+    }
+    views.sort_by_key(|view| view.draw_order());
+
+    let default_material = MeshMaterial::default();
+    let white_texture = *renderer.white_texture;
+    let material_texture = |_material: &MeshMaterial| -> Option<mq::TextureId> {
+        #[cfg(feature = "image")]
+        if let Some(image) = &_material.base_color_texture {
+            return gpu_textures.as_ref()?.get(image);
+        }
+        Some(white_texture)
+    };
+
+    let mut items: Vec<(&GpuMesh, &MeshMaterial, mq::TextureId, &GlobalTransform)> = entities
+        .iter()
+        .filter_map(|(mesh, material, transform)| {
+            let gpu_mesh = gpu_meshes.get(&mesh.0)?;
+            let material = match material {
+                Some(material) => materials.get(&material.0)?,
+                None => &default_material,
+            };
+            Some((gpu_mesh, material, material_texture(material)?, transform))
+        })
+        .collect();
+    // Opaque meshes first, grouped by pipeline.
+    items.sort_by_key(|(_, material, _, _)| (material.alpha_blend, pipeline_index(material)));
+    let opaque_count = items
+        .iter()
+        .take_while(|(_, material, _, _)| !material.alpha_blend)
+        .count();
+
+    let mut directional_directions = [Vec4::ZERO; MAX_DIRECTIONAL_LIGHTS];
+    let mut directional_colors = [Vec4::ZERO; MAX_DIRECTIONAL_LIGHTS];
+    for (index, (light, transform)) in directional_lights
+        .iter()
+        .take(MAX_DIRECTIONAL_LIGHTS)
+        .enumerate()
+    {
+        // Direction towards the light.
+        directional_directions[index] = (-transform.forward().as_vec3()).extend(0.);
+        directional_colors[index] = linear_vec4(light.color, light.illuminance);
+    }
+    let ambient = linear_vec4(ambient_light.color, ambient_light.brightness);
+
+    for view in views {
+        let camera_position = view.view.inverse().w_axis;
+
+        let mut nearest_lights: Vec<(&PointLight, Vec3)> = point_lights
+            .iter()
+            .map(|(light, transform)| (light, transform.translation()))
+            .collect();
+        nearest_lights.sort_by(|a, b| {
+            let distance = |position: Vec3| position.distance_squared(camera_position.truncate());
+            distance(a.1).total_cmp(&distance(b.1))
+        });
+        let mut point_positions = [Vec4::ZERO; MAX_POINT_LIGHTS];
+        let mut point_colors = [Vec4::ZERO; MAX_POINT_LIGHTS];
+        for (index, (light, position)) in nearest_lights
+            .into_iter()
+            .take(MAX_POINT_LIGHTS)
+            .enumerate()
+        {
+            point_positions[index] = position.extend(light.range);
+            point_colors[index] = linear_vec4(light.color, light.intensity);
+        }
+
+        // Blended meshes are drawn from back to front.
+        let (opaque, blended) = items.split_at_mut(opaque_count);
+        blended.sort_by(|a, b| {
+            let depth =
+                |transform: &GlobalTransform| view.view.transform_point3(transform.translation()).z;
+            depth(a.3).total_cmp(&depth(b.3))
+        });
+
+        if !view.begin_pass(ctx, mq::PassAction::Nothing) {
+            continue;
+        }
+        view.apply_viewport(ctx);
+
+        let mut current_pipeline = None;
+        for (gpu_mesh, material, texture, transform) in opaque.iter().chain(blended.iter()) {
+            let index = pipeline_index(material);
+            if current_pipeline != Some(index) {
+                ctx.apply_pipeline(&renderer.pipelines[index]);
+                current_pipeline = Some(index);
+            }
+
+            let model = transform.compute_matrix();
+            let normal_matrix = Mat4::from_mat3(Mat3::from_mat4(model).inverse().transpose());
+            ctx.apply_bindings(&mq::Bindings {
+                vertex_buffers: vec![*gpu_mesh.vertex_buffer],
+                index_buffer: *gpu_mesh.index_buffer,
+                images: vec![*texture],
+            });
+            ctx.apply_uniforms(
+                shader::Uniforms {
+                    view_proj: view.view_projection,
+                    model,
+                    normal_matrix,
+                    camera_position,
+                    base_color: linear_vec4(material.base_color, 1.),
+                    material: Vec4::new(
+                        material.shininess,
+                        material.specular_strength,
+                        material.unlit as u8 as f32,
+                        0.,
+                    ),
+                    ambient,
+                    directional_directions,
+                    directional_colors,
+                    point_positions,
+                    point_colors,
+                }
+                .as_uniforms_source(),
+            );
+            ctx.draw(0, gpu_mesh.index_count as i32, 1);
+        }
+
+        ctx.end_render_pass();
+    }
+}
+
+mod shader {
+    use bevy_math::{Mat4, Vec4};
+    use miniquad::ShaderMeta;
+
+    use super::{MAX_DIRECTIONAL_LIGHTS, MAX_POINT_LIGHTS};
+    use crate::MiniquadUniforms;
+
+    pub const VERTEX: &str = r#"#version 100
+    attribute vec3 in_position;
+    attribute vec3 in_normal;
+    attribute vec2 in_uv;
+    uniform mat4 view_proj;
+    uniform mat4 model;
+    uniform mat4 normal_matrix;
+    varying highp vec3 world_position;
+    varying mediump vec3 world_normal;
+    varying mediump vec2 uv;
+    void main() {
+        vec4 world = model * vec4(in_position, 1.0);
+        world_position = world.xyz;
+        world_normal = (normal_matrix * vec4(in_normal, 0.0)).xyz;
+        uv = in_uv;
+        gl_Position = view_proj * world;
+    }"#;
+
+    // Lighting is computed in linear space, colors are converted from and to sRGB.
+    pub const FRAGMENT: &str = r#"#version 100
+    precision mediump float;
+    varying highp vec3 world_position;
+    varying mediump vec3 world_normal;
+    varying mediump vec2 uv;
+    uniform vec4 camera_position;
+    uniform vec4 base_color;
+    // Shininess, specular strength, unlit.
+    uniform vec4 material;
+    uniform vec4 ambient;
+    uniform vec4 directional_directions[2];
+    uniform vec4 directional_colors[2];
+    // Position and range.
+    uniform vec4 point_positions[4];
+    uniform vec4 point_colors[4];
+    uniform sampler2D base_color_texture;
+
+    vec3 shade(vec3 normal, vec3 view, vec3 light, vec3 radiance, vec3 albedo) {
+        float diffuse = max(dot(normal, light), 0.0);
+        vec3 halfway = normalize(light + view);
+        float specular = diffuse > 0.0
+            ? pow(max(dot(normal, halfway), 0.0), material.x) * material.y
+            : 0.0;
+        return radiance * (albedo * diffuse + vec3(specular));
+    }
+
+    void main() {
+        vec4 texel = texture2D(base_color_texture, uv);
+        vec4 color = base_color * vec4(pow(texel.rgb, vec3(2.2)), texel.a);
+        if (material.z > 0.5) {
+            gl_FragColor = vec4(pow(color.rgb, vec3(1.0 / 2.2)), color.a);
+            return;
+        }
+
+        vec3 normal = normalize(world_normal);
+        if (!gl_FrontFacing) {
+            normal = -normal;
+        }
+        vec3 view = normalize(camera_position.xyz - world_position);
+        vec3 light = ambient.rgb * color.rgb;
+        for (int i = 0; i < 2; i++) {
+            light += shade(normal, view, directional_directions[i].xyz,
+                directional_colors[i].rgb, color.rgb);
+        }
+        for (int i = 0; i < 4; i++) {
+            vec3 offset = point_positions[i].xyz - world_position;
+            float distance = max(length(offset), 0.0001);
+            float falloff = clamp(1.0 - distance / max(point_positions[i].w, 0.0001), 0.0, 1.0);
+            light += shade(normal, view, offset / distance,
+                point_colors[i].rgb * falloff * falloff, color.rgb);
+        }
+        gl_FragColor = vec4(pow(light, vec3(1.0 / 2.2)), color.a);
+    }"#;
+
+    pub const METAL: &str = r#"
+    #include <metal_stdlib>
+    using namespace metal;
+
+    struct Uniforms {
+        float4x4 view_proj;
+        float4x4 model;
+        float4x4 normal_matrix;
+        float4 camera_position;
+        float4 base_color;
+        float4 material;
+        float4 ambient;
+        float4 directional_directions[2];
+        float4 directional_colors[2];
+        float4 point_positions[4];
+        float4 point_colors[4];
+    };
+
+    struct Vertex {
+        float3 in_position [[attribute(0)]];
+        float3 in_normal [[attribute(1)]];
+        float2 in_uv [[attribute(2)]];
+    };
+
+    struct RasterizerData {
+        float4 position [[position]];
+        float3 world_position [[user(locn0)]];
+        float3 world_normal [[user(locn1)]];
+        float2 uv [[user(locn2)]];
+    };
+
+    vertex RasterizerData vertexShader(Vertex v [[stage_in]], constant Uniforms& uniforms [[buffer(0)]]) {
+        RasterizerData out;
+        float4 world = uniforms.model * float4(v.in_position, 1.0);
+        out.world_position = world.xyz;
+        out.world_normal = (uniforms.normal_matrix * float4(v.in_normal, 0.0)).xyz;
+        out.uv = v.in_uv;
+        out.position = uniforms.view_proj * world;
+        return out;
+    }
+
+    float3 shade(constant Uniforms& uniforms, float3 normal, float3 view, float3 light, float3 radiance, float3 albedo) {
+        float diffuse = max(dot(normal, light), 0.0);
+        float3 halfway = normalize(light + view);
+        float specular = diffuse > 0.0
+            ? pow(max(dot(normal, halfway), 0.0), uniforms.material.x) * uniforms.material.y
+            : 0.0;
+        return radiance * (albedo * diffuse + float3(specular));
+    }
+
+    fragment float4 fragmentShader(
+        RasterizerData in [[stage_in]],
+        bool front_facing [[front_facing]],
+        constant Uniforms& uniforms [[buffer(0)]],
+        texture2d<float> base_color_texture [[texture(0)]],
+        sampler base_color_sampler [[sampler(0)]]
+    ) {
+        float4 texel = base_color_texture.sample(base_color_sampler, in.uv);
+        float4 color = uniforms.base_color * float4(pow(texel.rgb, float3(2.2)), texel.a);
+        if (uniforms.material.z > 0.5) {
+            return float4(pow(color.rgb, float3(1.0 / 2.2)), color.a);
+        }
+
+        float3 normal = normalize(in.world_normal);
+        if (!front_facing) {
+            normal = -normal;
+        }
+        float3 view = normalize(uniforms.camera_position.xyz - in.world_position);
+        float3 light = uniforms.ambient.rgb * color.rgb;
+        for (int i = 0; i < 2; i++) {
+            light += shade(uniforms, normal, view, uniforms.directional_directions[i].xyz,
+                uniforms.directional_colors[i].rgb, color.rgb);
+        }
+        for (int i = 0; i < 4; i++) {
+            float3 offset = uniforms.point_positions[i].xyz - in.world_position;
+            float dist = max(length(offset), 0.0001);
+            float falloff = clamp(1.0 - dist / max(uniforms.point_positions[i].w, 0.0001), 0.0, 1.0);
+            light += shade(uniforms, normal, view, offset / dist,
+                uniforms.point_colors[i].rgb * falloff * falloff, color.rgb);
+        }
+        return float4(pow(light, float3(1.0 / 2.2)), color.a);
+    }"#;
+
+    pub fn meta() -> ShaderMeta {
+        Uniforms::shader_meta(&["base_color_texture"])
+    }
+
+    #[repr(C)]
+    #[derive(MiniquadUniforms)]
+    pub struct Uniforms {
+        pub view_proj: Mat4,
+        pub model: Mat4,
+        pub normal_matrix: Mat4,
+        pub camera_position: Vec4,
+        pub base_color: Vec4,
+        pub material: Vec4,
+        pub ambient: Vec4,
+        pub directional_directions: [Vec4; MAX_DIRECTIONAL_LIGHTS],
+        pub directional_colors: [Vec4; MAX_DIRECTIONAL_LIGHTS],
+        pub point_positions: [Vec4; MAX_POINT_LIGHTS],
+        pub point_colors: [Vec4; MAX_POINT_LIGHTS],
+    }
+}
@@ -0,0 +1,623 @@
+//! Rendering backend without a GPU, for testing draw systems headless.
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_miniquad::{mock::MockRenderingBackend, Draw, MiniquadContext, MiniquadPlugin};
+//! fn draw(mut ctx: NonSendMut<MiniquadContext>) {
+//!     ctx.0.begin_default_pass(Default::default());
+//!     ctx.0.draw(0, 3, 1);
+//!     ctx.0.end_render_pass();
+//! }
+//!
+//! let backend = MockRenderingBackend::new();
+//! let log = backend.log();
+//! let mut app = App::new();
+//! app.add_plugins(MiniquadPlugin)
+//!     .insert_non_send_resource(MiniquadContext(Box::new(backend)))
+//!     .add_systems(Draw, draw);
+//! app.world_mut().run_schedule(Draw);
+//! assert_eq!(log.draw_calls(), 1);
+//! ```
+
+use miniquad as mq;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::buffer_source::SliceReader;
+
+/// Call made to a `MockRenderingBackend`, with the ids it returned.
+#[derive(Clone, Debug)]
+pub enum RenderCall {
+    NewShader {
+        shader: mq::ShaderId,
+        images: Vec<String>,
+        uniforms: Vec<String>,
+    },
+    DeleteShader(mq::ShaderId),
+    NewTexture {
+        texture: mq::TextureId,
+        access: mq::TextureAccess,
+        params: mq::TextureParams,
+    },
+    /// Texture resized or its filter changed.
+    UpdateTextureParams {
+        texture: mq::TextureId,
+        params: mq::TextureParams,
+    },
+    /// `TextureParams` only has one wrap mode, `wrap_y` is kept here.
+    SetTextureWrap {
+        texture: mq::TextureId,
+        wrap_x: mq::TextureWrap,
+        wrap_y: mq::TextureWrap,
+    },
+    UpdateTexture {
+        texture: mq::TextureId,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    },
+    GenerateMipmaps(mq::TextureId),
+    ReadTexture(mq::TextureId),
+    DeleteTexture(mq::TextureId),
+    NewRenderPass {
+        pass: mq::RenderPass,
+        color: Vec<mq::TextureId>,
+        depth: Option<mq::TextureId>,
+    },
+    DeleteRenderPass(mq::RenderPass),
+    NewPipeline {
+        pipeline: mq::Pipeline,
+        shader: mq::ShaderId,
+        buffer_layouts: Vec<mq::BufferLayout>,
+        attributes: Vec<mq::VertexAttribute>,
+        params: mq::PipelineParams,
+    },
+    DeletePipeline(mq::Pipeline),
+    /// `size` is only known for `BufferSource::Empty` and `buffer_source::slice`,
+    /// miniquad does not expose the slice of `BufferSource::slice`.
+    NewBuffer {
+        buffer: mq::BufferId,
+        buffer_type: mq::BufferType,
+        usage: mq::BufferUsage,
+        size: Option<usize>,
+    },
+    UpdateBuffer {
+        buffer: mq::BufferId,
+        size: Option<usize>,
+    },
+    DeleteBuffer(mq::BufferId),
+    /// `pass` is `None` for the default framebuffer.
+    BeginPass {
+        pass: Option<mq::RenderPass>,
+        clear: Option<Clear>,
+    },
+    EndPass,
+    ApplyPipeline(mq::Pipeline),
+    ApplyBindings {
+        vertex_buffers: Vec<mq::BufferId>,
+        index_buffer: mq::BufferId,
+        images: Vec<mq::TextureId>,
+    },
+    ApplyUniforms(Vec<u8>),
+    ApplyViewport {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    },
+    ApplyScissorRect {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    },
+    Clear(Clear),
+    Draw {
+        base_element: i32,
+        num_elements: i32,
+        num_instances: i32,
+    },
+    CommitFrame,
+}
+
+/// Values a pass or `clear` call cleared the attachments with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Clear {
+    pub color: Option<(f32, f32, f32, f32)>,
+    pub depth: Option<f32>,
+    pub stencil: Option<i32>,
+}
+
+/// Calls recorded by a `MockRenderingBackend`, kept readable once the backend
+/// is boxed in the `MiniquadContext`.
+#[derive(Clone, Debug, Default)]
+pub struct RenderLog(Rc<RefCell<Vec<RenderCall>>>);
+
+impl RenderLog {
+    pub fn calls(&self) -> Vec<RenderCall> {
+        self.0.borrow().clone()
+    }
+
+    /// Calls recorded since the last `take`, e.g. for one frame.
+    pub fn take(&self) -> Vec<RenderCall> {
+        std::mem::take(&mut *self.0.borrow_mut())
+    }
+
+    pub fn clear(&self) {
+        self.0.borrow_mut().clear();
+    }
+
+    pub fn draw_calls(&self) -> usize {
+        self.0
+            .borrow()
+            .iter()
+            .filter(|call| matches!(call, RenderCall::Draw { .. }))
+            .count()
+    }
+
+    fn push(&self, call: RenderCall) {
+        self.0.borrow_mut().push(call);
+    }
+}
+
+struct MockPass {
+    pass: mq::RenderPass,
+    color: Vec<mq::TextureId>,
+    resolve: Vec<mq::TextureId>,
+    depth: Option<mq::TextureId>,
+}
+
+struct MockTexture {
+    raw_id: u32,
+    params: mq::TextureParams,
+    data: Vec<u8>,
+}
+
+/// miniquad `RenderingBackend` handing out fake ids and recording every call in
+/// its `RenderLog`. Texture contents are kept, so reading back what was uploaded
+/// works, nothing is drawn.
+///
+/// It reports the GL backend unless created `with_backend`, and like miniquad's
+/// GL backend deletes the attachments of a render pass with it. Window
+/// screenshots fail, the mock has no `WindowReadback`.
+pub struct MockRenderingBackend {
+    backend: mq::Backend,
+    log: RenderLog,
+    next_id: usize,
+    textures: HashMap<mq::TextureId, MockTexture>,
+    buffer_sizes: HashMap<mq::BufferId, usize>,
+    passes: Vec<MockPass>,
+    slices: SliceReader,
+}
+
+impl Default for MockRenderingBackend {
+    fn default() -> Self {
+        MockRenderingBackend::with_backend(mq::Backend::OpenGl)
+    }
+}
+
+/// miniquad ids other than textures wrap a private `usize` and can only be
+/// created by its backends, so the mock builds them from the bits. The assertion
+/// fails the build if miniquad changes the layout of an id type.
+///
+/// # Safety
+///
+/// `T` must be a struct with a single `usize` field.
+unsafe fn id_from_usize<T: Copy>(id: usize) -> T {
+    const { assert!(size_of::<T>() == size_of::<usize>()) };
+    std::mem::transmute_copy(&id)
+}
+
+impl MockRenderingBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_backend(backend: mq::Backend) -> Self {
+        MockRenderingBackend {
+            backend,
+            log: RenderLog::default(),
+            next_id: 1,
+            textures: HashMap::new(),
+            buffer_sizes: HashMap::new(),
+            passes: Vec::new(),
+            slices: SliceReader::sizes(),
+        }
+    }
+
+    /// Handle to the recorded calls.
+    pub fn log(&self) -> RenderLog {
+        self.log.clone()
+    }
+
+    fn next_id(&mut self) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    fn texture(&mut self, texture: mq::TextureId) -> &mut MockTexture {
+        self.textures
+            .get_mut(&texture)
+            .expect("Texture does not exist or was deleted")
+    }
+
+    fn update_texture_params(
+        &mut self,
+        texture: mq::TextureId,
+        update: impl FnOnce(&mut mq::TextureParams),
+    ) {
+        let params = &mut self.texture(texture).params;
+        update(params);
+        let params = *params;
+        self.log
+            .push(RenderCall::UpdateTextureParams { texture, params });
+    }
+}
+
+impl mq::RenderingBackend for MockRenderingBackend {
+    fn info(&self) -> mq::ContextInfo {
+        mq::ContextInfo {
+            backend: self.backend,
+            gl_version_string: String::new(),
+            glsl_support: mq::GlslSupport {
+                v100: true,
+                ..Default::default()
+            },
+            features: Default::default(),
+        }
+    }
+
+    fn new_shader(
+        &mut self,
+        _shader: mq::ShaderSource,
+        meta: mq::ShaderMeta,
+    ) -> Result<mq::ShaderId, mq::ShaderError> {
+        // Safety: `ShaderId` wraps a `usize`.
+        let shader = unsafe { id_from_usize(self.next_id()) };
+        self.log.push(RenderCall::NewShader {
+            shader,
+            images: meta.images,
+            uniforms: meta
+                .uniforms
+                .uniforms
+                .into_iter()
+                .map(|uniform| uniform.name)
+                .collect(),
+        });
+        Ok(shader)
+    }
+
+    fn new_texture(
+        &mut self,
+        access: mq::TextureAccess,
+        source: mq::TextureSource,
+        params: mq::TextureParams,
+    ) -> mq::TextureId {
+        let raw_id = self.next_id() as u32;
+        let texture = mq::TextureId::from_raw_id(mq::RawId::OpenGl(raw_id));
+        let size = params.format.size(params.width, params.height) as usize;
+        let mut data = match source {
+            mq::TextureSource::Bytes(bytes) => bytes.to_vec(),
+            // The first mipmap of the first cubemap face.
+            mq::TextureSource::Array(faces) => faces
+                .first()
+                .and_then(|levels| levels.first())
+                .map_or_else(Vec::new, |bytes| bytes.to_vec()),
+            mq::TextureSource::Empty => Vec::new(),
+        };
+        data.resize(size, 0);
+        self.textures.insert(
+            texture,
+            MockTexture {
+                raw_id,
+                params,
+                data,
+            },
+        );
+        self.log.push(RenderCall::NewTexture {
+            texture,
+            access,
+            params,
+        });
+        texture
+    }
+
+    fn texture_params(&self, texture: mq::TextureId) -> mq::TextureParams {
+        self.textures[&texture].params
+    }
+
+    unsafe fn texture_raw_id(&self, texture: mq::TextureId) -> mq::RawId {
+        mq::RawId::OpenGl(self.textures[&texture].raw_id)
+    }
+
+    fn texture_set_min_filter(
+        &mut self,
+        texture: mq::TextureId,
+        filter: mq::FilterMode,
+        mipmap_filter: mq::MipmapFilterMode,
+    ) {
+        self.update_texture_params(texture, |params| {
+            params.min_filter = filter;
+            params.mipmap_filter = mipmap_filter;
+        });
+    }
+
+    fn texture_set_mag_filter(&mut self, texture: mq::TextureId, filter: mq::FilterMode) {
+        self.update_texture_params(texture, |params| params.mag_filter = filter);
+    }
+
+    fn texture_set_wrap(
+        &mut self,
+        texture: mq::TextureId,
+        wrap_x: mq::TextureWrap,
+        wrap_y: mq::TextureWrap,
+    ) {
+        self.texture(texture).params.wrap = wrap_x;
+        self.log.push(RenderCall::SetTextureWrap {
+            texture,
+            wrap_x,
+            wrap_y,
+        });
+    }
+
+    fn texture_generate_mipmaps(&mut self, texture: mq::TextureId) {
+        self.log.push(RenderCall::GenerateMipmaps(texture));
+    }
+
+    fn texture_resize(
+        &mut self,
+        texture: mq::TextureId,
+        width: u32,
+        height: u32,
+        bytes: Option<&[u8]>,
+    ) {
+        let mock = self.texture(texture);
+        mock.params.width = width;
+        mock.params.height = height;
+        mock.data = bytes.map_or_else(Vec::new, <[u8]>::to_vec);
+        mock.data
+            .resize(mock.params.format.size(width, height) as usize, 0);
+        let params = mock.params;
+        self.log
+            .push(RenderCall::UpdateTextureParams { texture, params });
+    }
+
+    fn texture_read_pixels(&mut self, texture: mq::TextureId, bytes: &mut [u8]) {
+        let data = &self.texture(texture).data;
+        let len = bytes.len().min(data.len());
+        bytes[..len].copy_from_slice(&data[..len]);
+        self.log.push(RenderCall::ReadTexture(texture));
+    }
+
+    fn texture_update_part(
+        &mut self,
+        texture: mq::TextureId,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        bytes: &[u8],
+    ) {
+        let mock = self.texture(texture);
+        let pixel_size = mock.params.format.size(1, 1) as usize;
+        let texture_row = mock.params.width as usize * pixel_size;
+        let row = width as usize * pixel_size;
+        for (index, source) in bytes.chunks_exact(row).take(height as usize).enumerate() {
+            let start = (y as usize + index) * texture_row + x as usize * pixel_size;
+            if let Some(destination) = mock.data.get_mut(start..start + row) {
+                destination.copy_from_slice(source);
+            }
+        }
+        self.log.push(RenderCall::UpdateTexture {
+            texture,
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    fn new_render_pass_mrt(
+        &mut self,
+        color: &[mq::TextureId],
+        resolve: Option<&[mq::TextureId]>,
+        depth: Option<mq::TextureId>,
+    ) -> mq::RenderPass {
+        // Safety: `RenderPass` wraps a `usize`.
+        let pass = unsafe { id_from_usize(self.next_id()) };
+        self.passes.push(MockPass {
+            pass,
+            color: color.to_vec(),
+            resolve: resolve.map_or_else(Vec::new, <[mq::TextureId]>::to_vec),
+            depth,
+        });
+        self.log.push(RenderCall::NewRenderPass {
+            pass,
+            color: color.to_vec(),
+            depth,
+        });
+        pass
+    }
+
+    fn render_pass_color_attachments(&self, render_pass: mq::RenderPass) -> &[mq::TextureId] {
+        self.passes
+            .iter()
+            .find(|mock| mock.pass == render_pass)
+            .map_or(&[], |mock| &mock.color)
+    }
+
+    fn delete_render_pass(&mut self, render_pass: mq::RenderPass) {
+        let index = self.passes.iter().position(|mock| mock.pass == render_pass);
+        let mock = self
+            .passes
+            .swap_remove(index.expect("Render pass does not exist or was deleted"));
+        self.log.push(RenderCall::DeleteRenderPass(render_pass));
+        // As in miniquad, GL deletes the attachments with the pass, Metal does not.
+        if self.backend == mq::Backend::OpenGl {
+            for texture in mock.color.into_iter().chain(mock.resolve).chain(mock.depth) {
+                self.delete_texture(texture);
+            }
+        }
+    }
+
+    fn new_pipeline(
+        &mut self,
+        buffer_layouts: &[mq::BufferLayout],
+        attributes: &[mq::VertexAttribute],
+        shader: mq::ShaderId,
+        params: mq::PipelineParams,
+    ) -> mq::Pipeline {
+        // Safety: `Pipeline` wraps a `usize`.
+        let pipeline = unsafe { id_from_usize(self.next_id()) };
+        self.log.push(RenderCall::NewPipeline {
+            pipeline,
+            shader,
+            buffer_layouts: buffer_layouts.to_vec(),
+            attributes: attributes.to_vec(),
+            params,
+        });
+        pipeline
+    }
+
+    fn apply_pipeline(&mut self, pipeline: &mq::Pipeline) {
+        self.log.push(RenderCall::ApplyPipeline(*pipeline));
+    }
+
+    fn delete_pipeline(&mut self, pipeline: mq::Pipeline) {
+        self.log.push(RenderCall::DeletePipeline(pipeline));
+    }
+
+    fn new_buffer(
+        &mut self,
+        buffer_type: mq::BufferType,
+        usage: mq::BufferUsage,
+        data: mq::BufferSource,
+    ) -> mq::BufferId {
+        // Safety: `BufferId` wraps a `usize`.
+        let buffer = unsafe { id_from_usize(self.next_id()) };
+        let size = self.slices.size(&data);
+        self.slices.clear();
+        self.buffer_sizes.insert(buffer, size.unwrap_or_default());
+        self.log.push(RenderCall::NewBuffer {
+            buffer,
+            buffer_type,
+            usage,
+            size,
+        });
+        buffer
+    }
+
+    fn buffer_update(&mut self, buffer: mq::BufferId, data: mq::BufferSource) {
+        let size = self.slices.size(&data);
+        self.slices.clear();
+        self.log.push(RenderCall::UpdateBuffer { buffer, size });
+    }
+
+    /// 0 for buffers created from a `BufferSource::slice`, whose size is not known.
+    fn buffer_size(&mut self, buffer: mq::BufferId) -> usize {
+        self.buffer_sizes.get(&buffer).copied().unwrap_or_default()
+    }
+
+    fn delete_buffer(&mut self, buffer: mq::BufferId) {
+        self.buffer_sizes.remove(&buffer);
+        self.log.push(RenderCall::DeleteBuffer(buffer));
+    }
+
+    fn delete_texture(&mut self, texture: mq::TextureId) {
+        self.textures.remove(&texture);
+        self.log.push(RenderCall::DeleteTexture(texture));
+    }
+
+    fn delete_shader(&mut self, shader: mq::ShaderId) {
+        self.log.push(RenderCall::DeleteShader(shader));
+    }
+
+    fn apply_viewport(&mut self, x: i32, y: i32, width: i32, height: i32) {
+        self.log.push(RenderCall::ApplyViewport {
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    fn apply_scissor_rect(&mut self, x: i32, y: i32, width: i32, height: i32) {
+        self.log.push(RenderCall::ApplyScissorRect {
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    fn apply_bindings_from_slice(
+        &mut self,
+        vertex_buffers: &[mq::BufferId],
+        index_buffer: mq::BufferId,
+        images: &[mq::TextureId],
+    ) {
+        self.log.push(RenderCall::ApplyBindings {
+            vertex_buffers: vertex_buffers.to_vec(),
+            index_buffer,
+            images: images.to_vec(),
+        });
+    }
+
+    // The trait method is safe, miniquad backends read the pointer the same way.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
+        // Safety: miniquad passes the pointer and size of the uniforms struct.
+        let bytes = unsafe { std::slice::from_raw_parts(uniform_ptr, size) };
+        self.log.push(RenderCall::ApplyUniforms(bytes.to_vec()));
+    }
+
+    fn clear(
+        &mut self,
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) {
+        self.log.push(RenderCall::Clear(Clear {
+            color,
+            depth,
+            stencil,
+        }));
+    }
+
+    fn begin_default_pass(&mut self, action: mq::PassAction) {
+        self.begin_pass(None, action);
+    }
+
+    fn begin_pass(&mut self, pass: Option<mq::RenderPass>, action: mq::PassAction) {
+        let clear = match action {
+            mq::PassAction::Nothing => None,
+            mq::PassAction::Clear {
+                color,
+                depth,
+                stencil,
+            } => Some(Clear {
+                color,
+                depth,
+                stencil,
+            }),
+        };
+        self.log.push(RenderCall::BeginPass { pass, clear });
+    }
+
+    fn end_render_pass(&mut self) {
+        self.log.push(RenderCall::EndPass);
+    }
+
+    fn commit_frame(&mut self) {
+        self.log.push(RenderCall::CommitFrame);
+    }
+
+    fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
+        self.log.push(RenderCall::Draw {
+            base_element,
+            num_elements,
+            num_instances,
+        });
+    }
+}
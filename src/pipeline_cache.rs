@@ -0,0 +1,284 @@
+use bevy_ecs::prelude::*;
+use miniquad as mq;
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    mem::discriminant,
+};
+
+use crate::{
+    gpu::{GpuDeletionQueue, GpuPipeline},
+    MiniquadContext,
+};
+
+/// Shader, vertex layout and parameters of a pipeline, identifying it in the
+/// `PipelineCache`.
+#[derive(Clone, Debug)]
+pub struct PipelineDescriptor {
+    pub shader: mq::ShaderId,
+    pub buffer_layouts: Vec<mq::BufferLayout>,
+    pub attributes: Vec<mq::VertexAttribute>,
+    pub params: mq::PipelineParams,
+}
+
+impl PipelineDescriptor {
+    pub fn new(
+        shader: mq::ShaderId,
+        buffer_layouts: &[mq::BufferLayout],
+        attributes: &[mq::VertexAttribute],
+        params: mq::PipelineParams,
+    ) -> Self {
+        PipelineDescriptor {
+            shader,
+            buffer_layouts: buffer_layouts.to_vec(),
+            attributes: attributes.to_vec(),
+            params,
+        }
+    }
+
+    /// Hash of the fields, equal for equal descriptors. miniquad types don't
+    /// implement `Hash`, so their fields are hashed one by one, leaving out the
+    /// floats and blend states, which only `matches` compares.
+    pub fn key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.shader.hash(&mut hasher);
+        for layout in &self.buffer_layouts {
+            layout.stride.hash(&mut hasher);
+            discriminant(&layout.step_func).hash(&mut hasher);
+            layout.step_rate.hash(&mut hasher);
+        }
+        for attribute in &self.attributes {
+            attribute.name.hash(&mut hasher);
+            discriminant(&attribute.format).hash(&mut hasher);
+            attribute.buffer_index.hash(&mut hasher);
+            attribute.gl_pass_as_float.hash(&mut hasher);
+        }
+        let params = &self.params;
+        discriminant(&params.cull_face).hash(&mut hasher);
+        discriminant(&params.front_face_order).hash(&mut hasher);
+        discriminant(&params.depth_test).hash(&mut hasher);
+        params.depth_write.hash(&mut hasher);
+        params.color_blend.is_some().hash(&mut hasher);
+        params.alpha_blend.is_some().hash(&mut hasher);
+        params.stencil_test.is_some().hash(&mut hasher);
+        params.color_write.hash(&mut hasher);
+        discriminant(&params.primitive_type).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Whether every field is equal, layouts and attributes don't implement
+    /// `PartialEq`.
+    pub fn matches(&self, other: &PipelineDescriptor) -> bool {
+        let layouts_match = self.buffer_layouts.len() == other.buffer_layouts.len()
+            && self
+                .buffer_layouts
+                .iter()
+                .zip(&other.buffer_layouts)
+                .all(|(a, b)| {
+                    a.stride == b.stride && a.step_func == b.step_func && a.step_rate == b.step_rate
+                });
+        let attributes_match = self.attributes.len() == other.attributes.len()
+            && self.attributes.iter().zip(&other.attributes).all(|(a, b)| {
+                a.name == b.name
+                    && a.format == b.format
+                    && a.buffer_index == b.buffer_index
+                    && a.gl_pass_as_float == b.gl_pass_as_float
+            });
+        self.shader == other.shader
+            && layouts_match
+            && attributes_match
+            && self.params == other.params
+    }
+}
+
+/// Identifies the pipelines of equal `PipelineDescriptor`s in a `PipelineCache`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineId(u64);
+
+struct CachedPipeline {
+    shader: mq::ShaderId,
+    pipeline: GpuPipeline,
+}
+
+/// Pipelines shared by every system asking for the same shader, vertex layout
+/// and parameters, instead of each creating its own.
+///
+/// Systems without context access `queue` a descriptor and get its pipeline
+/// from the next `DrawSet::Prepare` on, draw systems can also create it right
+/// away with `get_or_create`. Pipelines are kept until `remove_shader` is called
+/// with their shader, which the cache does not own.
+#[derive(Resource)]
+pub struct PipelineCache {
+    pipelines: HashMap<PipelineId, CachedPipeline>,
+    queued: HashMap<PipelineId, PipelineDescriptor>,
+    /// Descriptors by key, with the id of their pipeline.
+    ids: HashMap<u64, Vec<(PipelineDescriptor, PipelineId)>>,
+    next_id: u64,
+    deletion_queue: GpuDeletionQueue,
+}
+
+impl FromWorld for PipelineCache {
+    fn from_world(world: &mut World) -> Self {
+        PipelineCache {
+            pipelines: HashMap::new(),
+            queued: HashMap::new(),
+            ids: HashMap::new(),
+            next_id: 0,
+            deletion_queue: world
+                .get_resource_or_insert_with(GpuDeletionQueue::default)
+                .clone(),
+        }
+    }
+}
+
+impl PipelineCache {
+    fn id(&mut self, descriptor: &PipelineDescriptor) -> PipelineId {
+        let descriptors = self.ids.entry(descriptor.key()).or_default();
+        if let Some((_, id)) = descriptors
+            .iter()
+            .find(|(cached, _)| cached.matches(descriptor))
+        {
+            return *id;
+        }
+        self.next_id += 1;
+        let id = PipelineId(self.next_id);
+        descriptors.push((descriptor.clone(), id));
+        id
+    }
+
+    /// Id of the pipeline for `descriptor`, created in the next `DrawSet::Prepare`
+    /// if it doesn't exist yet.
+    pub fn queue(&mut self, descriptor: PipelineDescriptor) -> PipelineId {
+        let id = self.id(&descriptor);
+        if !self.pipelines.contains_key(&id) {
+            self.queued.entry(id).or_insert(descriptor);
+        }
+        id
+    }
+
+    /// Pipeline for `descriptor`, created now if it doesn't exist yet.
+    pub fn get_or_create(
+        &mut self,
+        ctx: &mut mq::Context,
+        descriptor: &PipelineDescriptor,
+    ) -> mq::Pipeline {
+        let id = self.id(descriptor);
+        self.queued.remove(&id);
+        let deletion_queue = &self.deletion_queue;
+        *self
+            .pipelines
+            .entry(id)
+            .or_insert_with(|| create_pipeline(ctx, deletion_queue, descriptor))
+            .pipeline
+    }
+
+    /// Pipeline of a queued descriptor, `None` until it is created.
+    pub fn get(&self, id: PipelineId) -> Option<mq::Pipeline> {
+        self.pipelines.get(&id).map(|cached| *cached.pipeline)
+    }
+
+    /// Deletes the pipelines using `shader`, to be called before deleting it.
+    pub fn remove_shader(&mut self, shader: mq::ShaderId) {
+        self.pipelines.retain(|_, cached| cached.shader != shader);
+        self.queued
+            .retain(|_, descriptor| descriptor.shader != shader);
+        let (pipelines, queued) = (&self.pipelines, &self.queued);
+        self.ids.retain(|_, descriptors| {
+            descriptors.retain(|(_, id)| pipelines.contains_key(id) || queued.contains_key(id));
+            !descriptors.is_empty()
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+
+    /// Number of pipelines waiting for the next `DrawSet::Prepare`.
+    pub fn queued_len(&self) -> usize {
+        self.queued.len()
+    }
+}
+
+fn create_pipeline(
+    ctx: &mut mq::Context,
+    deletion_queue: &GpuDeletionQueue,
+    descriptor: &PipelineDescriptor,
+) -> CachedPipeline {
+    let pipeline = ctx.new_pipeline(
+        &descriptor.buffer_layouts,
+        &descriptor.attributes,
+        descriptor.shader,
+        descriptor.params,
+    );
+    CachedPipeline {
+        shader: descriptor.shader,
+        pipeline: GpuPipeline::new(deletion_queue, pipeline),
+    }
+}
+
+pub(crate) fn create_queued_pipelines(
+    mut ctx: NonSendMut<MiniquadContext>,
+    mut cache: ResMut<PipelineCache>,
+) {
+    if cache.queued.is_empty() {
+        return;
+    }
+    let ctx = &mut *ctx.0;
+    let cache = &mut *cache;
+    for (id, descriptor) in cache.queued.drain() {
+        cache
+            .pipelines
+            .insert(id, create_pipeline(ctx, &cache.deletion_queue, &descriptor));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockRenderingBackend;
+
+    #[test]
+    fn equal_descriptors_share_an_id() {
+        let mut ctx = MockRenderingBackend::new();
+        let shader = mq::RenderingBackend::new_shader(
+            &mut ctx,
+            mq::ShaderSource::Glsl {
+                vertex: "",
+                fragment: "",
+            },
+            mq::ShaderMeta {
+                images: Vec::new(),
+                uniforms: mq::UniformBlockLayout {
+                    uniforms: Vec::new(),
+                },
+            },
+        )
+        .unwrap();
+        let descriptor = |offset| {
+            PipelineDescriptor::new(
+                shader,
+                &[mq::BufferLayout::default()],
+                &[mq::VertexAttribute::new("in_pos", mq::VertexFormat::Float2)],
+                mq::PipelineParams {
+                    depth_write_offset: Some((offset, 0.)),
+                    ..Default::default()
+                },
+            )
+        };
+        let mut cache = PipelineCache::from_world(&mut World::new());
+
+        // Same key, as floats are not hashed, but different pipelines.
+        assert_eq!(descriptor(1.).key(), descriptor(2.).key());
+        let id = cache.queue(descriptor(1.));
+        assert_eq!(cache.queue(descriptor(1.)), id);
+        assert_ne!(cache.queue(descriptor(2.)), id);
+        assert_eq!(cache.queued_len(), 2);
+
+        cache.remove_shader(shader);
+        assert!(cache.ids.is_empty());
+    }
+}
@@ -0,0 +1,715 @@
+use bevy_app::{App, Plugin};
+use bevy_color::Color;
+use bevy_ecs::prelude::*;
+use bevy_math::{UVec2, Vec2, Vec4};
+use miniquad as mq;
+
+use crate::{
+    buffer_source::{self, BufferData},
+    converters::convert_color,
+    gpu::{GpuBuffer, GpuDeletionQueue, GpuPipeline, GpuRenderPass},
+    render_target::{new_target_pass, prepare_render_targets, MainPass},
+    Draw, DrawSet, MiniquadContext, MiniquadUniforms, MiniquadVertex, Window,
+};
+
+/// Draws the window cameras to an offscreen target the size of the window,
+/// then runs the enabled `PostProcessEffect`s by increasing order, the last one
+/// drawing to the window.
+///
+/// An effect is enabled while its resource exists, e.g. `app.insert_resource(Vignette::default())`.
+/// The built-in effects are `Bloom`, `ColorGrading`, `Vignette` and `Crt`,
+/// in that order. Nothing is drawn offscreen while no effect is enabled.
+#[derive(Default)]
+pub struct PostProcessPlugin;
+
+impl Plugin for PostProcessPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PostProcessEffects>()
+            .add_systems(
+                Draw,
+                (
+                    prepare_post_process
+                        .in_set(DrawSet::Prepare)
+                        .before(prepare_render_targets),
+                    run_post_process.in_set(DrawSet::PostProcess),
+                ),
+            )
+            .add_post_process_effect::<Bloom>(0)
+            .add_post_process_effect::<ColorGrading>(10)
+            .add_post_process_effect::<Vignette>(20)
+            .add_post_process_effect::<Crt>(30);
+    }
+}
+
+/// Full-screen effect, with its settings as a resource.
+///
+/// `FRAGMENT` is a GLSL 100 fragment shader reading the previous image from
+/// `uniform sampler2D source` at `varying vec2 uv`, with the uniforms of `Uniforms`.
+pub trait PostProcessEffect: Resource {
+    type Uniforms: MiniquadUniforms;
+
+    const FRAGMENT: &'static str;
+    /// Metal program with `vertexShader` and `fragmentShader` functions, taking
+    /// `in_pos` and `in_uv` attributes. The effect is skipped on Metal without it.
+    const METAL: Option<&'static str> = None;
+
+    /// Uniforms for a `resolution` pixels image.
+    fn uniforms(&self, resolution: Vec2) -> Self::Uniforms;
+}
+
+pub trait AddPostProcessEffect {
+    /// Registers an effect, run after the effects with a lower `order`.
+    fn add_post_process_effect<E: PostProcessEffect>(&mut self, order: i32) -> &mut Self;
+}
+
+impl AddPostProcessEffect for App {
+    fn add_post_process_effect<E: PostProcessEffect>(&mut self, order: i32) -> &mut Self {
+        let mut effects = self
+            .world_mut()
+            .get_resource_or_insert_with(PostProcessEffects::default);
+        let index = effects
+            .effects
+            .partition_point(|effect| effect.order <= order);
+        effects.effects.insert(
+            index,
+            Effect {
+                name: std::any::type_name::<E>(),
+                order,
+                fragment: E::FRAGMENT,
+                metal: E::METAL,
+                meta: || E::Uniforms::shader_meta(&["source"]),
+                is_enabled: |world| world.contains_resource::<E>(),
+                apply_uniforms: apply_effect_uniforms::<E>,
+                pipeline: None,
+                failed: false,
+            },
+        );
+        self
+    }
+}
+
+fn apply_effect_uniforms<E: PostProcessEffect>(world: &mut World, resolution: Vec2) {
+    let Some(uniforms) = world
+        .get_resource::<E>()
+        .map(|effect| effect.uniforms(resolution))
+    else {
+        return;
+    };
+    let mut ctx = world.non_send_resource_mut::<MiniquadContext>();
+    ctx.0.apply_uniforms(uniforms.as_uniforms_source());
+}
+
+struct Effect {
+    name: &'static str,
+    order: i32,
+    fragment: &'static str,
+    metal: Option<&'static str>,
+    meta: fn() -> mq::ShaderMeta,
+    is_enabled: fn(&World) -> bool,
+    apply_uniforms: fn(&mut World, Vec2),
+    pipeline: Option<GpuPipeline>,
+    failed: bool,
+}
+
+/// Registered effects and the targets they draw to.
+#[derive(Default, Resource)]
+pub struct PostProcessEffects {
+    effects: Vec<Effect>,
+    quad: Option<(GpuBuffer, GpuBuffer)>,
+    /// Scene target, then the target effects draw to in turn.
+    targets: Option<[GpuRenderPass; 2]>,
+    size: UVec2,
+    active: Vec<usize>,
+}
+
+impl PostProcessEffects {
+    /// Names of the effects run this frame, in order.
+    pub fn active(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.active
+            .iter()
+            .map(move |index| self.effects[*index].name)
+    }
+}
+
+#[repr(C)]
+#[derive(MiniquadVertex)]
+struct QuadVertex {
+    #[vertex(name = "in_pos")]
+    pos: [f32; 2],
+    #[vertex(name = "in_uv")]
+    uv: [f32; 2],
+}
+
+// Safety: `repr(C)` struct of floats only, it has no padding.
+unsafe impl BufferData for QuadVertex {}
+
+/// Metal program of an effect: the vertex function shared by every effect,
+/// then `$fragment`, the effect's `fragmentShader` and what it uses.
+macro_rules! metal_effect {
+    ($fragment:literal) => {
+        concat!(
+            r#"
+    #include <metal_stdlib>
+    using namespace metal;
+
+    struct Vertex {
+        float2 in_pos [[attribute(0)]];
+        float2 in_uv [[attribute(1)]];
+    };
+
+    struct RasterizerData {
+        float4 position [[position]];
+        float2 uv [[user(locn0)]];
+    };
+
+    vertex RasterizerData vertexShader(Vertex v [[stage_in]]) {
+        RasterizerData out;
+        out.position = float4(v.in_pos, 0.0, 1.0);
+        out.uv = v.in_uv;
+        return out;
+    }
+"#,
+            $fragment
+        )
+    };
+}
+
+const VERTEX: &str = r#"#version 100
+attribute vec2 in_pos;
+attribute vec2 in_uv;
+varying vec2 uv;
+void main() {
+    uv = in_uv;
+    gl_Position = vec4(in_pos, 0.0, 1.0);
+}"#;
+
+fn compile_effect(
+    ctx: &mut mq::Context,
+    deletion_queue: &GpuDeletionQueue,
+    effect: &Effect,
+) -> Result<GpuPipeline, String> {
+    let source = match ctx.info().backend {
+        mq::Backend::OpenGl => mq::ShaderSource::Glsl {
+            vertex: VERTEX,
+            fragment: effect.fragment,
+        },
+        mq::Backend::Metal => mq::ShaderSource::Msl {
+            program: effect.metal.ok_or("no Metal source")?,
+        },
+    };
+    let shader = ctx
+        .new_shader(source, (effect.meta)())
+        .map_err(|error| error.to_string())?;
+    let pipeline = ctx.new_pipeline(
+        &[QuadVertex::buffer_layout()],
+        QuadVertex::ATTRIBUTES,
+        shader,
+        mq::PipelineParams::default(),
+    );
+    Ok(GpuPipeline::with_shader(deletion_queue, pipeline, shader))
+}
+
+fn new_quad(ctx: &mut mq::Context, deletion_queue: &GpuDeletionQueue) -> (GpuBuffer, GpuBuffer) {
+    // Metal textures start at the top, GL ones at the bottom.
+    let (top, bottom) = match ctx.info().backend {
+        mq::Backend::OpenGl => (1., 0.),
+        mq::Backend::Metal => (0., 1.),
+    };
+    let vertices = [
+        QuadVertex {
+            pos: [-1., -1.],
+            uv: [0., bottom],
+        },
+        QuadVertex {
+            pos: [1., -1.],
+            uv: [1., bottom],
+        },
+        QuadVertex {
+            pos: [1., 1.],
+            uv: [1., top],
+        },
+        QuadVertex {
+            pos: [-1., 1.],
+            uv: [0., top],
+        },
+    ];
+    let vertex_buffer = ctx.new_buffer(
+        mq::BufferType::VertexBuffer,
+        mq::BufferUsage::Immutable,
+        buffer_source::slice(&vertices),
+    );
+    let index_buffer = ctx.new_buffer(
+        mq::BufferType::IndexBuffer,
+        mq::BufferUsage::Immutable,
+        buffer_source::slice(&[0u16, 1, 2, 0, 2, 3]),
+    );
+    (
+        GpuBuffer::new(deletion_queue, vertex_buffer),
+        GpuBuffer::new(deletion_queue, index_buffer),
+    )
+}
+
+/// Compiles the enabled effects and redirects the `MainPass` to the scene
+/// target while any is enabled.
+fn prepare_post_process(world: &mut World) {
+    let size = {
+        let window = world.resource::<Window>();
+        UVec2::new(window.width as u32, window.height as u32).max(UVec2::ONE)
+    };
+
+    world.resource_scope(|world, mut state: Mut<PostProcessEffects>| {
+        let state = &mut *state;
+        state.active.clear();
+        for (index, effect) in state.effects.iter().enumerate() {
+            if (effect.is_enabled)(world) && !effect.failed {
+                state.active.push(index);
+            }
+        }
+
+        let deletion_queue = world.resource::<GpuDeletionQueue>().clone();
+        let mut ctx = world.non_send_resource_mut::<MiniquadContext>();
+        let ctx = &mut *ctx.0;
+        for &index in state.active.iter() {
+            let effect = &mut state.effects[index];
+            if effect.pipeline.is_some() {
+                continue;
+            }
+            match compile_effect(ctx, &deletion_queue, effect) {
+                Ok(pipeline) => effect.pipeline = Some(pipeline),
+                Err(error) => {
+                    log::error!("Cannot compile {} effect: {}", effect.name, error);
+                    effect.failed = true;
+                }
+            }
+        }
+        let effects = &state.effects;
+        state
+            .active
+            .retain(|index| effects[*index].pipeline.is_some());
+
+        if state.active.is_empty() {
+            state.targets = None;
+        } else {
+            if state.quad.is_none() {
+                state.quad = Some(new_quad(ctx, &deletion_queue));
+            }
+            // Recreated when the window is resized.
+            if state.targets.is_none() || state.size != size {
+                state.targets = Some([
+                    new_target_pass(
+                        ctx,
+                        &deletion_queue,
+                        size,
+                        mq::TextureFormat::RGBA8,
+                        true,
+                        mq::FilterMode::Linear,
+                    ),
+                    new_target_pass(
+                        ctx,
+                        &deletion_queue,
+                        size,
+                        mq::TextureFormat::RGBA8,
+                        false,
+                        mq::FilterMode::Linear,
+                    ),
+                ]);
+                state.size = size;
+            }
+        }
+
+        let scene_pass = state.targets.as_ref().map(|targets| *targets[0]);
+        world.resource_mut::<MainPass>().0 = scene_pass;
+    });
+}
+
+/// Runs the active effects, each reading the image of the previous one.
+fn run_post_process(world: &mut World) {
+    world.resource_scope(|world, state: Mut<PostProcessEffects>| {
+        let (Some(targets), Some((vertex_buffer, index_buffer))) = (&state.targets, &state.quad)
+        else {
+            return;
+        };
+        let resolution = state.size.as_vec2();
+
+        for (i, &index) in state.active.iter().enumerate() {
+            let effect = &state.effects[index];
+            let Some(pipeline) = &effect.pipeline else {
+                continue;
+            };
+            let source = &targets[i % 2];
+            let last = i + 1 == state.active.len();
+
+            {
+                let mut ctx = world.non_send_resource_mut::<MiniquadContext>();
+                let ctx = &mut *ctx.0;
+                if last {
+                    ctx.begin_default_pass(mq::PassAction::Nothing);
+                } else {
+                    ctx.begin_pass(Some(*targets[(i + 1) % 2]), mq::PassAction::Nothing);
+                }
+                ctx.apply_pipeline(pipeline);
+                ctx.apply_bindings(&mq::Bindings {
+                    vertex_buffers: vec![**vertex_buffer],
+                    index_buffer: **index_buffer,
+                    images: vec![source.texture()],
+                });
+            }
+            (effect.apply_uniforms)(world, resolution);
+
+            let mut ctx = world.non_send_resource_mut::<MiniquadContext>();
+            ctx.0.draw(0, 6, 1);
+            ctx.0.end_render_pass();
+        }
+    });
+}
+
+/// Adds a glow around the pixels brighter than `threshold`.
+#[derive(Clone, Debug, Resource)]
+pub struct Bloom {
+    pub threshold: f32,
+    pub intensity: f32,
+    /// Spread of the glow, in pixels between samples.
+    pub radius: f32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Bloom {
+            threshold: 0.7,
+            intensity: 0.8,
+            radius: 2.,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(MiniquadUniforms)]
+pub struct BloomUniforms {
+    texel_size: Vec2,
+    threshold: f32,
+    intensity: f32,
+    radius: f32,
+}
+
+impl PostProcessEffect for Bloom {
+    type Uniforms = BloomUniforms;
+
+    const FRAGMENT: &'static str = r#"#version 100
+precision mediump float;
+varying vec2 uv;
+uniform sampler2D source;
+uniform vec2 texel_size;
+uniform float threshold;
+uniform float intensity;
+uniform float radius;
+
+vec3 bright(vec2 position) {
+    vec3 color = texture2D(source, position).rgb;
+    float luma = max(color.r, max(color.g, color.b));
+    return color * max(luma - threshold, 0.0) / max(luma, 0.0001);
+}
+
+void main() {
+    vec4 base = texture2D(source, uv);
+    vec3 glow = vec3(0.0);
+    float total = 0.0;
+    for (int x = -3; x <= 3; x++) {
+        for (int y = -3; y <= 3; y++) {
+            vec2 offset = vec2(float(x), float(y));
+            float weight = exp(-dot(offset, offset) / 8.0);
+            glow += bright(uv + offset * texel_size * radius) * weight;
+            total += weight;
+        }
+    }
+    gl_FragColor = vec4(base.rgb + glow / total * intensity, base.a);
+}"#;
+
+    const METAL: Option<&'static str> = Some(metal_effect!(
+        r#"
+    struct Uniforms {
+        float2 texel_size;
+        float threshold;
+        float intensity;
+        float radius;
+    };
+
+    float3 bright(texture2d<float> source, sampler smplr, float2 position, float threshold) {
+        float3 color = source.sample(smplr, position).rgb;
+        float luma = max(color.r, max(color.g, color.b));
+        return color * max(luma - threshold, 0.0) / max(luma, 0.0001);
+    }
+
+    fragment float4 fragmentShader(RasterizerData in [[stage_in]], constant Uniforms& uniforms [[buffer(0)]], texture2d<float> source [[texture(0)]], sampler sourceSmplr [[sampler(0)]]) {
+        float4 base = source.sample(sourceSmplr, in.uv);
+        float3 glow = float3(0.0);
+        float total = 0.0;
+        for (int x = -3; x <= 3; x++) {
+            for (int y = -3; y <= 3; y++) {
+                float2 offset = float2(float(x), float(y));
+                float weight = exp(-dot(offset, offset) / 8.0);
+                float2 position = in.uv + offset * uniforms.texel_size * uniforms.radius;
+                glow += bright(source, sourceSmplr, position, uniforms.threshold) * weight;
+                total += weight;
+            }
+        }
+        return float4(base.rgb + glow / total * uniforms.intensity, base.a);
+    }"#
+    ));
+
+    fn uniforms(&self, resolution: Vec2) -> BloomUniforms {
+        BloomUniforms {
+            texel_size: resolution.recip(),
+            threshold: self.threshold,
+            intensity: self.intensity,
+            radius: self.radius,
+        }
+    }
+}
+
+/// Exposure, contrast, saturation and gamma adjustments.
+#[derive(Clone, Debug, Resource)]
+pub struct ColorGrading {
+    /// In stops, `0.` keeps the brightness.
+    pub exposure: f32,
+    pub contrast: f32,
+    pub saturation: f32,
+    pub gamma: f32,
+}
+
+impl Default for ColorGrading {
+    fn default() -> Self {
+        ColorGrading {
+            exposure: 0.,
+            contrast: 1.,
+            saturation: 1.,
+            gamma: 1.,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(MiniquadUniforms)]
+pub struct ColorGradingUniforms {
+    exposure: f32,
+    contrast: f32,
+    saturation: f32,
+    gamma: f32,
+}
+
+impl PostProcessEffect for ColorGrading {
+    type Uniforms = ColorGradingUniforms;
+
+    const FRAGMENT: &'static str = r#"#version 100
+precision mediump float;
+varying vec2 uv;
+uniform sampler2D source;
+uniform float exposure;
+uniform float contrast;
+uniform float saturation;
+uniform float gamma;
+
+void main() {
+    vec4 color = texture2D(source, uv);
+    vec3 graded = color.rgb * exp2(exposure);
+    graded = (graded - 0.5) * contrast + 0.5;
+    float luma = dot(graded, vec3(0.2126, 0.7152, 0.0722));
+    graded = mix(vec3(luma), graded, saturation);
+    graded = pow(max(graded, 0.0), vec3(1.0 / gamma));
+    gl_FragColor = vec4(graded, color.a);
+}"#;
+
+    const METAL: Option<&'static str> = Some(metal_effect!(
+        r#"
+    struct Uniforms {
+        float exposure;
+        float contrast;
+        float saturation;
+        float gamma;
+    };
+
+    fragment float4 fragmentShader(RasterizerData in [[stage_in]], constant Uniforms& uniforms [[buffer(0)]], texture2d<float> source [[texture(0)]], sampler sourceSmplr [[sampler(0)]]) {
+        float4 color = source.sample(sourceSmplr, in.uv);
+        float3 graded = color.rgb * exp2(uniforms.exposure);
+        graded = (graded - 0.5) * uniforms.contrast + 0.5;
+        float luma = dot(graded, float3(0.2126, 0.7152, 0.0722));
+        graded = mix(float3(luma), graded, uniforms.saturation);
+        graded = pow(max(graded, 0.0), float3(1.0 / uniforms.gamma));
+        return float4(graded, color.a);
+    }"#
+    ));
+
+    fn uniforms(&self, _resolution: Vec2) -> ColorGradingUniforms {
+        ColorGradingUniforms {
+            exposure: self.exposure,
+            contrast: self.contrast,
+            saturation: self.saturation,
+            gamma: self.gamma.max(0.01),
+        }
+    }
+}
+
+/// Darkens the corners of the image.
+#[derive(Clone, Debug, Resource)]
+pub struct Vignette {
+    pub color: Color,
+    pub intensity: f32,
+    /// Distance from the center where darkening ends, `1.` is the corners.
+    pub radius: f32,
+    /// Width of the transition, in the same units as `radius`.
+    pub smoothness: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Vignette {
+            color: Color::BLACK,
+            intensity: 0.6,
+            radius: 0.9,
+            smoothness: 0.5,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(MiniquadUniforms)]
+pub struct VignetteUniforms {
+    vignette_color: Vec4,
+    intensity: f32,
+    radius: f32,
+    smoothness: f32,
+}
+
+impl PostProcessEffect for Vignette {
+    type Uniforms = VignetteUniforms;
+
+    const FRAGMENT: &'static str = r#"#version 100
+precision mediump float;
+varying vec2 uv;
+uniform sampler2D source;
+uniform vec4 vignette_color;
+uniform float intensity;
+uniform float radius;
+uniform float smoothness;
+
+void main() {
+    vec4 color = texture2D(source, uv);
+    float distance_to_center = distance(uv, vec2(0.5)) * 1.41421;
+    float amount = smoothstep(radius - smoothness, radius, distance_to_center);
+    amount *= intensity * vignette_color.a;
+    gl_FragColor = vec4(mix(color.rgb, vignette_color.rgb, amount), color.a);
+}"#;
+
+    const METAL: Option<&'static str> = Some(metal_effect!(
+        r#"
+    struct Uniforms {
+        float4 vignette_color;
+        float intensity;
+        float radius;
+        float smoothness;
+    };
+
+    fragment float4 fragmentShader(RasterizerData in [[stage_in]], constant Uniforms& uniforms [[buffer(0)]], texture2d<float> source [[texture(0)]], sampler sourceSmplr [[sampler(0)]]) {
+        float4 color = source.sample(sourceSmplr, in.uv);
+        float distance_to_center = distance(in.uv, float2(0.5)) * 1.41421;
+        float amount = smoothstep(uniforms.radius - uniforms.smoothness, uniforms.radius, distance_to_center);
+        amount *= uniforms.intensity * uniforms.vignette_color.a;
+        return float4(mix(color.rgb, uniforms.vignette_color.rgb, amount), color.a);
+    }"#
+    ));
+
+    fn uniforms(&self, _resolution: Vec2) -> VignetteUniforms {
+        let (r, g, b, a) = convert_color(self.color);
+        VignetteUniforms {
+            vignette_color: Vec4::new(r, g, b, a),
+            intensity: self.intensity,
+            radius: self.radius,
+            smoothness: self.smoothness.max(0.001),
+        }
+    }
+}
+
+/// Curved screen with scanlines.
+#[derive(Clone, Debug, Resource)]
+pub struct Crt {
+    pub curvature: f32,
+    /// Darkening between the lines, `0.` disables scanlines.
+    pub scanlines: f32,
+    /// Pixel rows per scanline.
+    pub line_height: f32,
+}
+
+impl Default for Crt {
+    fn default() -> Self {
+        Crt {
+            curvature: 0.08,
+            scanlines: 0.3,
+            line_height: 3.,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(MiniquadUniforms)]
+pub struct CrtUniforms {
+    resolution: Vec2,
+    curvature: f32,
+    scanlines: f32,
+    line_height: f32,
+}
+
+impl PostProcessEffect for Crt {
+    type Uniforms = CrtUniforms;
+
+    const FRAGMENT: &'static str = r#"#version 100
+precision mediump float;
+varying vec2 uv;
+uniform sampler2D source;
+uniform vec2 resolution;
+uniform float curvature;
+uniform float scanlines;
+uniform float line_height;
+
+void main() {
+    vec2 centered = uv * 2.0 - 1.0;
+    centered += centered * centered.yx * centered.yx * curvature;
+    vec2 position = centered * 0.5 + 0.5;
+    if (position.x < 0.0 || position.x > 1.0 || position.y < 0.0 || position.y > 1.0) {
+        gl_FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+    vec4 color = texture2D(source, position);
+    float line = sin(position.y * resolution.y / line_height * 3.14159) * 0.5 + 0.5;
+    gl_FragColor = vec4(color.rgb * mix(1.0, line, scanlines), color.a);
+}"#;
+
+    const METAL: Option<&'static str> = Some(metal_effect!(
+        r#"
+    struct Uniforms {
+        float2 resolution;
+        float curvature;
+        float scanlines;
+        float line_height;
+    };
+
+    fragment float4 fragmentShader(RasterizerData in [[stage_in]], constant Uniforms& uniforms [[buffer(0)]], texture2d<float> source [[texture(0)]], sampler sourceSmplr [[sampler(0)]]) {
+        float2 centered = in.uv * 2.0 - 1.0;
+        centered += centered * centered.yx * centered.yx * uniforms.curvature;
+        float2 position = centered * 0.5 + 0.5;
+        if (position.x < 0.0 || position.x > 1.0 || position.y < 0.0 || position.y > 1.0) {
+            return float4(0.0, 0.0, 0.0, 1.0);
+        }
+        float4 color = source.sample(sourceSmplr, position);
+        float line = sin(position.y * uniforms.resolution.y / uniforms.line_height * 3.14159) * 0.5 + 0.5;
+        return float4(color.rgb * mix(1.0, line, uniforms.scanlines), color.a);
+    }"#
+    ));
+
+    fn uniforms(&self, resolution: Vec2) -> CrtUniforms {
+        CrtUniforms {
+            resolution,
+            curvature: self.curvature,
+            scanlines: self.scanlines,
+            line_height: self.line_height.max(1.),
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use bevy_ecs::prelude::*;
+use miniquad as mq;
+use std::{cmp::Ordering, collections::HashMap};
+
+use crate::{render_target::MainPass, MiniquadContext, MiniquadUniforms};
+
+/// Draw call submitted to the `RenderQueue`.
+#[derive(Clone, Debug)]
+pub struct DrawItem {
+    pub pipeline: mq::Pipeline,
+    pub bindings: mq::Bindings,
+    /// Bytes of the uniforms struct, set with `with_uniforms`.
+    pub uniforms: Vec<u8>,
+    pub base_element: i32,
+    pub num_elements: i32,
+    pub num_instances: i32,
+    /// Items of a lower layer are drawn first, opaque or not.
+    pub layer: i32,
+    /// Distance to the camera, opaque items are drawn front to back and
+    /// transparent ones back to front.
+    pub depth: f32,
+    pub transparent: bool,
+}
+
+impl DrawItem {
+    /// Opaque draw of the first `num_elements` indices, without uniforms.
+    pub fn new(pipeline: mq::Pipeline, bindings: mq::Bindings, num_elements: i32) -> Self {
+        DrawItem {
+            pipeline,
+            bindings,
+            uniforms: Vec::new(),
+            base_element: 0,
+            num_elements,
+            num_instances: 1,
+            layer: 0,
+            depth: 0.,
+            transparent: false,
+        }
+    }
+
+    pub fn with_uniforms<T: MiniquadUniforms>(self, uniforms: &T) -> Self {
+        let size = T::uniforms_size();
+        assert!(size <= size_of::<T>(), "Uniforms larger than their struct");
+        // SAFETY: the uniforms are packed from the start of the struct, as the derive
+        // checks, so these bytes are initialized, unlike the trailing padding.
+        let bytes = unsafe { std::slice::from_raw_parts(uniforms as *const T as *const u8, size) };
+        DrawItem {
+            uniforms: bytes.to_vec(),
+            ..self
+        }
+    }
+
+    pub fn with_elements(self, base_element: i32, num_elements: i32) -> Self {
+        DrawItem {
+            base_element,
+            num_elements,
+            ..self
+        }
+    }
+
+    pub fn with_instances(self, num_instances: i32) -> Self {
+        DrawItem {
+            num_instances,
+            ..self
+        }
+    }
+
+    pub fn with_layer(self, layer: i32) -> Self {
+        DrawItem { layer, ..self }
+    }
+
+    pub fn with_depth(self, depth: f32) -> Self {
+        DrawItem { depth, ..self }
+    }
+
+    /// Blended with what is drawn before it, drawn after the opaque items of its layer.
+    pub fn transparent(self) -> Self {
+        DrawItem {
+            transparent: true,
+            ..self
+        }
+    }
+}
+
+/// Draw items submitted by `Update` systems, drawn to the `MainPass` in
+/// `DrawSet::Main`, after meshes and before sprites, then cleared.
+///
+/// Opaque items are grouped by pipeline and bindings, then drawn front to back,
+/// and transparent items drawn back to front after them. Consecutive items with
+/// the same pipeline, bindings and uniforms drawing adjacent elements are merged
+/// into one draw call, and pipelines and bindings are only applied when they change.
+#[derive(Debug, Default, Resource)]
+pub struct RenderQueue {
+    items: Vec<DrawItem>,
+}
+
+impl RenderQueue {
+    pub fn push(&mut self, item: DrawItem) {
+        self.items.push(item);
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+impl Extend<DrawItem> for RenderQueue {
+    fn extend<I: IntoIterator<Item = DrawItem>>(&mut self, items: I) {
+        self.items.extend(items);
+    }
+}
+
+/// Counts of the last frame drawing the `RenderQueue`.
+#[derive(Clone, Copy, Debug, Default, Resource)]
+pub struct RenderQueueStats {
+    pub items: usize,
+    /// Items drawn in the same draw call as the previous one.
+    pub merged_items: usize,
+    pub draw_calls: usize,
+    pub pipeline_changes: usize,
+    pub binding_changes: usize,
+}
+
+/// Item with the index of its pipeline and bindings among the distinct ones
+/// of the frame, as miniquad ids are not ordered.
+struct SortedItem {
+    item: DrawItem,
+    pipeline: usize,
+    bindings: usize,
+}
+
+impl SortedItem {
+    fn cmp(&self, other: &SortedItem) -> Ordering {
+        let (a, b) = (&self.item, &other.item);
+        a.layer
+            .cmp(&b.layer)
+            .then(a.transparent.cmp(&b.transparent))
+            .then_with(|| {
+                if a.transparent {
+                    b.depth.total_cmp(&a.depth)
+                } else {
+                    (self.pipeline, self.bindings)
+                        .cmp(&(other.pipeline, other.bindings))
+                        .then(a.depth.total_cmp(&b.depth))
+                }
+            })
+    }
+
+    /// Whether `next` can be drawn in the same draw call.
+    fn can_merge(&self, next: &SortedItem) -> bool {
+        let (a, b) = (&self.item, &next.item);
+        self.pipeline == next.pipeline
+            && self.bindings == next.bindings
+            && a.num_instances == 1
+            && b.num_instances == 1
+            && a.base_element + a.num_elements == b.base_element
+            && a.uniforms == b.uniforms
+    }
+}
+
+fn sort_items(items: Vec<DrawItem>) -> Vec<SortedItem> {
+    let mut pipelines = HashMap::new();
+    let mut bindings = HashMap::new();
+    let mut items: Vec<SortedItem> = items
+        .into_iter()
+        .map(|item| {
+            let next_pipeline = pipelines.len();
+            let pipeline = *pipelines.entry(item.pipeline).or_insert(next_pipeline);
+            let next_bindings = bindings.len();
+            let bindings = *bindings
+                .entry((
+                    item.bindings.vertex_buffers.clone(),
+                    item.bindings.index_buffer,
+                    item.bindings.images.clone(),
+                ))
+                .or_insert(next_bindings);
+            SortedItem {
+                item,
+                pipeline,
+                bindings,
+            }
+        })
+        .collect();
+    items.sort_by(SortedItem::cmp);
+    items
+}
+
+pub(crate) fn draw_render_queue(
+    mut ctx: NonSendMut<MiniquadContext>,
+    main_pass: Res<MainPass>,
+    mut queue: ResMut<RenderQueue>,
+    mut stats: ResMut<RenderQueueStats>,
+) {
+    *stats = RenderQueueStats {
+        items: queue.items.len(),
+        ..Default::default()
+    };
+    if queue.items.is_empty() {
+        return;
+    }
+    let ctx = &mut *ctx.0;
+
+    let mut batches: Vec<SortedItem> = Vec::with_capacity(queue.items.len());
+    for item in sort_items(std::mem::take(&mut queue.items)) {
+        match batches.last_mut() {
+            Some(last) if last.can_merge(&item) => {
+                last.item.num_elements += item.item.num_elements;
+                stats.merged_items += 1;
+            }
+            _ => batches.push(item),
+        }
+    }
+
+    main_pass.begin(ctx, mq::PassAction::Nothing);
+    let mut previous: Option<&SortedItem> = None;
+    for batch in &batches {
+        let pipeline_changed = previous.is_none_or(|previous| previous.pipeline != batch.pipeline);
+        if pipeline_changed {
+            ctx.apply_pipeline(&batch.item.pipeline);
+            stats.pipeline_changes += 1;
+        }
+        if previous.is_none_or(|previous| previous.bindings != batch.bindings) {
+            ctx.apply_bindings(&batch.item.bindings);
+            stats.binding_changes += 1;
+        }
+        // Uniforms belong to the shader, so are applied again after a pipeline change.
+        if !batch.item.uniforms.is_empty()
+            && (pipeline_changed
+                || previous.is_none_or(|previous| previous.item.uniforms != batch.item.uniforms))
+        {
+            ctx.apply_uniforms_from_bytes(batch.item.uniforms.as_ptr(), batch.item.uniforms.len());
+        }
+        ctx.draw(
+            batch.item.base_element,
+            batch.item.num_elements,
+            batch.item.num_instances,
+        );
+        stats.draw_calls += 1;
+        previous = Some(batch);
+    }
+    ctx.end_render_pass();
+}
@@ -0,0 +1,182 @@
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::*;
+use bevy_math::UVec2;
+use miniquad as mq;
+
+use crate::{
+    camera::CameraView,
+    gpu::{GpuDeletionQueue, GpuRenderPass},
+    Draw, DrawSet, MiniquadContext,
+};
+
+/// Creates the textures of `RenderTarget` entities and hands their pass
+/// to the cameras drawing to them.
+#[derive(Default)]
+pub struct RenderTargetPlugin;
+
+impl Plugin for RenderTargetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MainPass>()
+            .add_systems(Draw, prepare_render_targets.in_set(DrawSet::Prepare));
+    }
+}
+
+/// Pass the cameras drawing to the window draw to instead, e.g. to post-process
+/// the frame, the default pass when `None`.
+///
+/// Set in `DrawSet::Prepare`, before the cameras are updated.
+#[derive(Clone, Copy, Debug, Default, Resource)]
+pub struct MainPass(pub Option<mq::RenderPass>);
+
+impl MainPass {
+    pub fn begin(&self, ctx: &mut mq::Context, action: mq::PassAction) {
+        ctx.begin_pass(self.0, action);
+    }
+}
+
+/// Offscreen texture cameras can draw to, by setting their `target` to this entity.
+///
+/// Targets are drawn in `DrawSet::Offscreen`, by increasing `order`, before
+/// anything is drawn to the window. Their texture can then be sampled like any
+/// other, e.g. with `Sprite::from_render_target`.
+/// On the GL backend the texture rows start at the bottom of the image.
+///
+/// Textures are created in `DrawSet::Prepare`, recreated when the settings change
+/// and deleted when the component is removed.
+#[derive(Component, Debug)]
+pub struct RenderTarget {
+    /// Size in pixels.
+    pub size: UVec2,
+    pub format: mq::TextureFormat,
+    /// Adds a depth texture, for 3D cameras.
+    pub depth: bool,
+    pub filter: mq::FilterMode,
+    /// Targets with a higher order are drawn later, and can sample lower ones.
+    pub order: isize,
+    gpu: Option<(GpuRenderPass, TargetSettings)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TargetSettings {
+    size: UVec2,
+    format: mq::TextureFormat,
+    depth: bool,
+    filter: mq::FilterMode,
+}
+
+impl RenderTarget {
+    pub fn new(size: UVec2) -> Self {
+        RenderTarget {
+            size,
+            format: mq::TextureFormat::RGBA8,
+            depth: false,
+            filter: mq::FilterMode::Linear,
+            order: 0,
+            gpu: None,
+        }
+    }
+
+    pub fn with_depth(self) -> Self {
+        RenderTarget {
+            depth: true,
+            ..self
+        }
+    }
+
+    pub fn with_order(self, order: isize) -> Self {
+        RenderTarget { order, ..self }
+    }
+
+    /// Color texture, `None` until created in `DrawSet::Prepare`.
+    pub fn texture(&self) -> Option<mq::TextureId> {
+        self.gpu.as_ref().map(|(pass, _)| pass.texture())
+    }
+
+    pub fn depth_texture(&self) -> Option<mq::TextureId> {
+        self.gpu.as_ref().and_then(|(pass, _)| pass.depth_texture())
+    }
+
+    pub fn pass(&self) -> Option<mq::RenderPass> {
+        self.gpu.as_ref().map(|(pass, _)| **pass)
+    }
+
+    fn settings(&self) -> TargetSettings {
+        TargetSettings {
+            size: self.size.max(UVec2::ONE),
+            format: self.format,
+            depth: self.depth,
+            filter: self.filter,
+        }
+    }
+}
+
+/// Pass drawing to a new color texture, and depth texture if `depth`.
+pub(crate) fn new_target_pass(
+    ctx: &mut mq::Context,
+    deletion_queue: &GpuDeletionQueue,
+    size: UVec2,
+    format: mq::TextureFormat,
+    depth: bool,
+    filter: mq::FilterMode,
+) -> GpuRenderPass {
+    let params = mq::TextureParams {
+        kind: mq::TextureKind::Texture2D,
+        format,
+        wrap: mq::TextureWrap::Clamp,
+        min_filter: filter,
+        mag_filter: filter,
+        mipmap_filter: mq::MipmapFilterMode::None,
+        width: size.x,
+        height: size.y,
+        allocate_mipmaps: false,
+        sample_count: 1,
+    };
+    let color = ctx.new_render_texture(params);
+    let depth = depth.then(|| {
+        ctx.new_render_texture(mq::TextureParams {
+            format: mq::TextureFormat::Depth,
+            ..params
+        })
+    });
+    GpuRenderPass::new(ctx, deletion_queue, color, depth)
+}
+
+pub(crate) fn prepare_render_targets(
+    mut ctx: NonSendMut<MiniquadContext>,
+    deletion_queue: Res<GpuDeletionQueue>,
+    main_pass: Res<MainPass>,
+    mut targets: Query<&mut RenderTarget>,
+    mut views: Query<&mut CameraView>,
+) {
+    let ctx = &mut *ctx.0;
+
+    for mut target in targets.iter_mut() {
+        let settings = target.settings();
+        if target.gpu.as_ref().map(|(_, current)| *current) != Some(settings) {
+            // The previous pass, if any, is deleted on the next frame.
+            let pass = new_target_pass(
+                ctx,
+                &deletion_queue,
+                settings.size,
+                settings.format,
+                settings.depth,
+                settings.filter,
+            );
+            target.gpu = Some((pass, settings));
+        }
+    }
+
+    for mut view in views.iter_mut() {
+        let (pass, order) = match view.target {
+            None => (main_pass.0, 0),
+            Some(entity) => match targets.get(entity) {
+                Ok(target) => (target.pass(), target.order),
+                Err(_) => (None, 0),
+            },
+        };
+        if view.target_pass != pass || view.target_order != order {
+            view.target_pass = pass;
+            view.target_order = order;
+        }
+    }
+}
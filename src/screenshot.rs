@@ -0,0 +1,218 @@
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::*;
+use miniquad as mq;
+use std::path::PathBuf;
+
+use crate::{render_target::RenderTarget, MiniquadContext, PostDraw, Window};
+
+/// Captures the frame on `TakeScreenshot` events, see `ScreenshotCaptured`.
+#[derive(Default)]
+pub struct ScreenshotPlugin;
+
+impl Plugin for ScreenshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TakeScreenshot>()
+            .add_event::<ScreenshotCaptured>()
+            .add_systems(PostDraw, take_screenshots);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScreenshotSource {
+    /// What the window shows, needs a `WindowReadback`.
+    Window,
+    /// The texture of a `RenderTarget` entity.
+    RenderTarget(Entity),
+}
+
+/// Captures `source` once the current frame is drawn.
+#[derive(Event, Clone, Debug)]
+pub struct TakeScreenshot {
+    pub source: ScreenshotSource,
+    /// Also writes the screenshot to this PNG file, needs the `png` feature.
+    pub path: Option<PathBuf>,
+}
+
+impl TakeScreenshot {
+    pub fn window() -> Self {
+        TakeScreenshot {
+            source: ScreenshotSource::Window,
+            path: None,
+        }
+    }
+
+    pub fn render_target(entity: Entity) -> Self {
+        TakeScreenshot {
+            source: ScreenshotSource::RenderTarget(entity),
+            path: None,
+        }
+    }
+
+    pub fn with_path(self, path: impl Into<PathBuf>) -> Self {
+        TakeScreenshot {
+            path: Some(path.into()),
+            ..self
+        }
+    }
+}
+
+/// Pixels of a `TakeScreenshot`, sent after the frame it was taken in.
+#[derive(Event, Clone, Debug)]
+pub struct ScreenshotCaptured {
+    pub source: ScreenshotSource,
+    pub width: u32,
+    pub height: u32,
+    /// RGBA8 pixels, rows from the top of the image.
+    pub data: Vec<u8>,
+}
+
+impl ScreenshotCaptured {
+    #[cfg(feature = "png")]
+    pub fn save_png(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.data))
+            .map_err(std::io::Error::other)
+    }
+}
+
+/// How the pixels of the window are read back, a non-send resource inserted
+/// with the `MiniquadContext`. Window screenshots fail without it.
+///
+/// The runner inserts `Gl` for the window it opens on the GL backend, there is
+/// no way to read the window through the miniquad backend API.
+pub enum WindowReadback {
+    /// `glReadPixels` on the current GL context.
+    Gl,
+    /// The framebuffer of a `SoftRenderingBackend`.
+    #[cfg(feature = "soft-render")]
+    Soft(crate::soft_render::SoftFramebuffer),
+}
+
+/// Reverses the rows of a `width` pixels wide RGBA8 image.
+fn flip_rows(data: &mut [u8], width: u32) {
+    let row = width as usize * 4;
+    let rows = data.len() / row;
+    for y in 0..rows / 2 {
+        let (top, bottom) = data.split_at_mut((rows - 1 - y) * row);
+        top[y * row..(y + 1) * row].swap_with_slice(&mut bottom[..row]);
+    }
+}
+
+/// Pixels of the default framebuffer, top row first.
+fn read_window(
+    ctx: &mut mq::Context,
+    readback: Option<&WindowReadback>,
+    width: u32,
+    height: u32,
+) -> Result<(u32, u32, Vec<u8>), &'static str> {
+    match readback {
+        Some(WindowReadback::Gl) => {
+            let mut data = read_gl_window(ctx, width, height);
+            flip_rows(&mut data, width);
+            Ok((width, height, data))
+        }
+        #[cfg(feature = "soft-render")]
+        Some(WindowReadback::Soft(framebuffer)) => {
+            let size = framebuffer.size();
+            Ok((size.x, size.y, framebuffer.pixels()))
+        }
+        None => Err("The window cannot be read back without a WindowReadback resource"),
+    }
+}
+
+/// Pixels of the default GL framebuffer, bottom row first.
+fn read_gl_window(ctx: &mut mq::Context, width: u32, height: u32) -> Vec<u8> {
+    let mut data = vec![0; width as usize * height as usize * 4];
+    ctx.begin_default_pass(mq::PassAction::Nothing);
+    // Safety: the buffer holds `width * height` RGBA8 pixels.
+    unsafe {
+        mq::gl::glReadPixels(
+            0,
+            0,
+            width as i32,
+            height as i32,
+            mq::gl::GL_RGBA,
+            mq::gl::GL_UNSIGNED_BYTE,
+            data.as_mut_ptr() as *mut _,
+        );
+    }
+    ctx.end_render_pass();
+    data
+}
+
+fn capture(
+    ctx: &mut mq::Context,
+    source: ScreenshotSource,
+    readback: Option<&WindowReadback>,
+    window: &Window,
+    targets: &Query<&RenderTarget>,
+) -> Result<ScreenshotCaptured, String> {
+    let (width, height, data) = match source {
+        ScreenshotSource::Window => {
+            read_window(ctx, readback, window.width as u32, window.height as u32)?
+        }
+        ScreenshotSource::RenderTarget(entity) => {
+            let texture = targets
+                .get(entity)
+                .ok()
+                .and_then(RenderTarget::texture)
+                .ok_or("No render target texture")?;
+            let params = ctx.texture_params(texture);
+            if params.format != mq::TextureFormat::RGBA8 {
+                return Err(format!("Cannot read {:?} textures", params.format));
+            }
+            let mut data = vec![0; params.format.size(params.width, params.height) as usize];
+            ctx.texture_read_pixels(texture, &mut data);
+            // GL images start at the bottom.
+            if ctx.info().backend == mq::Backend::OpenGl {
+                flip_rows(&mut data, params.width);
+            }
+            (params.width, params.height, data)
+        }
+    };
+    Ok(ScreenshotCaptured {
+        source,
+        width,
+        height,
+        data,
+    })
+}
+
+pub(crate) fn take_screenshots(
+    mut ctx: NonSendMut<MiniquadContext>,
+    readback: Option<NonSend<WindowReadback>>,
+    mut requests: EventReader<TakeScreenshot>,
+    mut captured: EventWriter<ScreenshotCaptured>,
+    window: Res<Window>,
+    targets: Query<&RenderTarget>,
+) {
+    let ctx = &mut *ctx.0;
+    for request in requests.read() {
+        let screenshot = match capture(ctx, request.source, readback.as_deref(), &window, &targets)
+        {
+            Ok(screenshot) => screenshot,
+            Err(error) => {
+                log::warn!("Cannot take screenshot: {}", error);
+                continue;
+            }
+        };
+
+        if let Some(path) = &request.path {
+            #[cfg(feature = "png")]
+            if let Err(error) = screenshot.save_png(path) {
+                log::warn!("Cannot write screenshot to {}: {}", path.display(), error);
+            }
+            #[cfg(not(feature = "png"))]
+            log::warn!(
+                "Cannot write screenshot to {}, the png feature is disabled",
+                path.display()
+            );
+        }
+        captured.send(screenshot);
+    }
+}
@@ -0,0 +1,370 @@
+use bevy_app::{App, Plugin};
+use bevy_asset::{
+    io::Reader, Asset, AssetApp, AssetEvent, AssetId, AssetLoader, Assets, Handle, LoadContext,
+};
+use bevy_ecs::prelude::*;
+use bevy_reflect::TypePath;
+use miniquad as mq;
+use std::fmt;
+
+use crate::{
+    gpu::{GpuDeletionQueue, GpuPipeline},
+    Draw, DrawSet, MiniquadContext,
+};
+
+/// Loads `MiniquadShader` assets and compiles the pipelines registered in
+/// `ShaderPipelines`, recompiling them when their shader is modified.
+///
+/// bevy's `AssetPlugin` needs to be added first, with its `file_watcher`
+/// feature for hot reload.
+#[derive(Default)]
+pub struct MiniquadShaderPlugin;
+
+impl Plugin for MiniquadShaderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<MiniquadShader>()
+            .init_asset_loader::<MiniquadShaderLoader>()
+            .init_resource::<ShaderPipelines>()
+            .add_event::<ShaderCompileError>()
+            .add_systems(Draw, prepare_shader_pipelines.in_set(DrawSet::Prepare));
+    }
+}
+
+/// Shader sources with the uniforms and images they use.
+///
+/// Loaded from `.mqshader` files, made of sections started by `@` directives:
+///
+/// ```text
+/// @uniform time Float1
+/// @uniform positions Float2 32
+/// @image tex
+/// @vertex
+/// #version 100
+/// ...
+/// @fragment
+/// #version 100
+/// ...
+/// @metal
+/// #include <metal_stdlib>
+/// ...
+/// ```
+///
+/// `@uniform` takes a `miniquad::UniformType` name and an optional array size,
+/// in the order of the fields of the uniforms struct.
+/// The `@metal` section is optional and only used on the Metal backend.
+#[derive(Asset, TypePath, Clone, Debug, Default)]
+pub struct MiniquadShader {
+    pub vertex: String,
+    pub fragment: String,
+    pub metal: Option<String>,
+    pub uniforms: Vec<mq::UniformDesc>,
+    pub images: Vec<String>,
+}
+
+impl MiniquadShader {
+    pub fn meta(&self) -> mq::ShaderMeta {
+        mq::ShaderMeta {
+            uniforms: mq::UniformBlockLayout {
+                uniforms: self.uniforms.clone(),
+            },
+            images: self.images.clone(),
+        }
+    }
+
+    pub fn parse(source: &str) -> Result<Self, ShaderLoadError> {
+        enum Section {
+            None,
+            Vertex,
+            Fragment,
+            Metal,
+        }
+
+        let mut shader = MiniquadShader::default();
+        let mut metal = String::new();
+        let mut section = Section::None;
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let Some(directive) = line.trim_start().strip_prefix('@') else {
+                let target = match section {
+                    Section::None if line.trim().is_empty() => continue,
+                    Section::None => {
+                        return Err(ShaderLoadError::Parse {
+                            line: line_number,
+                            message: "source outside of a section".to_string(),
+                        })
+                    }
+                    Section::Vertex => &mut shader.vertex,
+                    Section::Fragment => &mut shader.fragment,
+                    Section::Metal => &mut metal,
+                };
+                target.push_str(line);
+                target.push('\n');
+                continue;
+            };
+
+            let mut words = directive.split_whitespace();
+            match words.next() {
+                Some("vertex") => section = Section::Vertex,
+                Some("fragment") => section = Section::Fragment,
+                Some("metal") => section = Section::Metal,
+                Some("image") => match words.next() {
+                    Some(name) => shader.images.push(name.to_string()),
+                    None => {
+                        return Err(ShaderLoadError::Parse {
+                            line: line_number,
+                            message: "missing image name".to_string(),
+                        })
+                    }
+                },
+                Some("uniform") => {
+                    let uniform =
+                        parse_uniform(&mut words).map_err(|message| ShaderLoadError::Parse {
+                            line: line_number,
+                            message,
+                        })?;
+                    shader.uniforms.push(uniform);
+                }
+                other => {
+                    return Err(ShaderLoadError::Parse {
+                        line: line_number,
+                        message: format!("unknown directive {:?}", other.unwrap_or_default()),
+                    })
+                }
+            }
+        }
+
+        if shader.vertex.is_empty() || shader.fragment.is_empty() {
+            return Err(ShaderLoadError::MissingSource);
+        }
+        if !metal.is_empty() {
+            shader.metal = Some(metal);
+        }
+        Ok(shader)
+    }
+}
+
+fn parse_uniform<'a>(words: &mut impl Iterator<Item = &'a str>) -> Result<mq::UniformDesc, String> {
+    let name = words.next().ok_or("missing uniform name")?;
+    let uniform_type = match words.next().ok_or("missing uniform type")? {
+        "Float1" => mq::UniformType::Float1,
+        "Float2" => mq::UniformType::Float2,
+        "Float3" => mq::UniformType::Float3,
+        "Float4" => mq::UniformType::Float4,
+        "Int1" => mq::UniformType::Int1,
+        "Int2" => mq::UniformType::Int2,
+        "Int3" => mq::UniformType::Int3,
+        "Int4" => mq::UniformType::Int4,
+        "Mat4" => mq::UniformType::Mat4,
+        other => return Err(format!("unknown uniform type {:?}", other)),
+    };
+    let uniform = mq::UniformDesc::new(name, uniform_type);
+    match words.next() {
+        Some(count) => count
+            .parse()
+            .map(|count| uniform.array(count))
+            .map_err(|_| format!("invalid array size {:?}", count)),
+        None => Ok(uniform),
+    }
+}
+
+#[derive(Debug)]
+pub enum ShaderLoadError {
+    Io(std::io::Error),
+    Utf8(std::string::FromUtf8Error),
+    Parse { line: usize, message: String },
+    MissingSource,
+}
+
+impl fmt::Display for ShaderLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderLoadError::Io(error) => write!(f, "Cannot read shader: {}", error),
+            ShaderLoadError::Utf8(error) => write!(f, "Shader is not valid UTF-8: {}", error),
+            ShaderLoadError::Parse { line, message } => write!(f, "Line {}: {}", line, message),
+            ShaderLoadError::MissingSource => {
+                write!(f, "Shader needs both @vertex and @fragment sources")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ShaderLoadError {}
+
+#[derive(Default)]
+pub struct MiniquadShaderLoader;
+
+impl AssetLoader for MiniquadShaderLoader {
+    type Asset = MiniquadShader;
+    type Settings = ();
+    type Error = ShaderLoadError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<MiniquadShader, ShaderLoadError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(ShaderLoadError::Io)?;
+        let source = String::from_utf8(bytes).map_err(ShaderLoadError::Utf8)?;
+        MiniquadShader::parse(&source)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["mqshader"]
+    }
+}
+
+/// Sent when a shader fails to compile, the previous pipeline is kept if any.
+#[derive(Event, Debug, Clone)]
+pub struct ShaderCompileError {
+    pub shader: AssetId<MiniquadShader>,
+    pub pipeline: ShaderPipelineId,
+    pub error: String,
+}
+
+/// Everything needed to create a pipeline, besides the compiled shader.
+#[derive(Clone, Debug)]
+pub struct ShaderPipelineDescriptor {
+    pub shader: Handle<MiniquadShader>,
+    pub buffer_layouts: Vec<mq::BufferLayout>,
+    pub attributes: Vec<mq::VertexAttribute>,
+    pub params: mq::PipelineParams,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ShaderPipelineId(usize);
+
+#[derive(Debug, PartialEq)]
+enum PipelineState {
+    Pending,
+    Ready,
+    Failed,
+}
+
+struct ShaderPipeline {
+    descriptor: ShaderPipelineDescriptor,
+    compiled: Option<GpuPipeline>,
+    state: PipelineState,
+}
+
+/// Pipelines compiled in `DrawSet::Prepare` from `MiniquadShader` assets.
+///
+/// Pipelines can be registered from any system, and are available to draw
+/// code once their shader is loaded and compiled. They are deleted when their
+/// shader asset is removed, or with the resource.
+#[derive(Default, Resource)]
+pub struct ShaderPipelines {
+    pipelines: Vec<ShaderPipeline>,
+}
+
+impl ShaderPipelines {
+    pub fn add(&mut self, descriptor: ShaderPipelineDescriptor) -> ShaderPipelineId {
+        self.pipelines.push(ShaderPipeline {
+            descriptor,
+            compiled: None,
+            state: PipelineState::Pending,
+        });
+        ShaderPipelineId(self.pipelines.len() - 1)
+    }
+
+    pub fn get(&self, id: ShaderPipelineId) -> Option<mq::Pipeline> {
+        self.pipelines
+            .get(id.0)
+            .and_then(|pipeline| pipeline.compiled.as_deref())
+            .copied()
+    }
+
+    pub fn descriptor(&self, id: ShaderPipelineId) -> Option<&ShaderPipelineDescriptor> {
+        self.pipelines
+            .get(id.0)
+            .map(|pipeline| &pipeline.descriptor)
+    }
+}
+
+pub(crate) fn compile_shader(
+    ctx: &mut mq::Context,
+    shader: &MiniquadShader,
+) -> Result<mq::ShaderId, String> {
+    let source = match ctx.info().backend {
+        mq::Backend::OpenGl => mq::ShaderSource::Glsl {
+            vertex: &shader.vertex,
+            fragment: &shader.fragment,
+        },
+        mq::Backend::Metal => mq::ShaderSource::Msl {
+            program: shader
+                .metal
+                .as_deref()
+                .ok_or("Shader has no @metal source for the Metal backend")?,
+        },
+    };
+    ctx.new_shader(source, shader.meta())
+        .map_err(|error| error.to_string())
+}
+
+fn prepare_shader_pipelines(
+    mut ctx: NonSendMut<MiniquadContext>,
+    deletion_queue: Res<GpuDeletionQueue>,
+    mut events: EventReader<AssetEvent<MiniquadShader>>,
+    shaders: Res<Assets<MiniquadShader>>,
+    mut pipelines: ResMut<ShaderPipelines>,
+    mut errors: EventWriter<ShaderCompileError>,
+) {
+    let ctx = &mut *ctx.0;
+
+    for event in events.read() {
+        let (AssetEvent::Modified { id } | AssetEvent::Removed { id }) = event else {
+            continue;
+        };
+        for pipeline in pipelines.pipelines.iter_mut() {
+            if pipeline.descriptor.shader.id() == *id {
+                // Compiled again if the shader is added back.
+                if let AssetEvent::Removed { .. } = event {
+                    pipeline.compiled = None;
+                }
+                pipeline.state = PipelineState::Pending;
+            }
+        }
+    }
+
+    for (index, pipeline) in pipelines.pipelines.iter_mut().enumerate() {
+        if pipeline.state != PipelineState::Pending {
+            continue;
+        }
+        let Some(shader) = shaders.get(&pipeline.descriptor.shader) else {
+            continue;
+        };
+
+        match compile_shader(ctx, shader) {
+            Ok(shader) => {
+                let descriptor = &pipeline.descriptor;
+                let new_pipeline = ctx.new_pipeline(
+                    &descriptor.buffer_layouts,
+                    &descriptor.attributes,
+                    shader,
+                    descriptor.params,
+                );
+                // The previous pipeline, if any, is deleted on the next frame.
+                pipeline.compiled = Some(GpuPipeline::with_shader(
+                    &deletion_queue,
+                    new_pipeline,
+                    shader,
+                ));
+                pipeline.state = PipelineState::Ready;
+            }
+            Err(error) => {
+                log::error!("Cannot compile shader: {}", error);
+                errors.send(ShaderCompileError {
+                    shader: pipeline.descriptor.shader.id(),
+                    pipeline: ShaderPipelineId(index),
+                    error,
+                });
+                pipeline.state = PipelineState::Failed;
+            }
+        }
+    }
+}
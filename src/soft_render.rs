@@ -0,0 +1,1782 @@
+//! Rendering backend drawing on the CPU, for comparing frames with reference
+//! images in tests without a GPU.
+//!
+//! Shaders are Rust closures registered for the GLSL vertex source they replace,
+//! the sprite shader is built in. Triangles and lines are rasterized with depth
+//! testing, culling, blending, scissor rects and nearest or linear texture sampling.
+//! Stencil, mipmaps, multisampling and points are not supported. Buffer data
+//! must be created with `buffer_source::slice` to be read.
+//!
+//! ```ignore
+//! let backend = SoftRenderingBackend::new(320, 240);
+//! let framebuffer = backend.framebuffer();
+//! app.world_mut()
+//!     .insert_non_send_resource(WindowReadback::Soft(framebuffer.clone()));
+//! app.world_mut()
+//!     .insert_non_send_resource(MiniquadContext(Box::new(backend)));
+//! app.world_mut().run_schedule(Draw);
+//! framebuffer.compare_png("tests/golden/sprites.png", 2).unwrap();
+//! ```
+
+use bevy_math::{Mat4, UVec2, Vec2, Vec4};
+use miniquad as mq;
+use std::{cell::RefCell, collections::HashMap, convert::TryInto, fmt, rc::Rc};
+
+use crate::{buffer_source::SliceReader, uniforms::UniformElement};
+
+type VertexFn = dyn Fn(&SoftVertex, &SoftUniforms, &mut [f32]) -> Vec4;
+type FragmentFn = dyn Fn(&[f32], &SoftUniforms, &SoftSamplers) -> Vec4;
+
+/// Shader run by the `SoftRenderingBackend` in place of a GLSL one.
+///
+/// The vertex function returns the clip space position and writes `varyings`
+/// values, interpolated for the fragment function returning the color.
+pub struct SoftShader {
+    varyings: usize,
+    vertex: Box<VertexFn>,
+    fragment: Box<FragmentFn>,
+}
+
+impl SoftShader {
+    pub fn new(
+        varyings: usize,
+        vertex: impl Fn(&SoftVertex, &SoftUniforms, &mut [f32]) -> Vec4 + 'static,
+        fragment: impl Fn(&[f32], &SoftUniforms, &SoftSamplers) -> Vec4 + 'static,
+    ) -> Self {
+        SoftShader {
+            varyings,
+            vertex: Box::new(vertex),
+            fragment: Box::new(fragment),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct AttributeLayout {
+    name: &'static str,
+    format: mq::VertexFormat,
+    buffer_index: usize,
+    offset: usize,
+    stride: usize,
+    /// Instances per element of per-instance attributes.
+    step_rate: Option<usize>,
+}
+
+/// Vertex attributes read by a `SoftShader`.
+pub struct SoftVertex<'a> {
+    attributes: &'a [AttributeLayout],
+    buffers: &'a [&'a [u8]],
+    index: usize,
+    instance: usize,
+}
+
+impl SoftVertex<'_> {
+    /// First four components of the attribute, missing ones are 0 and w is 1
+    /// as in GL. Bytes, shorts and ints are not normalized.
+    pub fn attribute(&self, name: &str) -> Vec4 {
+        let components = self.components(name);
+        let mut value = Vec4::new(0., 0., 0., 1.);
+        for (index, component) in components.iter().take(4).enumerate() {
+            value[index] = *component;
+        }
+        value
+    }
+
+    pub fn attribute_mat4(&self, name: &str) -> Mat4 {
+        let components = self.components(name);
+        let mut columns = [0.; 16];
+        columns[..components.len().min(16)]
+            .copy_from_slice(&components[..components.len().min(16)]);
+        Mat4::from_cols_array(&columns)
+    }
+
+    /// Index of the vertex in the vertex buffers.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn instance(&self) -> usize {
+        self.instance
+    }
+
+    fn components(&self, name: &str) -> Vec<f32> {
+        let Some(attribute) = self
+            .attributes
+            .iter()
+            .find(|attribute| attribute.name == name)
+        else {
+            panic!("No vertex attribute {}", name);
+        };
+        let element = match attribute.step_rate {
+            Some(step_rate) => self.instance / step_rate.max(1),
+            None => self.index,
+        };
+        let start = element * attribute.stride + attribute.offset;
+        let bytes = self.buffers[attribute.buffer_index]
+            .get(start..start + attribute.format.size_bytes() as usize)
+            .unwrap_or_else(|| panic!("Vertex attribute {} out of the buffer", name));
+        use mq::VertexFormat::*;
+        match attribute.format {
+            Float1 | Float2 | Float3 | Float4 | Mat4 => bytes
+                .chunks_exact(4)
+                .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+                .collect(),
+            Byte1 | Byte2 | Byte3 | Byte4 => bytes.iter().map(|byte| *byte as f32).collect(),
+            Short1 | Short2 | Short3 | Short4 => bytes
+                .chunks_exact(2)
+                .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()) as f32)
+                .collect(),
+            Int1 | Int2 | Int3 | Int4 => bytes
+                .chunks_exact(4)
+                .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()) as f32)
+                .collect(),
+        }
+    }
+}
+
+struct UniformSlot {
+    name: String,
+    uniform_type: mq::UniformType,
+    array_count: usize,
+    offset: usize,
+}
+
+/// Uniforms applied for a `SoftShader` draw, laid out as in its `ShaderMeta`.
+pub struct SoftUniforms<'a> {
+    slots: &'a [UniformSlot],
+    bytes: &'a [u8],
+}
+
+impl SoftUniforms<'_> {
+    pub fn get<T: UniformElement + Copy>(&self, name: &str) -> T {
+        self.get_at(name, 0)
+    }
+
+    /// Element `index` of a uniform array.
+    pub fn get_at<T: UniformElement + Copy>(&self, name: &str, index: usize) -> T {
+        let Some(slot) = self.slots.iter().find(|slot| slot.name == name) else {
+            panic!("No uniform {}", name);
+        };
+        assert!(
+            std::mem::discriminant(&slot.uniform_type) == std::mem::discriminant(&T::UNIFORM_TYPE)
+                && index < slot.array_count,
+            "Uniform {} is a {:?} array of {}",
+            name,
+            slot.uniform_type,
+            slot.array_count
+        );
+        let start = slot.offset + index * size_of::<T>();
+        let bytes = &self.bytes[start..start + size_of::<T>()];
+        // Safety: uniform elements are plain floats and ints, valid for any bytes.
+        unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const T) }
+    }
+}
+
+/// Textures bound for a `SoftShader` draw, in `Bindings::images` order.
+pub struct SoftSamplers<'a> {
+    textures: Vec<Option<&'a SoftTexture>>,
+}
+
+impl SoftSamplers<'_> {
+    /// Color of texture `index` at `uv`, with v = 0 on the first uploaded row.
+    pub fn sample(&self, index: usize, uv: Vec2) -> Vec4 {
+        match self.textures.get(index).copied().flatten() {
+            Some(texture) => texture.sample(uv),
+            None => Vec4::new(0., 0., 0., 1.),
+        }
+    }
+}
+
+/// Pixels stored bottom row first, as GL does.
+struct Image {
+    width: u32,
+    height: u32,
+    format: mq::TextureFormat,
+    color: Vec<u8>,
+    /// Empty without a depth attachment.
+    depth: Vec<f32>,
+}
+
+impl Image {
+    fn read(&self, index: usize) -> Vec4 {
+        match self.format {
+            mq::TextureFormat::RGBA8 => {
+                let bytes = &self.color[index * 4..index * 4 + 4];
+                Vec4::new(
+                    bytes[0] as f32 / 255.,
+                    bytes[1] as f32 / 255.,
+                    bytes[2] as f32 / 255.,
+                    bytes[3] as f32 / 255.,
+                )
+            }
+            mq::TextureFormat::RGB8 => {
+                let bytes = &self.color[index * 3..index * 3 + 3];
+                Vec4::new(
+                    bytes[0] as f32 / 255.,
+                    bytes[1] as f32 / 255.,
+                    bytes[2] as f32 / 255.,
+                    1.,
+                )
+            }
+            mq::TextureFormat::RGBA16F => {
+                let bytes = &self.color[index * 8..index * 8 + 8];
+                let component =
+                    |i: usize| f16_to_f32(u16::from_le_bytes([bytes[i * 2], bytes[i * 2 + 1]]));
+                Vec4::new(component(0), component(1), component(2), component(3))
+            }
+            mq::TextureFormat::Alpha => Vec4::new(0., 0., 0., self.color[index] as f32 / 255.),
+            mq::TextureFormat::Depth | mq::TextureFormat::Depth32 => {
+                let depth = self.depth[index];
+                Vec4::new(depth, depth, depth, 1.)
+            }
+        }
+    }
+
+    fn write(&mut self, index: usize, color: Vec4, mask: (bool, bool, bool, bool)) {
+        let mask = [mask.0, mask.1, mask.2, mask.3];
+        match self.format {
+            mq::TextureFormat::RGBA8 | mq::TextureFormat::RGB8 | mq::TextureFormat::Alpha => {
+                let (channels, first) = match self.format {
+                    mq::TextureFormat::RGBA8 => (4, 0),
+                    mq::TextureFormat::RGB8 => (3, 0),
+                    _ => (1, 3),
+                };
+                for channel in 0..channels {
+                    if mask[first + channel] {
+                        self.color[index * channels + channel] =
+                            (color[first + channel].clamp(0., 1.) * 255.).round() as u8;
+                    }
+                }
+            }
+            mq::TextureFormat::RGBA16F => {
+                for channel in 0..4 {
+                    if mask[channel] {
+                        let bytes = f32_to_f16(color[channel]).to_le_bytes();
+                        self.color[index * 8 + channel * 2..index * 8 + channel * 2 + 2]
+                            .copy_from_slice(&bytes);
+                    }
+                }
+            }
+            mq::TextureFormat::Depth | mq::TextureFormat::Depth32 => {}
+        }
+    }
+}
+
+/// Texture of the `SoftRenderingBackend`.
+struct SoftTexture {
+    raw_id: u32,
+    params: mq::TextureParams,
+    image: Image,
+}
+
+impl SoftTexture {
+    fn texel(&self, x: i32, y: i32) -> Vec4 {
+        let wrap = |coordinate: i32, size: u32| {
+            let size = size as i32;
+            match self.params.wrap {
+                mq::TextureWrap::Repeat => coordinate.rem_euclid(size),
+                mq::TextureWrap::Mirror => {
+                    let coordinate = coordinate.rem_euclid(size * 2);
+                    if coordinate < size {
+                        coordinate
+                    } else {
+                        size * 2 - 1 - coordinate
+                    }
+                }
+                mq::TextureWrap::Clamp => coordinate.clamp(0, size - 1),
+            }
+        };
+        let (x, y) = (wrap(x, self.params.width), wrap(y, self.params.height));
+        self.image
+            .read(y as usize * self.params.width as usize + x as usize)
+    }
+
+    fn sample(&self, uv: Vec2) -> Vec4 {
+        if self.params.width == 0
+            || self.params.height == 0
+            || self.image.color.is_empty() && self.image.depth.is_empty()
+        {
+            return Vec4::ZERO;
+        }
+        let position = uv * Vec2::new(self.params.width as f32, self.params.height as f32);
+        match self.params.mag_filter {
+            mq::FilterMode::Nearest => {
+                let position = position.floor();
+                self.texel(position.x as i32, position.y as i32)
+            }
+            mq::FilterMode::Linear => {
+                let position = position - 0.5;
+                let (floor, fract) = (position.floor(), position.fract_gl());
+                let (x, y) = (floor.x as i32, floor.y as i32);
+                let top = self.texel(x, y).lerp(self.texel(x + 1, y), fract.x);
+                let bottom = self.texel(x, y + 1).lerp(self.texel(x + 1, y + 1), fract.x);
+                top.lerp(bottom, fract.y)
+            }
+        }
+    }
+}
+
+/// Default framebuffer of a `SoftRenderingBackend`, readable once the backend
+/// is boxed in the `MiniquadContext`.
+#[derive(Clone)]
+pub struct SoftFramebuffer(Rc<RefCell<Image>>);
+
+impl SoftFramebuffer {
+    pub fn size(&self) -> UVec2 {
+        let image = self.0.borrow();
+        UVec2::new(image.width, image.height)
+    }
+
+    /// RGBA8 pixels, rows from the top as in `ScreenshotCaptured`.
+    pub fn pixels(&self) -> Vec<u8> {
+        let image = self.0.borrow();
+        image
+            .color
+            .chunks_exact(image.width as usize * 4)
+            .rev()
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    pub fn save_png(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        let size = self.size();
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let mut encoder = png::Encoder::new(file, size.x, size.y);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.pixels()))
+            .map_err(std::io::Error::other)
+    }
+
+    /// Compares the pixels with a reference PNG, channels may differ by up to
+    /// `tolerance`.
+    pub fn compare_png(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        tolerance: u8,
+    ) -> Result<(), GoldenImageError> {
+        let file =
+            std::io::BufReader::new(std::fs::File::open(path).map_err(GoldenImageError::Io)?);
+        let mut decoder = png::Decoder::new(file);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info().map_err(GoldenImageError::Decode)?;
+        let mut expected = vec![0; reader.output_buffer_size().unwrap_or_default()];
+        let info = reader
+            .next_frame(&mut expected)
+            .map_err(GoldenImageError::Decode)?;
+
+        let size = self.size();
+        let expected_size = UVec2::new(info.width, info.height);
+        if size != expected_size {
+            return Err(GoldenImageError::SizeMismatch {
+                expected: expected_size,
+                actual: size,
+            });
+        }
+        let expected: Vec<[u8; 4]> = match info.color_type {
+            png::ColorType::Rgba => expected
+                .chunks_exact(4)
+                .map(|pixel| pixel.try_into().unwrap())
+                .collect(),
+            png::ColorType::Rgb => expected
+                .chunks_exact(3)
+                .map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => expected
+                .chunks_exact(2)
+                .map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+                .collect(),
+            png::ColorType::Grayscale | png::ColorType::Indexed => expected
+                .iter()
+                .map(|gray| [*gray, *gray, *gray, 255])
+                .collect(),
+        };
+
+        let (mut pixels, mut max_difference) = (0, 0);
+        for (actual, expected) in self.pixels().chunks_exact(4).zip(expected) {
+            let difference = (0..4)
+                .map(|channel| actual[channel].abs_diff(expected[channel]))
+                .max()
+                .unwrap_or_default();
+            if difference > tolerance {
+                pixels += 1;
+                max_difference = max_difference.max(difference);
+            }
+        }
+        if pixels > 0 {
+            return Err(GoldenImageError::PixelMismatch {
+                pixels,
+                max_difference,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum GoldenImageError {
+    Io(std::io::Error),
+    Decode(png::DecodingError),
+    SizeMismatch {
+        expected: UVec2,
+        actual: UVec2,
+    },
+    /// Pixels with a channel differing by more than the tolerance.
+    PixelMismatch {
+        pixels: usize,
+        max_difference: u8,
+    },
+}
+
+impl fmt::Display for GoldenImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GoldenImageError::Io(error) => write!(f, "Cannot read reference image: {}", error),
+            GoldenImageError::Decode(error) => {
+                write!(f, "Cannot decode reference image: {}", error)
+            }
+            GoldenImageError::SizeMismatch { expected, actual } => {
+                write!(f, "Frame is {} but reference image is {}", actual, expected)
+            }
+            GoldenImageError::PixelMismatch {
+                pixels,
+                max_difference,
+            } => write!(
+                f,
+                "{} pixels differ from the reference image, by up to {}",
+                pixels, max_difference
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GoldenImageError {}
+
+struct SoftBuffer {
+    data: Vec<u8>,
+    element_size: usize,
+}
+
+struct SoftProgram {
+    /// `None` for GLSL sources without a registered `SoftShader`, whose draws
+    /// are skipped.
+    shader: Option<Rc<SoftShader>>,
+    uniforms: Rc<[UniformSlot]>,
+}
+
+struct SoftPipeline {
+    shader: mq::ShaderId,
+    attributes: Rc<[AttributeLayout]>,
+    params: mq::PipelineParams,
+    /// Color and alpha blending of `params`.
+    blend: Option<(BlendParts, BlendParts)>,
+}
+
+struct SoftPass {
+    pass: mq::RenderPass,
+    color: Vec<mq::TextureId>,
+    depth: Option<mq::TextureId>,
+}
+
+#[derive(Clone, Copy)]
+struct Rect {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+impl Rect {
+    fn intersect(self, other: Rect) -> Rect {
+        let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+        Rect {
+            x,
+            y,
+            width: ((self.x + self.width).min(other.x + other.width) - x).max(0),
+            height: ((self.y + self.height).min(other.y + other.height) - y).max(0),
+        }
+    }
+}
+
+/// miniquad `RenderingBackend` rasterizing on the CPU into RGBA8 images,
+/// the default framebuffer being readable through `framebuffer`.
+///
+/// It reports the GL backend, so images are stored bottom row first.
+pub struct SoftRenderingBackend {
+    framebuffer: SoftFramebuffer,
+    registered_shaders: HashMap<String, Rc<SoftShader>>,
+    next_id: usize,
+    shaders: HashMap<mq::ShaderId, SoftProgram>,
+    pipelines: HashMap<mq::Pipeline, SoftPipeline>,
+    buffers: HashMap<mq::BufferId, SoftBuffer>,
+    textures: RefCell<HashMap<mq::TextureId, SoftTexture>>,
+    passes: Vec<SoftPass>,
+    current_pass: Option<mq::RenderPass>,
+    viewport: Rect,
+    scissor: Rect,
+    /// GL only tests the scissor rect from `apply_pipeline` to the end of the pass.
+    scissor_enabled: bool,
+    pipeline: Option<mq::Pipeline>,
+    bindings: Option<mq::Bindings>,
+    uniforms: Vec<u8>,
+    slices: SliceReader,
+}
+
+/// miniquad ids other than textures wrap a private `usize`.
+///
+/// # Safety
+///
+/// `T` must be a struct with a single `usize` field.
+unsafe fn id_from_usize<T: Copy>(id: usize) -> T {
+    const { assert!(size_of::<T>() == size_of::<usize>()) };
+    std::mem::transmute_copy(&id)
+}
+
+impl SoftRenderingBackend {
+    /// Backend drawing to a `width` by `height` default framebuffer, which
+    /// should match the `Window` size.
+    pub fn new(width: u32, height: u32) -> Self {
+        let pixels = width as usize * height as usize;
+        #[allow(unused_mut)]
+        let mut backend = SoftRenderingBackend {
+            framebuffer: SoftFramebuffer(Rc::new(RefCell::new(Image {
+                width,
+                height,
+                format: mq::TextureFormat::RGBA8,
+                color: vec![0; pixels * 4],
+                depth: vec![1.; pixels],
+            }))),
+            registered_shaders: HashMap::new(),
+            next_id: 1,
+            shaders: HashMap::new(),
+            pipelines: HashMap::new(),
+            buffers: HashMap::new(),
+            textures: RefCell::new(HashMap::new()),
+            passes: Vec::new(),
+            current_pass: None,
+            viewport: Rect {
+                x: 0,
+                y: 0,
+                width: width as i32,
+                height: height as i32,
+            },
+            scissor: Rect {
+                x: 0,
+                y: 0,
+                width: width as i32,
+                height: height as i32,
+            },
+            scissor_enabled: false,
+            pipeline: None,
+            bindings: None,
+            uniforms: Vec::new(),
+            slices: SliceReader::copies(),
+        };
+        #[cfg(feature = "sprite")]
+        backend.add_shader(crate::sprite::shader::VERTEX, sprite_shader());
+        backend
+    }
+
+    /// Handle to the default framebuffer.
+    pub fn framebuffer(&self) -> SoftFramebuffer {
+        self.framebuffer.clone()
+    }
+
+    /// Runs `shader` for shaders created with the GLSL `vertex_source`.
+    pub fn add_shader(&mut self, vertex_source: &str, shader: SoftShader) {
+        self.registered_shaders
+            .insert(vertex_source.to_string(), Rc::new(shader));
+    }
+
+    fn next_id(&mut self) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    fn pass(&self, pass: mq::RenderPass) -> &SoftPass {
+        self.passes
+            .iter()
+            .find(|soft_pass| soft_pass.pass == pass)
+            .expect("Render pass does not exist or was deleted")
+    }
+
+    /// Moves the images of the current pass out of their textures while drawing,
+    /// `None` for the default framebuffer.
+    fn take_target(&self) -> Option<Image> {
+        let pass = self.pass(self.current_pass?);
+        let mut textures = self.textures.borrow_mut();
+        let color = pass.color.first().and_then(|id| textures.get_mut(id));
+        let (width, height, format, color) = match color {
+            Some(texture) => (
+                texture.params.width,
+                texture.params.height,
+                texture.params.format,
+                std::mem::take(&mut texture.image.color),
+            ),
+            None => (0, 0, mq::TextureFormat::RGBA8, Vec::new()),
+        };
+        let depth = pass
+            .depth
+            .and_then(|id| textures.get_mut(&id))
+            .map(|texture| std::mem::take(&mut texture.image.depth))
+            .unwrap_or_default();
+        Some(Image {
+            width,
+            height,
+            format,
+            color,
+            depth,
+        })
+    }
+
+    fn restore_target(&self, image: Image) {
+        let Some(pass) = self.current_pass.map(|pass| self.pass(pass)) else {
+            return;
+        };
+        let mut textures = self.textures.borrow_mut();
+        if let Some(texture) = pass.color.first().and_then(|id| textures.get_mut(id)) {
+            texture.image.color = image.color;
+        }
+        if let Some(texture) = pass.depth.and_then(|id| textures.get_mut(&id)) {
+            texture.image.depth = image.depth;
+        }
+    }
+
+    fn target_size(&self) -> (i32, i32) {
+        match self.current_pass {
+            None => {
+                let size = self.framebuffer.size();
+                (size.x as i32, size.y as i32)
+            }
+            Some(pass) => {
+                let pass = self.pass(pass);
+                let textures = self.textures.borrow();
+                pass.color
+                    .first()
+                    .copied()
+                    .or(pass.depth)
+                    .and_then(|id| textures.get(&id))
+                    .map_or((0, 0), |texture| {
+                        (texture.params.width as i32, texture.params.height as i32)
+                    })
+            }
+        }
+    }
+
+    fn with_target(&self, draw: impl FnOnce(&mut Image)) {
+        match self.take_target() {
+            Some(mut image) => {
+                draw(&mut image);
+                self.restore_target(image);
+            }
+            None => draw(&mut self.framebuffer.0.borrow_mut()),
+        }
+    }
+
+    fn clip_rect(&self, image: &Image) -> Rect {
+        let target = Rect {
+            x: 0,
+            y: 0,
+            width: image.width as i32,
+            height: image.height as i32,
+        };
+        if self.scissor_enabled {
+            target.intersect(self.scissor)
+        } else {
+            target
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ClipVertex {
+    position: Vec4,
+    varyings: Vec<f32>,
+}
+
+impl ClipVertex {
+    fn lerp(&self, other: &ClipVertex, t: f32) -> ClipVertex {
+        ClipVertex {
+            position: self.position.lerp(other.position, t),
+            varyings: self
+                .varyings
+                .iter()
+                .zip(&other.varyings)
+                .map(|(a, b)| a + (b - a) * t)
+                .collect(),
+        }
+    }
+}
+
+/// Vertex after the perspective divide, in target pixels.
+struct ScreenVertex<'a> {
+    position: Vec2,
+    depth: f32,
+    inverse_w: f32,
+    varyings: &'a [f32],
+}
+
+/// Whether the point is in front of the near plane.
+fn in_front(vertex: &ClipVertex) -> bool {
+    vertex.position.z + vertex.position.w >= 0.
+}
+
+/// Triangle or line clipped by the near plane, triangles becoming polygons.
+fn clip_near(vertices: &[ClipVertex]) -> Vec<ClipVertex> {
+    if vertices.iter().all(in_front) {
+        return vertices.to_vec();
+    }
+    let intersect = |a: &ClipVertex, b: &ClipVertex| {
+        let (da, db) = (a.position.z + a.position.w, b.position.z + b.position.w);
+        a.lerp(b, da / (da - db))
+    };
+    if let [a, b] = vertices {
+        return match (in_front(a), in_front(b)) {
+            (true, false) => vec![a.clone(), intersect(a, b)],
+            (false, true) => vec![intersect(a, b), b.clone()],
+            _ => Vec::new(),
+        };
+    }
+    let mut clipped = Vec::with_capacity(vertices.len() + 1);
+    for (index, a) in vertices.iter().enumerate() {
+        let b = &vertices[(index + 1) % vertices.len()];
+        if in_front(a) {
+            clipped.push(a.clone());
+        }
+        if in_front(a) != in_front(b) {
+            clipped.push(intersect(a, b));
+        }
+    }
+    clipped
+}
+
+/// Cross product of `b - a` and `p - a`, positive when `p` is left of `a -> b`.
+fn edge(a: Vec2, b: Vec2, p: Vec2) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+/// Whether pixel centers on the edge `a -> b` of a counter clockwise triangle
+/// are drawn, so pixels on edges shared by two triangles are drawn once.
+fn is_top_left(a: Vec2, b: Vec2) -> bool {
+    (a.y == b.y && b.x < a.x) || b.y < a.y
+}
+
+fn compare(comparison: mq::Comparison, value: f32, stored: f32) -> bool {
+    match comparison {
+        mq::Comparison::Never => false,
+        mq::Comparison::Less => value < stored,
+        mq::Comparison::LessOrEqual => value <= stored,
+        mq::Comparison::Greater => value > stored,
+        mq::Comparison::GreaterOrEqual => value >= stored,
+        mq::Comparison::Equal => value == stored,
+        mq::Comparison::NotEqual => value != stored,
+        mq::Comparison::Always => true,
+    }
+}
+
+fn blend_factor(factor: mq::BlendFactor, source: Vec4, destination: Vec4) -> Vec4 {
+    let value = |value: mq::BlendValue| match value {
+        mq::BlendValue::SourceColor => source,
+        mq::BlendValue::SourceAlpha => Vec4::splat(source.w),
+        mq::BlendValue::DestinationColor => destination,
+        mq::BlendValue::DestinationAlpha => Vec4::splat(destination.w),
+    };
+    match factor {
+        mq::BlendFactor::Zero => Vec4::ZERO,
+        mq::BlendFactor::One => Vec4::ONE,
+        mq::BlendFactor::Value(factor) => value(factor),
+        mq::BlendFactor::OneMinusValue(factor) => Vec4::ONE - value(factor),
+        mq::BlendFactor::SourceAlphaSaturate => {
+            let factor = source.w.min(1. - destination.w);
+            Vec4::new(factor, factor, factor, 1.)
+        }
+    }
+}
+
+/// Equation, source and destination factors of a `BlendState`.
+#[derive(Clone, Copy)]
+struct BlendParts(mq::Equation, mq::BlendFactor, mq::BlendFactor);
+
+/// Parts of a `BlendState`, whose fields are private, found among all the
+/// possible ones.
+fn blend_state_parts(state: mq::BlendState) -> BlendParts {
+    use mq::{BlendFactor::*, BlendValue::*};
+    let values = [SourceColor, SourceAlpha, DestinationColor, DestinationAlpha];
+    let factors: Vec<mq::BlendFactor> = IntoIterator::into_iter([Zero, One, SourceAlphaSaturate])
+        .chain(values.map(Value))
+        .chain(values.map(OneMinusValue))
+        .collect();
+    let equations = [
+        mq::Equation::Add,
+        mq::Equation::Subtract,
+        mq::Equation::ReverseSubtract,
+    ];
+    for &equation in &equations {
+        for &sfactor in &factors {
+            for &dfactor in &factors {
+                if mq::BlendState::new(equation, sfactor, dfactor) == state {
+                    return BlendParts(equation, sfactor, dfactor);
+                }
+            }
+        }
+    }
+    unreachable!("Unknown blend state {:?}", state)
+}
+
+fn blend_state(parts: BlendParts, source: Vec4, destination: Vec4) -> Vec4 {
+    let BlendParts(equation, sfactor, dfactor) = parts;
+    let source_term = source * blend_factor(sfactor, source, destination);
+    let destination_term = destination * blend_factor(dfactor, source, destination);
+    match equation {
+        mq::Equation::Add => source_term + destination_term,
+        mq::Equation::Subtract => source_term - destination_term,
+        mq::Equation::ReverseSubtract => destination_term - source_term,
+    }
+}
+
+/// Color and alpha blending of pipeline `params`, decoded once per pipeline.
+fn pipeline_blend(params: &mq::PipelineParams) -> Option<(BlendParts, BlendParts)> {
+    let color_blend = params.color_blend?;
+    Some((
+        blend_state_parts(color_blend),
+        blend_state_parts(params.alpha_blend.unwrap_or(color_blend)),
+    ))
+}
+
+fn blend(blend: Option<(BlendParts, BlendParts)>, source: Vec4, destination: Vec4) -> Vec4 {
+    let Some((color_blend, alpha_blend)) = blend else {
+        return source;
+    };
+    let color = blend_state(color_blend, source, destination);
+    let alpha = blend_state(alpha_blend, source, destination);
+    color.truncate().extend(alpha.w)
+}
+
+/// State of a draw call.
+struct Raster<'a> {
+    shader: &'a SoftShader,
+    uniforms: SoftUniforms<'a>,
+    samplers: SoftSamplers<'a>,
+    params: &'a mq::PipelineParams,
+    blend: Option<(BlendParts, BlendParts)>,
+    viewport: Rect,
+    clip: Rect,
+}
+
+impl Raster<'_> {
+    fn to_screen<'v>(&self, vertex: &'v ClipVertex) -> ScreenVertex<'v> {
+        let inverse_w = 1. / vertex.position.w;
+        let ndc = vertex.position.truncate() * inverse_w;
+        ScreenVertex {
+            position: Vec2::new(
+                self.viewport.x as f32 + (ndc.x * 0.5 + 0.5) * self.viewport.width as f32,
+                self.viewport.y as f32 + (ndc.y * 0.5 + 0.5) * self.viewport.height as f32,
+            ),
+            depth: ndc.z * 0.5 + 0.5,
+            inverse_w,
+            varyings: &vertex.varyings,
+        }
+    }
+
+    fn shade(&self, image: &mut Image, x: i32, y: i32, depth: f32, varyings: &[f32]) {
+        let index = y as usize * image.width as usize + x as usize;
+        // miniquad only tests depth while writing it.
+        if self.params.depth_write && !image.depth.is_empty() {
+            if !compare(self.params.depth_test, depth, image.depth[index]) {
+                return;
+            }
+            image.depth[index] = depth;
+        }
+        if image.color.is_empty() {
+            return;
+        }
+        let color = (self.shader.fragment)(varyings, &self.uniforms, &self.samplers);
+        let color = blend(
+            self.blend,
+            color.clamp(Vec4::ZERO, Vec4::ONE),
+            image.read(index),
+        );
+        image.write(index, color, self.params.color_write);
+    }
+
+    fn triangle(&self, image: &mut Image, vertices: [&ClipVertex; 3]) {
+        let [a, mut b, mut c] = vertices.map(|vertex| self.to_screen(vertex));
+        let area = edge(a.position, b.position, c.position);
+        if area == 0. || !area.is_finite() {
+            return;
+        }
+        let front =
+            (area > 0.) == (self.params.front_face_order == mq::FrontFaceOrder::CounterClockwise);
+        match self.params.cull_face {
+            mq::CullFace::Back if !front => return,
+            mq::CullFace::Front if front => return,
+            _ => {}
+        }
+        if area < 0. {
+            std::mem::swap(&mut b, &mut c);
+        }
+        let area = area.abs();
+
+        let min = a.position.min(b.position).min(c.position).floor();
+        let max = a.position.max(b.position).max(c.position).ceil();
+        let bounds = Rect {
+            x: min.x as i32,
+            y: min.y as i32,
+            width: (max.x - min.x) as i32 + 1,
+            height: (max.y - min.y) as i32 + 1,
+        }
+        .intersect(self.viewport)
+        .intersect(self.clip);
+
+        let mut varyings = vec![0.; self.shader.varyings];
+        for y in bounds.y..bounds.y + bounds.height {
+            for x in bounds.x..bounds.x + bounds.width {
+                let p = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let weights = [
+                    (
+                        edge(b.position, c.position, p),
+                        is_top_left(b.position, c.position),
+                    ),
+                    (
+                        edge(c.position, a.position, p),
+                        is_top_left(c.position, a.position),
+                    ),
+                    (
+                        edge(a.position, b.position, p),
+                        is_top_left(a.position, b.position),
+                    ),
+                ];
+                if weights
+                    .iter()
+                    .any(|(weight, top_left)| *weight < 0. || *weight == 0. && !top_left)
+                {
+                    continue;
+                }
+                let [wa, wb, wc] = weights.map(|(weight, _)| weight / area);
+                let depth = a.depth * wa + b.depth * wb + c.depth * wc;
+                // Perspective correct interpolation.
+                let [pa, pb, pc] = [wa * a.inverse_w, wb * b.inverse_w, wc * c.inverse_w];
+                let sum = pa + pb + pc;
+                for (index, varying) in varyings.iter_mut().enumerate() {
+                    *varying =
+                        (a.varyings[index] * pa + b.varyings[index] * pb + c.varyings[index] * pc)
+                            / sum;
+                }
+                self.shade(image, x, y, depth, &varyings);
+            }
+        }
+    }
+
+    fn line(&self, image: &mut Image, vertices: [&ClipVertex; 2]) {
+        let [a, b] = vertices.map(|vertex| self.to_screen(vertex));
+        let delta = b.position - a.position;
+        let steps = delta.x.abs().max(delta.y.abs()).ceil().max(1.) as usize;
+        let bounds = self.viewport.intersect(self.clip);
+        let mut varyings = vec![0.; self.shader.varyings];
+        for step in 0..steps {
+            let t = (step as f32 + 0.5) / steps as f32;
+            let p = a.position + delta * t;
+            let (x, y) = (p.x.floor() as i32, p.y.floor() as i32);
+            if x < bounds.x
+                || y < bounds.y
+                || x >= bounds.x + bounds.width
+                || y >= bounds.y + bounds.height
+            {
+                continue;
+            }
+            let (pa, pb) = ((1. - t) * a.inverse_w, t * b.inverse_w);
+            for (index, varying) in varyings.iter_mut().enumerate() {
+                *varying = (a.varyings[index] * pa + b.varyings[index] * pb) / (pa + pb);
+            }
+            self.shade(image, x, y, a.depth + (b.depth - a.depth) * t, &varyings);
+        }
+    }
+}
+
+impl mq::RenderingBackend for SoftRenderingBackend {
+    fn info(&self) -> mq::ContextInfo {
+        mq::ContextInfo {
+            backend: mq::Backend::OpenGl,
+            gl_version_string: String::new(),
+            glsl_support: mq::GlslSupport {
+                v100: true,
+                ..Default::default()
+            },
+            features: mq::Features {
+                instancing: true,
+                resolve_attachments: false,
+            },
+        }
+    }
+
+    fn new_shader(
+        &mut self,
+        source: mq::ShaderSource,
+        meta: mq::ShaderMeta,
+    ) -> Result<mq::ShaderId, mq::ShaderError> {
+        let shader = match source {
+            mq::ShaderSource::Glsl { vertex, .. } => self.registered_shaders.get(vertex).cloned(),
+            mq::ShaderSource::Msl { .. } => None,
+        };
+        if shader.is_none() {
+            log::warn!("No soft shader registered for a shader, its draws are skipped");
+        }
+        let mut offset = 0;
+        let uniforms = meta
+            .uniforms
+            .uniforms
+            .into_iter()
+            .map(|uniform| {
+                let slot = UniformSlot {
+                    offset,
+                    uniform_type: uniform.uniform_type,
+                    array_count: uniform.array_count,
+                    name: uniform.name,
+                };
+                offset += slot.uniform_type.size() * slot.array_count;
+                slot
+            })
+            .collect();
+        // Safety: `ShaderId` wraps a `usize`.
+        let id = unsafe { id_from_usize(self.next_id()) };
+        self.shaders.insert(id, SoftProgram { shader, uniforms });
+        Ok(id)
+    }
+
+    fn new_texture(
+        &mut self,
+        _access: mq::TextureAccess,
+        source: mq::TextureSource,
+        params: mq::TextureParams,
+    ) -> mq::TextureId {
+        let raw_id = self.next_id() as u32;
+        let texture = mq::TextureId::from_raw_id(mq::RawId::OpenGl(raw_id));
+        let pixels = params.width as usize * params.height as usize;
+        let image = match params.format {
+            mq::TextureFormat::Depth | mq::TextureFormat::Depth32 => Image {
+                width: params.width,
+                height: params.height,
+                format: params.format,
+                color: Vec::new(),
+                depth: vec![1.; pixels],
+            },
+            format => {
+                let mut color = match source {
+                    mq::TextureSource::Bytes(bytes) => bytes.to_vec(),
+                    mq::TextureSource::Array(faces) => faces
+                        .first()
+                        .and_then(|levels| levels.first())
+                        .map_or_else(Vec::new, |bytes| bytes.to_vec()),
+                    mq::TextureSource::Empty => Vec::new(),
+                };
+                color.resize(format.size(params.width, params.height) as usize, 0);
+                Image {
+                    width: params.width,
+                    height: params.height,
+                    format,
+                    color,
+                    depth: Vec::new(),
+                }
+            }
+        };
+        self.textures.get_mut().insert(
+            texture,
+            SoftTexture {
+                raw_id,
+                params,
+                image,
+            },
+        );
+        texture
+    }
+
+    fn texture_params(&self, texture: mq::TextureId) -> mq::TextureParams {
+        self.textures.borrow()[&texture].params
+    }
+
+    unsafe fn texture_raw_id(&self, texture: mq::TextureId) -> mq::RawId {
+        mq::RawId::OpenGl(self.textures.borrow()[&texture].raw_id)
+    }
+
+    fn texture_set_min_filter(
+        &mut self,
+        texture: mq::TextureId,
+        filter: mq::FilterMode,
+        mipmap_filter: mq::MipmapFilterMode,
+    ) {
+        if let Some(texture) = self.textures.get_mut().get_mut(&texture) {
+            texture.params.min_filter = filter;
+            texture.params.mipmap_filter = mipmap_filter;
+        }
+    }
+
+    fn texture_set_mag_filter(&mut self, texture: mq::TextureId, filter: mq::FilterMode) {
+        if let Some(texture) = self.textures.get_mut().get_mut(&texture) {
+            texture.params.mag_filter = filter;
+        }
+    }
+
+    fn texture_set_wrap(
+        &mut self,
+        texture: mq::TextureId,
+        wrap_x: mq::TextureWrap,
+        _wrap_y: mq::TextureWrap,
+    ) {
+        if let Some(texture) = self.textures.get_mut().get_mut(&texture) {
+            texture.params.wrap = wrap_x;
+        }
+    }
+
+    fn texture_generate_mipmaps(&mut self, _texture: mq::TextureId) {}
+
+    fn texture_resize(
+        &mut self,
+        texture: mq::TextureId,
+        width: u32,
+        height: u32,
+        bytes: Option<&[u8]>,
+    ) {
+        let Some(texture) = self.textures.get_mut().get_mut(&texture) else {
+            return;
+        };
+        let format = texture.params.format;
+        texture.params.width = width;
+        texture.params.height = height;
+        texture.image.width = width;
+        texture.image.height = height;
+        if matches!(
+            format,
+            mq::TextureFormat::Depth | mq::TextureFormat::Depth32
+        ) {
+            texture.image.depth = vec![1.; width as usize * height as usize];
+        } else {
+            texture.image.color = bytes.map_or_else(Vec::new, <[u8]>::to_vec);
+            texture
+                .image
+                .color
+                .resize(format.size(width, height) as usize, 0);
+        }
+    }
+
+    fn texture_read_pixels(&mut self, texture: mq::TextureId, bytes: &mut [u8]) {
+        let textures = self.textures.get_mut();
+        let data = &textures[&texture].image.color;
+        let len = bytes.len().min(data.len());
+        bytes[..len].copy_from_slice(&data[..len]);
+    }
+
+    fn texture_update_part(
+        &mut self,
+        texture: mq::TextureId,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        bytes: &[u8],
+    ) {
+        let Some(texture) = self.textures.get_mut().get_mut(&texture) else {
+            return;
+        };
+        let pixel_size = texture.params.format.size(1, 1) as usize;
+        let texture_row = texture.params.width as usize * pixel_size;
+        let row = width as usize * pixel_size;
+        if row == 0 {
+            return;
+        }
+        for (index, source) in bytes.chunks_exact(row).take(height as usize).enumerate() {
+            let start = (y as usize + index) * texture_row + x as usize * pixel_size;
+            if let Some(destination) = texture.image.color.get_mut(start..start + row) {
+                destination.copy_from_slice(source);
+            }
+        }
+    }
+
+    fn new_render_pass_mrt(
+        &mut self,
+        color: &[mq::TextureId],
+        _resolve: Option<&[mq::TextureId]>,
+        depth: Option<mq::TextureId>,
+    ) -> mq::RenderPass {
+        // Safety: `RenderPass` wraps a `usize`.
+        let pass = unsafe { id_from_usize(self.next_id()) };
+        self.passes.push(SoftPass {
+            pass,
+            color: color.to_vec(),
+            depth,
+        });
+        pass
+    }
+
+    fn render_pass_color_attachments(&self, render_pass: mq::RenderPass) -> &[mq::TextureId] {
+        &self.pass(render_pass).color
+    }
+
+    fn delete_render_pass(&mut self, render_pass: mq::RenderPass) {
+        self.passes.retain(|pass| pass.pass != render_pass);
+    }
+
+    fn new_pipeline(
+        &mut self,
+        buffer_layouts: &[mq::BufferLayout],
+        attributes: &[mq::VertexAttribute],
+        shader: mq::ShaderId,
+        params: mq::PipelineParams,
+    ) -> mq::Pipeline {
+        // Attributes follow each other in their buffer, which strides over all
+        // of them unless the layout sets the stride.
+        let mut strides = vec![0; buffer_layouts.len()];
+        for attribute in attributes {
+            strides[attribute.buffer_index] += attribute.format.size_bytes() as usize;
+        }
+        let mut offsets = vec![0; buffer_layouts.len()];
+        let attributes = attributes
+            .iter()
+            .map(|attribute| {
+                let layout = &buffer_layouts[attribute.buffer_index];
+                let offset = offsets[attribute.buffer_index];
+                offsets[attribute.buffer_index] += attribute.format.size_bytes() as usize;
+                AttributeLayout {
+                    name: attribute.name,
+                    format: attribute.format,
+                    buffer_index: attribute.buffer_index,
+                    offset,
+                    stride: match layout.stride {
+                        0 => strides[attribute.buffer_index],
+                        stride => stride as usize,
+                    },
+                    step_rate: (layout.step_func == mq::VertexStep::PerInstance)
+                        .then_some(layout.step_rate as usize),
+                }
+            })
+            .collect();
+        // Safety: `Pipeline` wraps a `usize`.
+        let pipeline = unsafe { id_from_usize(self.next_id()) };
+        self.pipelines.insert(
+            pipeline,
+            SoftPipeline {
+                shader,
+                attributes,
+                blend: pipeline_blend(&params),
+                params,
+            },
+        );
+        pipeline
+    }
+
+    fn apply_pipeline(&mut self, pipeline: &mq::Pipeline) {
+        self.pipeline = Some(*pipeline);
+        self.scissor_enabled = true;
+    }
+
+    fn delete_pipeline(&mut self, pipeline: mq::Pipeline) {
+        self.pipelines.remove(&pipeline);
+    }
+
+    fn new_buffer(
+        &mut self,
+        _buffer_type: mq::BufferType,
+        _usage: mq::BufferUsage,
+        data: mq::BufferSource,
+    ) -> mq::BufferId {
+        let copy = self.slices.take(&data);
+        // Safety: `BufferId` wraps a `usize`.
+        let buffer = unsafe { id_from_usize(self.next_id()) };
+        self.buffers.insert(
+            buffer,
+            SoftBuffer {
+                data: copy.bytes,
+                element_size: copy.element_size,
+            },
+        );
+        buffer
+    }
+
+    fn buffer_update(&mut self, buffer: mq::BufferId, data: mq::BufferSource) {
+        let data = self.slices.take(&data).bytes;
+        if let Some(buffer) = self.buffers.get_mut(&buffer) {
+            let len = data.len().min(buffer.data.len());
+            buffer.data[..len].copy_from_slice(&data[..len]);
+        }
+    }
+
+    fn buffer_size(&mut self, buffer: mq::BufferId) -> usize {
+        self.buffers[&buffer].data.len()
+    }
+
+    fn delete_buffer(&mut self, buffer: mq::BufferId) {
+        self.buffers.remove(&buffer);
+    }
+
+    fn delete_texture(&mut self, texture: mq::TextureId) {
+        self.textures.get_mut().remove(&texture);
+    }
+
+    fn delete_shader(&mut self, shader: mq::ShaderId) {
+        self.shaders.remove(&shader);
+    }
+
+    fn apply_viewport(&mut self, x: i32, y: i32, width: i32, height: i32) {
+        self.viewport = Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+    }
+
+    fn apply_scissor_rect(&mut self, x: i32, y: i32, width: i32, height: i32) {
+        self.scissor = Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+    }
+
+    fn apply_bindings_from_slice(
+        &mut self,
+        vertex_buffers: &[mq::BufferId],
+        index_buffer: mq::BufferId,
+        images: &[mq::TextureId],
+    ) {
+        self.bindings = Some(mq::Bindings {
+            vertex_buffers: vertex_buffers.to_vec(),
+            index_buffer,
+            images: images.to_vec(),
+        });
+    }
+
+    // The trait method is safe, miniquad backends read the pointer the same way.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
+        // Safety: miniquad passes the pointer and size of the uniforms struct.
+        let bytes = unsafe { std::slice::from_raw_parts(uniform_ptr, size) };
+        self.uniforms.clear();
+        self.uniforms.extend_from_slice(bytes);
+    }
+
+    fn clear(
+        &mut self,
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        _stencil: Option<i32>,
+    ) {
+        self.with_target(|image| {
+            let rect = self.clip_rect(image);
+            for y in rect.y..rect.y + rect.height {
+                for x in rect.x..rect.x + rect.width {
+                    let index = y as usize * image.width as usize + x as usize;
+                    if let Some((r, g, b, a)) = color.filter(|_| !image.color.is_empty()) {
+                        image.write(index, Vec4::new(r, g, b, a), (true, true, true, true));
+                    }
+                    if let Some(depth) = depth.filter(|_| !image.depth.is_empty()) {
+                        image.depth[index] = depth;
+                    }
+                }
+            }
+        });
+    }
+
+    fn begin_default_pass(&mut self, action: mq::PassAction) {
+        self.begin_pass(None, action);
+    }
+
+    fn begin_pass(&mut self, pass: Option<mq::RenderPass>, action: mq::PassAction) {
+        self.current_pass = pass;
+        let (width, height) = self.target_size();
+        let full = Rect {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        };
+        self.viewport = full;
+        self.scissor = full;
+        if let mq::PassAction::Clear {
+            color,
+            depth,
+            stencil,
+        } = action
+        {
+            self.clear(color, depth, stencil);
+        }
+    }
+
+    fn end_render_pass(&mut self) {
+        self.current_pass = None;
+        self.scissor_enabled = false;
+    }
+
+    fn commit_frame(&mut self) {}
+
+    fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
+        let (Some(pipeline), Some(bindings)) = (
+            self.pipeline
+                .and_then(|pipeline| self.pipelines.get(&pipeline)),
+            self.bindings.as_ref(),
+        ) else {
+            return;
+        };
+        let Some(program) = self.shaders.get(&pipeline.shader) else {
+            return;
+        };
+        let Some(shader) = &program.shader else {
+            return;
+        };
+        let params = &pipeline.params;
+        let vertices_per_primitive = match params.primitive_type {
+            mq::PrimitiveType::Triangles => 3,
+            mq::PrimitiveType::Lines => 2,
+            mq::PrimitiveType::Points => return,
+        };
+
+        let Some(index_buffer) = self.buffers.get(&bindings.index_buffer) else {
+            return;
+        };
+        let indices: Vec<usize> = (base_element.max(0) as usize
+            ..(base_element + num_elements).max(0) as usize)
+            .filter_map(|element| {
+                let size = index_buffer.element_size;
+                let bytes = index_buffer
+                    .data
+                    .get(element * size..element * size + size)?;
+                Some(match size {
+                    1 => bytes[0] as usize,
+                    2 => u16::from_le_bytes([bytes[0], bytes[1]]) as usize,
+                    _ => u32::from_le_bytes(bytes.try_into().ok()?) as usize,
+                })
+            })
+            .collect();
+        let buffers: Vec<&[u8]> = bindings
+            .vertex_buffers
+            .iter()
+            .map(|buffer| {
+                self.buffers
+                    .get(buffer)
+                    .map_or(&[][..], |buffer| &buffer.data)
+            })
+            .collect();
+
+        let uniforms = SoftUniforms {
+            slots: &program.uniforms,
+            bytes: &self.uniforms,
+        };
+        let mut primitives = Vec::new();
+        for instance in 0..num_instances.max(1) as usize {
+            let mut vertices: HashMap<usize, ClipVertex> = HashMap::new();
+            for &index in &indices {
+                vertices.entry(index).or_insert_with(|| {
+                    let mut varyings = vec![0.; shader.varyings];
+                    let vertex = SoftVertex {
+                        attributes: &pipeline.attributes,
+                        buffers: &buffers,
+                        index,
+                        instance,
+                    };
+                    let position = (shader.vertex)(&vertex, &uniforms, &mut varyings);
+                    ClipVertex { position, varyings }
+                });
+            }
+            for primitive in indices.chunks_exact(vertices_per_primitive) {
+                let primitive: Vec<ClipVertex> = primitive
+                    .iter()
+                    .map(|index| vertices[index].clone())
+                    .collect();
+                primitives.push(clip_near(&primitive));
+            }
+        }
+        self.with_target(|image| {
+            let textures = self.textures.borrow();
+            let raster = Raster {
+                shader,
+                uniforms,
+                samplers: SoftSamplers {
+                    textures: bindings.images.iter().map(|id| textures.get(id)).collect(),
+                },
+                params,
+                blend: pipeline.blend,
+                viewport: self.viewport,
+                clip: self.clip_rect(image),
+            };
+            for polygon in &primitives {
+                match vertices_per_primitive {
+                    3 => {
+                        for index in 1..polygon.len().saturating_sub(1) {
+                            raster.triangle(
+                                image,
+                                [&polygon[0], &polygon[index], &polygon[index + 1]],
+                            );
+                        }
+                    }
+                    _ => {
+                        if let [a, b] = polygon.as_slice() {
+                            raster.line(image, [a, b]);
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Same as the GLSL sprite shader.
+#[cfg(feature = "sprite")]
+fn sprite_shader() -> SoftShader {
+    SoftShader::new(
+        6,
+        |vertex, uniforms, varyings| {
+            let uv = vertex.attribute("in_uv");
+            let color = vertex.attribute("in_color");
+            varyings.copy_from_slice(&[uv.x, uv.y, color.x, color.y, color.z, color.w]);
+            uniforms.get::<Mat4>("view_proj") * vertex.attribute("in_pos").truncate().extend(1.)
+        },
+        |varyings, _uniforms, samplers| {
+            Vec4::from_slice(&varyings[2..6]) * samplers.sample(0, Vec2::from_slice(&varyings[..2]))
+        },
+    )
+}
+
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1. } else { 1. };
+    let exponent = ((half >> 10) & 0x1f) as i32;
+    let mantissa = (half & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        31 if mantissa == 0. => f32::INFINITY,
+        31 => f32::NAN,
+        _ => (1. + mantissa / 1024.) * 2f32.powi(exponent - 15),
+    }
+}
+
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    if value.is_nan() {
+        return sign | 0x7e00;
+    }
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent >= 31 {
+        sign | 0x7c00
+    } else if exponent <= 0 {
+        // Subnormals, rounded to zero below the smallest one.
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = (mantissa | 0x80_0000) >> (1 - exponent);
+        sign | ((mantissa + 0x1000) >> 13) as u16
+    } else {
+        sign | ((exponent as u32) << 10 | (mantissa + 0x1000) >> 13) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer_source;
+    use mq::RenderingBackend;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+    /// 4x4 backend cleared to blue, with a shader drawing `in_pos` in `color`.
+    fn backend() -> (SoftRenderingBackend, mq::ShaderId) {
+        let mut backend = SoftRenderingBackend::new(4, 4);
+        backend.add_shader(
+            "flat",
+            SoftShader::new(
+                0,
+                |vertex, _uniforms, _varyings| vertex.attribute("in_pos"),
+                |_varyings, uniforms, _samplers| uniforms.get::<Vec4>("color"),
+            ),
+        );
+        let shader = backend
+            .new_shader(
+                mq::ShaderSource::Glsl {
+                    vertex: "flat",
+                    fragment: "",
+                },
+                mq::ShaderMeta {
+                    images: Vec::new(),
+                    uniforms: mq::UniformBlockLayout {
+                        uniforms: vec![mq::UniformDesc::new("color", mq::UniformType::Float4)],
+                    },
+                },
+            )
+            .unwrap();
+        backend.begin_default_pass(mq::PassAction::clear_color(0., 0., 1., 1.));
+        (backend, shader)
+    }
+
+    /// Draws triangles through the `indices` of `positions` in clip space.
+    fn draw(
+        backend: &mut SoftRenderingBackend,
+        shader: mq::ShaderId,
+        positions: &[[f32; 2]],
+        indices: &[u16],
+        color: Vec4,
+        color_blend: Option<mq::BlendState>,
+    ) {
+        let pipeline = backend.new_pipeline(
+            &[mq::BufferLayout::default()],
+            &[mq::VertexAttribute::new("in_pos", mq::VertexFormat::Float2)],
+            shader,
+            mq::PipelineParams {
+                color_blend,
+                ..Default::default()
+            },
+        );
+        let vertex_buffer = backend.new_buffer(
+            mq::BufferType::VertexBuffer,
+            mq::BufferUsage::Immutable,
+            buffer_source::slice(positions),
+        );
+        let index_buffer = backend.new_buffer(
+            mq::BufferType::IndexBuffer,
+            mq::BufferUsage::Immutable,
+            buffer_source::slice(indices),
+        );
+        backend.apply_pipeline(&pipeline);
+        backend.apply_bindings(&mq::Bindings {
+            vertex_buffers: vec![vertex_buffer],
+            index_buffer,
+            images: Vec::new(),
+        });
+        backend.apply_uniforms(mq::UniformsSource::table(&color));
+        backend.draw(0, indices.len() as i32, 1);
+    }
+
+    fn pixels(framebuffer: &SoftFramebuffer) -> Vec<[u8; 4]> {
+        framebuffer
+            .pixels()
+            .chunks_exact(4)
+            .map(|pixel| pixel.try_into().unwrap())
+            .collect()
+    }
+
+    fn png_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("bevy_miniquad_{}_{}.png", name, std::process::id()))
+    }
+
+    #[test]
+    fn draws_a_quad() {
+        let (mut backend, shader) = backend();
+        let framebuffer = backend.framebuffer();
+        // Left half of the framebuffer.
+        let positions = [[-1., -1.], [0., -1.], [0., 1.], [-1., 1.]];
+        draw(
+            &mut backend,
+            shader,
+            &positions,
+            &[0, 1, 2, 0, 2, 3],
+            Vec4::new(1., 0., 0., 1.),
+            None,
+        );
+
+        for (index, pixel) in pixels(&framebuffer).into_iter().enumerate() {
+            let expected = if index % 4 < 2 { RED } else { BLUE };
+            assert_eq!(pixel, expected, "pixel {}", index);
+        }
+    }
+
+    #[test]
+    fn draws_a_triangle_with_the_top_left_rule() {
+        let (mut backend, shader) = backend();
+        let framebuffer = backend.framebuffer();
+        // Lower left half, pixel centers on the diagonal are left out.
+        let positions = [[-1., -1.], [1., -1.], [-1., 1.]];
+        draw(
+            &mut backend,
+            shader,
+            &positions,
+            &[0, 1, 2],
+            Vec4::new(1., 0., 0., 1.),
+            None,
+        );
+
+        for (index, pixel) in pixels(&framebuffer).into_iter().enumerate() {
+            // Rows from the top, with x + y < 3 counted from the bottom left.
+            let (x, y) = (index % 4, 3 - index / 4);
+            let expected = if x + y < 3 { RED } else { BLUE };
+            assert_eq!(pixel, expected, "pixel {}, {}", x, y);
+        }
+    }
+
+    #[test]
+    fn blends_shared_edges_once() {
+        let (mut backend, shader) = backend();
+        let framebuffer = backend.framebuffer();
+        let positions = [[-1., -1.], [1., -1.], [1., 1.], [-1., 1.]];
+        draw(
+            &mut backend,
+            shader,
+            &positions,
+            &[0, 1, 2, 0, 2, 3],
+            Vec4::new(1., 0., 0., 0.5),
+            Some(mq::BlendState::new(
+                mq::Equation::Add,
+                mq::BlendFactor::Value(mq::BlendValue::SourceAlpha),
+                mq::BlendFactor::OneMinusValue(mq::BlendValue::SourceAlpha),
+            )),
+        );
+
+        // Half red over blue, alpha blended with the same factors.
+        assert_eq!(pixels(&framebuffer), vec![[128, 0, 128, 191]; 16]);
+    }
+
+    #[test]
+    fn compares_with_reference_images() {
+        let (mut backend, shader) = backend();
+        let framebuffer = backend.framebuffer();
+        let path = png_path("soft_render_reference");
+        framebuffer.save_png(&path).unwrap();
+
+        let same = framebuffer.compare_png(&path, 0);
+        let positions = [[-1., -1.], [-0.5, -1.], [-0.5, -0.5], [-1., -0.5]];
+        draw(
+            &mut backend,
+            shader,
+            &positions,
+            &[0, 1, 2, 0, 2, 3],
+            Vec4::new(0., 0., 0.8, 1.),
+            None,
+        );
+        let within_tolerance = framebuffer.compare_png(&path, 51);
+        let different = framebuffer.compare_png(&path, 50);
+        let missing = framebuffer.compare_png(png_path("soft_render_missing"), 0);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(same.is_ok());
+        assert!(within_tolerance.is_ok());
+        assert!(matches!(
+            different,
+            Err(GoldenImageError::PixelMismatch {
+                pixels: 1,
+                max_difference: 51
+            })
+        ));
+        assert!(matches!(missing, Err(GoldenImageError::Io(_))));
+    }
+
+    #[test]
+    fn compares_reference_image_sizes() {
+        let path = png_path("soft_render_size");
+        SoftRenderingBackend::new(2, 3)
+            .framebuffer()
+            .save_png(&path)
+            .unwrap();
+
+        let result = backend().0.framebuffer().compare_png(&path, 0);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            result,
+            Err(GoldenImageError::SizeMismatch { expected, actual })
+                if expected == UVec2::new(2, 3) && actual == UVec2::new(4, 4)
+        ));
+    }
+}
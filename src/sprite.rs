@@ -0,0 +1,557 @@
+use bevy_app::{App, Plugin};
+use bevy_color::Color;
+use bevy_ecs::prelude::*;
+use bevy_math::{Mat4, Rect, URect, UVec2, Vec2};
+use bevy_transform::components::{GlobalTransform, Transform};
+use miniquad as mq;
+use std::sync::Arc;
+
+#[cfg(feature = "image")]
+use crate::texture::GpuTextures;
+#[cfg(feature = "image")]
+use bevy_asset::{AssetId, Handle};
+#[cfg(feature = "image")]
+use bevy_image::Image;
+
+pub use crate::anchor::Anchor;
+use crate::{
+    buffer_source::{self, BufferData},
+    camera::{CameraView, MiniquadCamera2d},
+    converters::convert_color,
+    draw::builtin_shader_source,
+    gpu::{GpuBuffer, GpuDeletionQueue, GpuPipeline, GpuTexture},
+    render_target::{MainPass, RenderTarget},
+    Draw, DrawSet, MiniquadContext, MiniquadUniforms, MiniquadVertex, Window,
+};
+
+/// Draws `Sprite` entities in the `Draw` schedule.
+///
+/// Sprites are sorted by `GlobalTransform` translation z and batched by texture,
+/// then drawn by every active `MiniquadCamera2d`, or in window pixels centered
+/// on the window without one.
+/// Transforms are only propagated with bevy's `TransformPlugin` added.
+#[derive(Default)]
+pub struct SpritePlugin;
+
+impl Plugin for SpritePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Draw,
+            (
+                prepare_sprite_renderer.in_set(DrawSet::Prepare),
+                draw_sprites::<true>.in_set(DrawSet::Offscreen),
+                draw_sprites::<false>.in_set(DrawSet::Main),
+            ),
+        );
+    }
+}
+
+#[derive(Component, Clone, Debug)]
+#[require(Transform)]
+pub struct Sprite {
+    /// Texture to draw, a plain `color` quad when `None`.
+    pub texture: Option<mq::TextureId>,
+    /// Image to draw when `texture` is `None`, the sprite is skipped until it is uploaded.
+    /// A plain `color` quad with the default handle.
+    #[cfg(feature = "image")]
+    pub image: Handle<Image>,
+    /// `RenderTarget` entity to draw when `texture` is `None`,
+    /// the sprite is skipped until its texture is created.
+    pub render_target: Option<Entity>,
+    pub texture_atlas: Option<TextureAtlas>,
+    pub color: Color,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    /// Size in world units, the texture region size in pixels by default.
+    pub custom_size: Option<Vec2>,
+    /// Texture region in pixels, relative to the atlas tile when `texture_atlas` is set.
+    pub rect: Option<Rect>,
+    pub anchor: Anchor,
+}
+
+impl Default for Sprite {
+    fn default() -> Self {
+        Sprite {
+            texture: None,
+            #[cfg(feature = "image")]
+            image: Handle::default(),
+            render_target: None,
+            texture_atlas: None,
+            color: Color::WHITE,
+            flip_x: false,
+            flip_y: false,
+            custom_size: None,
+            rect: None,
+            anchor: Anchor::Center,
+        }
+    }
+}
+
+impl Sprite {
+    pub fn from_texture(texture: mq::TextureId) -> Self {
+        Sprite {
+            texture: Some(texture),
+            ..Default::default()
+        }
+    }
+
+    pub fn from_atlas_texture(texture: mq::TextureId, atlas: TextureAtlas) -> Self {
+        Sprite {
+            texture: Some(texture),
+            texture_atlas: Some(atlas),
+            ..Default::default()
+        }
+    }
+
+    #[cfg(feature = "image")]
+    pub fn from_image(image: Handle<Image>) -> Self {
+        Sprite {
+            image,
+            ..Default::default()
+        }
+    }
+
+    #[cfg(feature = "image")]
+    pub fn from_atlas_image(image: Handle<Image>, atlas: TextureAtlas) -> Self {
+        Sprite {
+            image,
+            texture_atlas: Some(atlas),
+            ..Default::default()
+        }
+    }
+
+    /// Sprite showing what the cameras drawing to a `RenderTarget` drew.
+    pub fn from_render_target(target: Entity) -> Self {
+        Sprite {
+            render_target: Some(target),
+            ..Default::default()
+        }
+    }
+
+    pub fn from_color(color: impl Into<Color>, size: Vec2) -> Self {
+        Sprite {
+            color: color.into(),
+            custom_size: Some(size),
+            ..Default::default()
+        }
+    }
+}
+
+/// Regions of a texture, in pixels.
+#[derive(Clone, Debug, Default)]
+pub struct TextureAtlasLayout {
+    pub size: UVec2,
+    pub textures: Vec<URect>,
+}
+
+impl TextureAtlasLayout {
+    pub fn new_empty(size: UVec2) -> Self {
+        TextureAtlasLayout {
+            size,
+            textures: Vec::new(),
+        }
+    }
+
+    /// Layout of a sprite sheet made of `columns` by `rows` tiles, row by row.
+    pub fn from_grid(
+        tile_size: UVec2,
+        columns: u32,
+        rows: u32,
+        padding: Option<UVec2>,
+        offset: Option<UVec2>,
+    ) -> Self {
+        let padding = padding.unwrap_or_default();
+        let offset = offset.unwrap_or_default();
+        let mut textures = Vec::with_capacity((columns * rows) as usize);
+        let mut size = UVec2::ZERO;
+
+        for y in 0..rows {
+            for x in 0..columns {
+                let min = offset + UVec2::new(x, y) * (tile_size + padding) + padding;
+                let max = min + tile_size;
+                size = size.max(max);
+                textures.push(URect::from_corners(min, max));
+            }
+        }
+
+        TextureAtlasLayout { size, textures }
+    }
+
+    pub fn add_texture(&mut self, rect: URect) -> usize {
+        self.textures.push(rect);
+        self.textures.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+}
+
+/// Tile of a `TextureAtlasLayout` a sprite is drawn with.
+#[derive(Clone, Debug)]
+pub struct TextureAtlas {
+    pub layout: Arc<TextureAtlasLayout>,
+    pub index: usize,
+}
+
+impl TextureAtlas {
+    pub fn new(layout: Arc<TextureAtlasLayout>, index: usize) -> Self {
+        TextureAtlas { layout, index }
+    }
+
+    pub fn texture_rect(&self) -> Option<URect> {
+        self.layout.textures.get(self.index).copied()
+    }
+}
+
+const MAX_SPRITES: usize = 8192;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, MiniquadVertex)]
+struct Vertex {
+    #[vertex(name = "in_pos")]
+    pos: [f32; 3],
+    #[vertex(name = "in_uv")]
+    uv: [f32; 2],
+    #[vertex(name = "in_color")]
+    color: [f32; 4],
+}
+
+// Safety: `repr(C)` struct of floats only, it has no padding.
+unsafe impl BufferData for Vertex {}
+
+#[derive(Resource)]
+pub struct SpriteRenderer {
+    pipeline: GpuPipeline,
+    vertex_buffer: GpuBuffer,
+    index_buffer: GpuBuffer,
+    white_texture: GpuTexture,
+    vertices: Vec<Vertex>,
+}
+
+impl SpriteRenderer {
+    fn new(ctx: &mut mq::Context, deletion_queue: &GpuDeletionQueue) -> Self {
+        let vertex_buffer = ctx.new_buffer(
+            mq::BufferType::VertexBuffer,
+            mq::BufferUsage::Stream,
+            mq::BufferSource::empty::<Vertex>(MAX_SPRITES * 4),
+        );
+
+        let indices: Vec<u16> = (0..MAX_SPRITES as u16)
+            .flat_map(|i| {
+                let base = i * 4;
+                [base, base + 1, base + 2, base, base + 2, base + 3]
+            })
+            .collect();
+        let index_buffer = ctx.new_buffer(
+            mq::BufferType::IndexBuffer,
+            mq::BufferUsage::Immutable,
+            buffer_source::slice(&indices),
+        );
+
+        let white_texture = ctx.new_texture_from_rgba8(1, 1, &[255, 255, 255, 255]);
+
+        let shader = ctx
+            .new_shader(
+                builtin_shader_source(
+                    ctx.info().backend,
+                    shader::VERTEX,
+                    shader::FRAGMENT,
+                    shader::METAL,
+                ),
+                shader::meta(),
+            )
+            .expect("Cannot compile sprite shader");
+
+        let pipeline = ctx.new_pipeline(
+            &[Vertex::buffer_layout()],
+            Vertex::ATTRIBUTES,
+            shader,
+            mq::PipelineParams {
+                color_blend: Some(mq::BlendState::new(
+                    mq::Equation::Add,
+                    mq::BlendFactor::Value(mq::BlendValue::SourceAlpha),
+                    mq::BlendFactor::OneMinusValue(mq::BlendValue::SourceAlpha),
+                )),
+                ..Default::default()
+            },
+        );
+
+        SpriteRenderer {
+            pipeline: GpuPipeline::with_shader(deletion_queue, pipeline, shader),
+            vertex_buffer: GpuBuffer::new(deletion_queue, vertex_buffer),
+            index_buffer: GpuBuffer::new(deletion_queue, index_buffer),
+            white_texture: GpuTexture::new(deletion_queue, white_texture),
+            vertices: Vec::with_capacity(MAX_SPRITES * 4),
+        }
+    }
+
+    pub fn white_texture(&self) -> mq::TextureId {
+        *self.white_texture
+    }
+}
+
+fn prepare_sprite_renderer(world: &mut World) {
+    if world.contains_resource::<SpriteRenderer>() {
+        return;
+    }
+
+    let deletion_queue = world.resource::<GpuDeletionQueue>().clone();
+    let renderer = SpriteRenderer::new(
+        &mut *world.non_send_resource_mut::<MiniquadContext>().0,
+        &deletion_queue,
+    );
+    world.insert_resource(renderer);
+}
+
+fn sprite_quad(
+    sprite: &Sprite,
+    transform: &GlobalTransform,
+    texture_size: (u32, u32),
+    bottom_up: bool,
+) -> [Vertex; 4] {
+    let texture_size = Vec2::new(texture_size.0 as f32, texture_size.1 as f32);
+    let mut rect = sprite
+        .texture_atlas
+        .as_ref()
+        .and_then(TextureAtlas::texture_rect)
+        .map(|rect| rect.as_rect())
+        .unwrap_or_else(|| Rect::from_corners(Vec2::ZERO, texture_size));
+    if let Some(sub_rect) = sprite.rect {
+        rect = Rect {
+            min: rect.min + sub_rect.min,
+            max: (rect.min + sub_rect.max).min(rect.max),
+        };
+    }
+    let size = sprite.custom_size.unwrap_or_else(|| rect.size());
+
+    // Texture rows start at the top of the image.
+    let (mut left, mut right) = (rect.min.x / texture_size.x, rect.max.x / texture_size.x);
+    let (mut top, mut bottom) = (rect.min.y / texture_size.y, rect.max.y / texture_size.y);
+    if sprite.flip_x {
+        std::mem::swap(&mut left, &mut right);
+    }
+    if sprite.flip_y != bottom_up {
+        std::mem::swap(&mut top, &mut bottom);
+    }
+
+    let (r, g, b, a) = convert_color(sprite.color);
+    let anchor = sprite.anchor.as_vec();
+    [
+        (Vec2::new(-0.5, -0.5), [left, bottom]),
+        (Vec2::new(0.5, -0.5), [right, bottom]),
+        (Vec2::new(0.5, 0.5), [right, top]),
+        (Vec2::new(-0.5, 0.5), [left, top]),
+    ]
+    .map(|(corner, uv)| Vertex {
+        pos: transform
+            .transform_point(((corner - anchor) * size).extend(0.))
+            .to_array(),
+        uv,
+        color: [r, g, b, a],
+    })
+}
+
+/// Draws the sprites with the cameras drawing to render targets when `OFFSCREEN`,
+/// with the ones drawing to the window otherwise.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn draw_sprites<const OFFSCREEN: bool>(
+    mut ctx: NonSendMut<MiniquadContext>,
+    renderer: Option<ResMut<SpriteRenderer>>,
+    window: Res<Window>,
+    sprites: Query<(&Sprite, &GlobalTransform)>,
+    cameras: Query<&CameraView, With<MiniquadCamera2d>>,
+    render_targets: Query<&RenderTarget>,
+    main_pass: Res<MainPass>,
+    #[cfg(feature = "image")] gpu_textures: Option<Res<GpuTextures>>,
+) {
+    let Some(mut renderer) = renderer else {
+        return;
+    };
+    let renderer = &mut *renderer;
+    let ctx = &mut *ctx.0;
+
+    let mut views: Vec<&CameraView> = cameras
+        .iter()
+        .filter(|view| view.is_active && view.is_offscreen() == OFFSCREEN)
+        .collect();
+    views.sort_by_key(|view| view.draw_order());
+    let fallback_view;
+    if !OFFSCREEN && cameras.iter().all(|view| !view.is_active) {
+        let (half_width, half_height) = (window.width / 2., window.height / 2.);
+        fallback_view = CameraView {
+            view_projection: Mat4::orthographic_rh_gl(
+                -half_width,
+                half_width,
+                -half_height,
+                half_height,
+                -1000.,
+                1000.,
+            ),
+            viewport: URect::new(0, 0, window.width as u32, window.height as u32),
+            target_size: UVec2::new(window.width as u32, window.height as u32),
+            is_active: true,
+            target_pass: main_pass.0,
+            ..Default::default()
+        };
+        views.push(&fallback_view);
+    }
+    if views.is_empty() {
+        return;
+    }
+
+    // Render targets are drawn bottom up on GL.
+    let gl = ctx.info().backend == mq::Backend::OpenGl;
+    let white_texture = *renderer.white_texture;
+    let sprite_texture = |sprite: &Sprite| -> Option<(mq::TextureId, bool)> {
+        if let Some(texture) = sprite.texture {
+            return Some((texture, false));
+        }
+        if let Some(target) = sprite.render_target {
+            let texture = render_targets.get(target).ok()?.texture()?;
+            return Some((texture, gl));
+        }
+        #[cfg(feature = "image")]
+        if sprite.image.id() != AssetId::default() {
+            return Some((gpu_textures.as_ref()?.get(&sprite.image)?, false));
+        }
+        Some((white_texture, false))
+    };
+
+    let mut quads: Vec<(f32, mq::TextureId, [Vertex; 4])> = sprites
+        .iter()
+        .filter_map(|(sprite, transform)| {
+            let (texture, bottom_up) = sprite_texture(sprite)?;
+            let quad = sprite_quad(sprite, transform, ctx.texture_size(texture), bottom_up);
+            Some((transform.translation().z, texture, quad))
+        })
+        .collect();
+    if quads.is_empty() {
+        return;
+    }
+    quads.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    for view in views {
+        if !view.begin_pass(ctx, mq::PassAction::Nothing) {
+            continue;
+        }
+        // A target can't be sampled while drawing to it.
+        let target_texture = view.target_pass.map(|pass| ctx.render_pass_texture(pass));
+        ctx.apply_pipeline(&renderer.pipeline);
+        view.apply_viewport(ctx);
+        ctx.apply_uniforms(
+            shader::Uniforms {
+                view_proj: view.view_projection,
+            }
+            .as_uniforms_source(),
+        );
+
+        for chunk in quads.chunks(MAX_SPRITES) {
+            renderer.vertices.clear();
+            renderer
+                .vertices
+                .extend(chunk.iter().flat_map(|(_, _, quad)| quad.iter().copied()));
+            ctx.buffer_update(
+                *renderer.vertex_buffer,
+                buffer_source::slice(&renderer.vertices),
+            );
+
+            let mut start = 0;
+            while start < chunk.len() {
+                let texture = chunk[start].1;
+                let count = chunk[start..]
+                    .iter()
+                    .take_while(|(_, quad_texture, _)| *quad_texture == texture)
+                    .count();
+                if Some(texture) == target_texture {
+                    start += count;
+                    continue;
+                }
+
+                ctx.apply_bindings(&mq::Bindings {
+                    vertex_buffers: vec![*renderer.vertex_buffer],
+                    index_buffer: *renderer.index_buffer,
+                    images: vec![texture],
+                });
+                ctx.draw((start * 6) as i32, (count * 6) as i32, 1);
+                start += count;
+            }
+        }
+
+        ctx.end_render_pass();
+    }
+}
+
+pub(crate) mod shader {
+    use bevy_math::Mat4;
+    use miniquad::*;
+
+    use crate::MiniquadUniforms;
+
+    pub const VERTEX: &str = r#"#version 100
+    attribute vec3 in_pos;
+    attribute vec2 in_uv;
+    attribute vec4 in_color;
+    uniform mat4 view_proj;
+    varying lowp vec4 color;
+    varying mediump vec2 uv;
+    void main() {
+        gl_Position = view_proj * vec4(in_pos, 1);
+        color = in_color;
+        uv = in_uv;
+    }"#;
+
+    pub const FRAGMENT: &str = r#"#version 100
+    varying lowp vec4 color;
+    varying mediump vec2 uv;
+    uniform sampler2D tex;
+    void main() {
+        gl_FragColor = color * texture2D(tex, uv);
+    }"#;
+
+    pub const METAL: &str = r#"
+    #include <metal_stdlib>
+    using namespace metal;
+
+    struct Uniforms {
+        float4x4 view_proj;
+    };
+
+    struct Vertex {
+        float3 in_pos [[attribute(0)]];
+        float2 in_uv [[attribute(1)]];
+        float4 in_color [[attribute(2)]];
+    };
+
+    struct RasterizerData {
+        float4 position [[position]];
+        float4 color [[user(locn0)]];
+        float2 uv [[user(locn1)]];
+    };
+
+    vertex RasterizerData vertexShader(Vertex v [[stage_in]], constant Uniforms& uniforms [[buffer(0)]]) {
+        RasterizerData out;
+        out.position = uniforms.view_proj * float4(v.in_pos, 1.0);
+        out.color = v.in_color;
+        out.uv = v.in_uv;
+        return out;
+    }
+
+    fragment float4 fragmentShader(RasterizerData in [[stage_in]], texture2d<float> tex [[texture(0)]], sampler texSmplr [[sampler(0)]]) {
+        return in.color * tex.sample(texSmplr, in.uv);
+    }"#;
+
+    pub fn meta() -> ShaderMeta {
+        Uniforms::shader_meta(&["tex"])
+    }
+
+    #[repr(C)]
+    #[derive(MiniquadUniforms)]
+    pub struct Uniforms {
+        pub view_proj: Mat4,
+    }
+}
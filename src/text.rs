@@ -0,0 +1,965 @@
+use bevy_app::{App, Plugin};
+use bevy_asset::{
+    io::Reader, Asset, AssetApp, AssetEvent, AssetId, AssetLoader, Assets, Handle, LoadContext,
+};
+use bevy_color::Color;
+use bevy_ecs::prelude::*;
+use bevy_math::{IVec2, Mat4, Rect, URect, UVec2, Vec2, Vec3};
+use bevy_reflect::TypePath;
+use bevy_transform::components::{GlobalTransform, Transform};
+use miniquad as mq;
+use std::{borrow::Cow, collections::HashMap, fmt, ops::Range};
+
+#[cfg(feature = "sprite")]
+use crate::sprite::draw_sprites;
+use crate::{
+    anchor::Anchor,
+    buffer_source::{self, BufferData},
+    camera::{CameraView, MiniquadCamera2d},
+    converters::convert_color,
+    draw::builtin_shader_source,
+    gpu::{GpuBuffer, GpuDeletionQueue, GpuPipeline, GpuTexture},
+    render_target::MainPass,
+    screenshot::take_screenshots,
+    Draw, DrawSet, MiniquadContext, MiniquadUniforms, MiniquadVertex, PostDraw, Window,
+};
+
+/// Loads `Font` assets from TTF and OTF files, and draws `Text2d` and
+/// `TextLabel` entities with glyphs rasterized into the `FontAtlas`.
+///
+/// `Text2d` entities are drawn like sprites, over them, by every active
+/// `MiniquadCamera2d`. Labels are drawn over the window at the end of the frame,
+/// after any post-processing.
+/// bevy's `AssetPlugin` needs to be added first.
+#[derive(Default)]
+pub struct TextPlugin;
+
+impl Plugin for TextPlugin {
+    fn build(&self, app: &mut App) {
+        let draw_offscreen = draw_text_2d::<true>.in_set(DrawSet::Offscreen);
+        let draw_main = draw_text_2d::<false>.in_set(DrawSet::Main);
+        #[cfg(feature = "sprite")]
+        let (draw_offscreen, draw_main) = (
+            draw_offscreen.after(draw_sprites::<true>),
+            draw_main.after(draw_sprites::<false>),
+        );
+
+        app.init_asset::<Font>()
+            .init_asset_loader::<FontLoader>()
+            .init_resource::<FontAtlas>()
+            .init_resource::<TextLayouts>()
+            .add_systems(
+                Draw,
+                (
+                    (prepare_text_renderer, prepare_text).in_set(DrawSet::Prepare),
+                    draw_offscreen,
+                    draw_main,
+                ),
+            )
+            .add_systems(PostDraw, draw_text_labels.before(take_screenshots));
+    }
+}
+
+/// TrueType or OpenType font.
+#[derive(Asset, TypePath, Clone)]
+pub struct Font(pub fontdue::Font);
+
+impl Font {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FontLoadError> {
+        fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default())
+            .map(Font)
+            .map_err(FontLoadError::Parse)
+    }
+}
+
+#[derive(Debug)]
+pub enum FontLoadError {
+    Io(std::io::Error),
+    Parse(&'static str),
+}
+
+impl fmt::Display for FontLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontLoadError::Io(error) => write!(f, "Cannot read font: {}", error),
+            FontLoadError::Parse(error) => write!(f, "Cannot parse font: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for FontLoadError {}
+
+#[derive(Default)]
+pub struct FontLoader;
+
+impl AssetLoader for FontLoader {
+    type Asset = Font;
+    type Settings = ();
+    type Error = FontLoadError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Font, FontLoadError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(FontLoadError::Io)?;
+        Font::from_bytes(&bytes)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ttf", "otf"]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TextStyle {
+    pub font: Handle<Font>,
+    /// Height of a line in pixels, or world units for `Text2d`.
+    pub font_size: f32,
+    pub color: Color,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle {
+            font: Handle::default(),
+            font_size: 24.,
+            color: Color::WHITE,
+        }
+    }
+}
+
+/// Alignment of the lines of a text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum JustifyText {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// Text drawn in world space, skipped until its font is loaded.
+///
+/// Lines are split on `\n`, and on spaces past `max_width`.
+#[derive(Component, Clone, Debug, Default)]
+#[require(Transform)]
+pub struct Text2d {
+    pub text: String,
+    pub style: TextStyle,
+    pub justify: JustifyText,
+    pub max_width: Option<f32>,
+    /// Point of the text block placed at its transform translation.
+    pub anchor: Anchor,
+}
+
+impl Text2d {
+    pub fn new(text: impl Into<String>, style: TextStyle) -> Self {
+        Text2d {
+            text: text.into(),
+            style,
+            ..Default::default()
+        }
+    }
+}
+
+/// Text drawn over the window, skipped until its font is loaded.
+///
+/// Lines are split on `\n`, and on spaces past `max_width`.
+#[derive(Component, Clone, Debug, Default)]
+pub struct TextLabel {
+    pub text: String,
+    pub style: TextStyle,
+    pub justify: JustifyText,
+    pub max_width: Option<f32>,
+    /// Top left corner of the text block, in window pixels from the top left.
+    pub position: Vec2,
+}
+
+impl TextLabel {
+    pub fn new(text: impl Into<String>, style: TextStyle, position: Vec2) -> Self {
+        TextLabel {
+            text: text.into(),
+            style,
+            position,
+            ..Default::default()
+        }
+    }
+}
+
+const ATLAS_START_SIZE: u32 = 256;
+const ATLAS_MAX_SIZE: u32 = 4096;
+const GLYPH_PADDING: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    font: AssetId<Font>,
+    character: char,
+    size: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct AtlasGlyph {
+    advance: f32,
+    /// Region of the atlas and offset of its bottom left corner from the
+    /// pen position, `None` for blank glyphs.
+    bitmap: Option<(URect, IVec2)>,
+}
+
+/// Texture holding the glyphs of every font and size drawn so far.
+///
+/// Glyphs are rasterized when first laid out and uploaded in `DrawSet::Prepare`,
+/// only the rows they changed. The atlas doubles in size when full, up to 4096
+/// pixels, past which new glyphs are not drawn.
+///
+/// The texture holds the coverage as alpha, in an `Alpha` texture on GL and
+/// white RGBA8 pixels on Metal, which has no one channel format in miniquad.
+#[derive(Resource)]
+pub struct FontAtlas {
+    size: UVec2,
+    /// Glyph coverage, one byte per pixel.
+    coverage: Vec<u8>,
+    glyphs: HashMap<GlyphKey, AtlasGlyph>,
+    cursor: UVec2,
+    row_height: u32,
+    texture: Option<(GpuTexture, UVec2)>,
+    /// Rows changed since the last upload.
+    dirty_rows: Option<Range<u32>>,
+}
+
+impl Default for FontAtlas {
+    fn default() -> Self {
+        let size = UVec2::splat(ATLAS_START_SIZE);
+        FontAtlas {
+            size,
+            coverage: vec![0; (size.x * size.y) as usize],
+            glyphs: HashMap::new(),
+            cursor: UVec2::ZERO,
+            row_height: 0,
+            texture: None,
+            dirty_rows: Some(0..size.y),
+        }
+    }
+}
+
+impl FontAtlas {
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    /// Texture of the atlas, `None` until it is first uploaded.
+    pub fn texture(&self) -> Option<mq::TextureId> {
+        self.texture.as_ref().map(|(texture, _)| **texture)
+    }
+
+    /// Number of glyphs rasterized.
+    pub fn len(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+
+    fn glyph(
+        &mut self,
+        font_id: AssetId<Font>,
+        font: &Font,
+        character: char,
+        size: f32,
+    ) -> AtlasGlyph {
+        let key = GlyphKey {
+            font: font_id,
+            character,
+            size: size.to_bits(),
+        };
+        if let Some(glyph) = self.glyphs.get(&key) {
+            return *glyph;
+        }
+
+        let (metrics, coverage) = font.0.rasterize(character, size);
+        let bitmap_size = UVec2::new(metrics.width as u32, metrics.height as u32);
+        let bitmap = if bitmap_size.cmpeq(UVec2::ZERO).any() {
+            None
+        } else if let Some(min) = self.allocate(bitmap_size) {
+            for (row, line) in coverage.chunks(metrics.width).enumerate() {
+                let start = (min.y as usize + row) * self.size.x as usize + min.x as usize;
+                self.coverage[start..start + line.len()].copy_from_slice(line);
+            }
+            let rows = min.y..min.y + bitmap_size.y;
+            self.dirty_rows = Some(match self.dirty_rows.take() {
+                Some(dirty) => dirty.start.min(rows.start)..dirty.end.max(rows.end),
+                None => rows,
+            });
+            let offset = IVec2::new(metrics.xmin, metrics.ymin);
+            Some((URect::from_corners(min, min + bitmap_size), offset))
+        } else {
+            log::warn!("Font atlas is full, cannot draw {:?}", character);
+            None
+        };
+
+        let glyph = AtlasGlyph {
+            advance: metrics.advance_width,
+            bitmap,
+        };
+        self.glyphs.insert(key, glyph);
+        glyph
+    }
+
+    /// Top left corner of a free `size` region, growing the atlas if needed.
+    fn allocate(&mut self, size: UVec2) -> Option<UVec2> {
+        loop {
+            if self.cursor.x + GLYPH_PADDING + size.x > self.size.x {
+                self.cursor = UVec2::new(0, self.cursor.y + self.row_height);
+                self.row_height = 0;
+            }
+            let max = self.cursor + GLYPH_PADDING + size;
+            if max.x <= self.size.x && max.y <= self.size.y {
+                let min = self.cursor + GLYPH_PADDING;
+                self.cursor.x = max.x;
+                self.row_height = self.row_height.max(size.y + GLYPH_PADDING);
+                return Some(min);
+            }
+            if !self.grow() {
+                return None;
+            }
+        }
+    }
+
+    /// Doubles the atlas size, glyphs keep their position.
+    fn grow(&mut self) -> bool {
+        if self.size.x >= ATLAS_MAX_SIZE {
+            return false;
+        }
+        let size = self.size * 2;
+        let mut coverage = vec![0; (size.x * size.y) as usize];
+        let (old_row, row) = (self.size.x as usize, size.x as usize);
+        for (y, line) in self.coverage.chunks(old_row).enumerate() {
+            coverage[y * row..y * row + old_row].copy_from_slice(line);
+        }
+        self.size = size;
+        self.coverage = coverage;
+        self.dirty_rows = Some(0..size.y);
+        true
+    }
+
+    fn upload(&mut self, ctx: &mut mq::Context, deletion_queue: &GpuDeletionQueue) {
+        let Some(rows) = self.dirty_rows.take() else {
+            return;
+        };
+        let format = match ctx.info().backend {
+            mq::Backend::OpenGl => mq::TextureFormat::Alpha,
+            mq::Backend::Metal => mq::TextureFormat::RGBA8,
+        };
+        match &self.texture {
+            Some((texture, size)) if *size == self.size => {
+                let row = self.size.x as usize;
+                let coverage = &self.coverage[rows.start as usize * row..rows.end as usize * row];
+                ctx.texture_update_part(
+                    **texture,
+                    0,
+                    rows.start as i32,
+                    self.size.x as i32,
+                    rows.len() as i32,
+                    &texture_bytes(coverage, format),
+                );
+            }
+            // The previous texture, if any, is deleted on the next frame.
+            _ => {
+                let texture = ctx.new_texture_from_data_and_format(
+                    &texture_bytes(&self.coverage, format),
+                    mq::TextureParams {
+                        width: self.size.x,
+                        height: self.size.y,
+                        format,
+                        ..Default::default()
+                    },
+                );
+                self.texture = Some((GpuTexture::new(deletion_queue, texture), self.size));
+            }
+        }
+    }
+}
+
+/// Pixels of `format` for coverage bytes, white with the coverage as alpha.
+fn texture_bytes(coverage: &[u8], format: mq::TextureFormat) -> Cow<'_, [u8]> {
+    match format {
+        mq::TextureFormat::Alpha => Cow::Borrowed(coverage),
+        _ => Cow::Owned(
+            coverage
+                .iter()
+                .flat_map(|alpha| [255, 255, 255, *alpha])
+                .collect(),
+        ),
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct PositionedGlyph {
+    /// Position from the top left of the text block, y up.
+    rect: Rect,
+    /// Region of the atlas in pixels.
+    atlas_rect: Rect,
+}
+
+#[derive(Clone, Debug, Default)]
+struct TextLayout {
+    glyphs: Vec<PositionedGlyph>,
+    size: Vec2,
+}
+
+/// Layouts of the texts whose font is loaded, computed in `DrawSet::Prepare`
+/// when the text or its font changes.
+#[derive(Default, Resource)]
+pub(crate) struct TextLayouts {
+    text_2d: HashMap<Entity, TextLayout>,
+    labels: HashMap<Entity, TextLayout>,
+}
+
+/// Width of `text` placed after `previous`, kerning included.
+fn line_width(
+    atlas: &mut FontAtlas,
+    font_id: AssetId<Font>,
+    font: &Font,
+    previous: Option<char>,
+    text: &str,
+    size: f32,
+) -> f32 {
+    let mut width = 0.;
+    let mut previous = previous;
+    for character in text.chars().filter(|character| !character.is_control()) {
+        if let Some(previous) = previous {
+            width += font
+                .0
+                .horizontal_kern(previous, character, size)
+                .unwrap_or(0.);
+        }
+        width += atlas.glyph(font_id, font, character, size).advance;
+        previous = Some(character);
+    }
+    width
+}
+
+fn layout_text(
+    atlas: &mut FontAtlas,
+    fonts: &Assets<Font>,
+    text: &str,
+    style: &TextStyle,
+    justify: JustifyText,
+    max_width: Option<f32>,
+) -> Option<TextLayout> {
+    let font_id = style.font.id();
+    let font = fonts.get(font_id)?;
+    let size = style.font_size;
+
+    let mut lines: Vec<(String, f32)> = Vec::new();
+    for paragraph in text.split('\n') {
+        let Some(max_width) = max_width else {
+            let width = line_width(atlas, font_id, font, None, paragraph, size);
+            lines.push((paragraph.to_string(), width));
+            continue;
+        };
+        let mut line = String::new();
+        let mut width = 0.;
+        for word in paragraph.split(' ') {
+            let last = line.chars().rev().find(|character| !character.is_control());
+            if line.is_empty() {
+                line.push_str(word);
+                width = line_width(atlas, font_id, font, None, word, size);
+                continue;
+            }
+            // Only the space and the word are measured, after the line so far.
+            let space = line_width(atlas, font_id, font, last, " ", size);
+            let word_width = line_width(atlas, font_id, font, Some(' '), word, size);
+            if width + space + word_width > max_width {
+                lines.push((std::mem::replace(&mut line, word.to_string()), width));
+                width = line_width(atlas, font_id, font, None, word, size);
+            } else {
+                line.push(' ');
+                line.push_str(word);
+                width += space + word_width;
+            }
+        }
+        lines.push((line, width));
+    }
+
+    let (ascent, line_height) = font
+        .0
+        .horizontal_line_metrics(size)
+        .map(|metrics| (metrics.ascent, metrics.new_line_size))
+        .unwrap_or((size, size * 1.2));
+    let block_width = lines.iter().fold(0f32, |max, (_, width)| max.max(*width));
+
+    let mut glyphs = Vec::new();
+    for (index, (line, width)) in lines.iter().enumerate() {
+        let mut x = match justify {
+            JustifyText::Left => 0.,
+            JustifyText::Center => (block_width - width) / 2.,
+            JustifyText::Right => block_width - width,
+        };
+        let baseline = -ascent - index as f32 * line_height;
+        let mut previous = None;
+        for character in line.chars().filter(|character| !character.is_control()) {
+            if let Some(previous) = previous {
+                x += font
+                    .0
+                    .horizontal_kern(previous, character, size)
+                    .unwrap_or(0.);
+            }
+            let glyph = atlas.glyph(font_id, font, character, size);
+            if let Some((atlas_rect, offset)) = glyph.bitmap {
+                // Whole pixel positions keep the glyphs sharp.
+                let min = Vec2::new(x.round(), baseline.round()) + offset.as_vec2();
+                glyphs.push(PositionedGlyph {
+                    rect: Rect::from_corners(min, min + atlas_rect.size().as_vec2()),
+                    atlas_rect: atlas_rect.as_rect(),
+                });
+            }
+            x += glyph.advance;
+            previous = Some(character);
+        }
+    }
+
+    Some(TextLayout {
+        glyphs,
+        size: Vec2::new(block_width, lines.len() as f32 * line_height),
+    })
+}
+
+/// Lays out a text again when it changed, its font changed, or its font was
+/// not loaded until now.
+fn update_layout(
+    layouts: &mut HashMap<Entity, TextLayout>,
+    entity: Entity,
+    font: AssetId<Font>,
+    changed: bool,
+    changed_fonts: &[AssetId<Font>],
+    layout: impl FnOnce() -> Option<TextLayout>,
+) {
+    if !changed && layouts.contains_key(&entity) && !changed_fonts.contains(&font) {
+        return;
+    }
+    match layout() {
+        Some(layout) => layouts.insert(entity, layout),
+        None => layouts.remove(&entity),
+    };
+}
+
+#[allow(clippy::too_many_arguments)]
+fn prepare_text(
+    mut ctx: NonSendMut<MiniquadContext>,
+    deletion_queue: Res<GpuDeletionQueue>,
+    mut atlas: ResMut<FontAtlas>,
+    mut layouts: ResMut<TextLayouts>,
+    fonts: Res<Assets<Font>>,
+    mut font_events: EventReader<AssetEvent<Font>>,
+    texts: Query<(Entity, Ref<Text2d>)>,
+    labels: Query<(Entity, Ref<TextLabel>)>,
+    mut removed_texts: RemovedComponents<Text2d>,
+    mut removed_labels: RemovedComponents<TextLabel>,
+) {
+    let layouts = &mut *layouts;
+    for entity in removed_texts.read() {
+        layouts.text_2d.remove(&entity);
+    }
+    for entity in removed_labels.read() {
+        layouts.labels.remove(&entity);
+    }
+    let changed_fonts: Vec<AssetId<Font>> = font_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } | AssetEvent::Removed { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    for (entity, text) in texts.iter() {
+        update_layout(
+            &mut layouts.text_2d,
+            entity,
+            text.style.font.id(),
+            text.is_changed(),
+            &changed_fonts,
+            || {
+                layout_text(
+                    &mut atlas,
+                    &fonts,
+                    &text.text,
+                    &text.style,
+                    text.justify,
+                    text.max_width,
+                )
+            },
+        );
+    }
+    for (entity, label) in labels.iter() {
+        update_layout(
+            &mut layouts.labels,
+            entity,
+            label.style.font.id(),
+            label.is_changed(),
+            &changed_fonts,
+            || {
+                layout_text(
+                    &mut atlas,
+                    &fonts,
+                    &label.text,
+                    &label.style,
+                    label.justify,
+                    label.max_width,
+                )
+            },
+        );
+    }
+
+    atlas.upload(&mut *ctx.0, &deletion_queue);
+}
+
+/// Adds the quads of `layout`, placing the block top left corner with `position`.
+fn push_glyph_quads(
+    vertices: &mut Vec<Vertex>,
+    layout: &TextLayout,
+    atlas_size: Vec2,
+    color: Color,
+    position: impl Fn(Vec2) -> Vec3,
+) {
+    let (r, g, b, a) = convert_color(color);
+    for glyph in &layout.glyphs {
+        let (rect, uv) = (glyph.rect, glyph.atlas_rect);
+        // Atlas rows start at the top.
+        vertices.extend(
+            [
+                (
+                    Vec2::new(rect.min.x, rect.min.y),
+                    Vec2::new(uv.min.x, uv.max.y),
+                ),
+                (
+                    Vec2::new(rect.max.x, rect.min.y),
+                    Vec2::new(uv.max.x, uv.max.y),
+                ),
+                (
+                    Vec2::new(rect.max.x, rect.max.y),
+                    Vec2::new(uv.max.x, uv.min.y),
+                ),
+                (
+                    Vec2::new(rect.min.x, rect.max.y),
+                    Vec2::new(uv.min.x, uv.min.y),
+                ),
+            ]
+            .map(|(point, uv)| Vertex {
+                pos: position(point).to_array(),
+                uv: (uv / atlas_size).to_array(),
+                color: [r, g, b, a],
+            }),
+        );
+    }
+}
+
+const MAX_GLYPHS: usize = 8192;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, MiniquadVertex)]
+struct Vertex {
+    #[vertex(name = "in_pos")]
+    pos: [f32; 3],
+    #[vertex(name = "in_uv")]
+    uv: [f32; 2],
+    #[vertex(name = "in_color")]
+    color: [f32; 4],
+}
+
+// Safety: `repr(C)` struct of floats only, it has no padding.
+unsafe impl BufferData for Vertex {}
+
+#[derive(Resource)]
+pub(crate) struct TextRenderer {
+    pipeline: GpuPipeline,
+    vertex_buffer: GpuBuffer,
+    index_buffer: GpuBuffer,
+    vertices: Vec<Vertex>,
+}
+
+impl TextRenderer {
+    fn new(ctx: &mut mq::Context, deletion_queue: &GpuDeletionQueue) -> Self {
+        let vertex_buffer = ctx.new_buffer(
+            mq::BufferType::VertexBuffer,
+            mq::BufferUsage::Stream,
+            mq::BufferSource::empty::<Vertex>(MAX_GLYPHS * 4),
+        );
+
+        let indices: Vec<u16> = (0..MAX_GLYPHS as u16)
+            .flat_map(|i| {
+                let base = i * 4;
+                [base, base + 1, base + 2, base, base + 2, base + 3]
+            })
+            .collect();
+        let index_buffer = ctx.new_buffer(
+            mq::BufferType::IndexBuffer,
+            mq::BufferUsage::Immutable,
+            buffer_source::slice(&indices),
+        );
+
+        let shader = ctx
+            .new_shader(
+                builtin_shader_source(
+                    ctx.info().backend,
+                    shader::VERTEX,
+                    shader::FRAGMENT,
+                    shader::METAL,
+                ),
+                shader::Uniforms::shader_meta(&["tex"]),
+            )
+            .expect("Cannot compile text shader");
+
+        let pipeline = ctx.new_pipeline(
+            &[Vertex::buffer_layout()],
+            Vertex::ATTRIBUTES,
+            shader,
+            mq::PipelineParams {
+                color_blend: Some(mq::BlendState::new(
+                    mq::Equation::Add,
+                    mq::BlendFactor::Value(mq::BlendValue::SourceAlpha),
+                    mq::BlendFactor::OneMinusValue(mq::BlendValue::SourceAlpha),
+                )),
+                ..Default::default()
+            },
+        );
+
+        TextRenderer {
+            pipeline: GpuPipeline::with_shader(deletion_queue, pipeline, shader),
+            vertex_buffer: GpuBuffer::new(deletion_queue, vertex_buffer),
+            index_buffer: GpuBuffer::new(deletion_queue, index_buffer),
+            vertices: Vec::with_capacity(MAX_GLYPHS * 4),
+        }
+    }
+
+    /// Draws `vertices` in the current pass, with the pipeline applied.
+    fn draw(&self, ctx: &mut mq::Context, view_projection: Mat4, texture: mq::TextureId) {
+        ctx.apply_uniforms(
+            shader::Uniforms {
+                view_proj: view_projection,
+            }
+            .as_uniforms_source(),
+        );
+        for chunk in self.vertices.chunks(MAX_GLYPHS * 4) {
+            ctx.buffer_update(*self.vertex_buffer, buffer_source::slice(chunk));
+            ctx.apply_bindings(&mq::Bindings {
+                vertex_buffers: vec![*self.vertex_buffer],
+                index_buffer: *self.index_buffer,
+                images: vec![texture],
+            });
+            ctx.draw(0, (chunk.len() / 4 * 6) as i32, 1);
+        }
+    }
+}
+
+fn prepare_text_renderer(world: &mut World) {
+    if world.contains_resource::<TextRenderer>() {
+        return;
+    }
+
+    let deletion_queue = world.resource::<GpuDeletionQueue>().clone();
+    let renderer = TextRenderer::new(
+        &mut *world.non_send_resource_mut::<MiniquadContext>().0,
+        &deletion_queue,
+    );
+    world.insert_resource(renderer);
+}
+
+/// Draws the `Text2d` entities with the cameras drawing to render targets when
+/// `OFFSCREEN`, with the ones drawing to the window otherwise.
+#[allow(clippy::too_many_arguments)]
+fn draw_text_2d<const OFFSCREEN: bool>(
+    mut ctx: NonSendMut<MiniquadContext>,
+    renderer: Option<ResMut<TextRenderer>>,
+    atlas: Res<FontAtlas>,
+    layouts: Res<TextLayouts>,
+    window: Res<Window>,
+    texts: Query<(Entity, &Text2d, &GlobalTransform)>,
+    cameras: Query<&CameraView, With<MiniquadCamera2d>>,
+    main_pass: Res<MainPass>,
+) {
+    let (Some(mut renderer), Some(texture)) = (renderer, atlas.texture()) else {
+        return;
+    };
+    if layouts.text_2d.is_empty() {
+        return;
+    }
+    let ctx = &mut *ctx.0;
+
+    let mut views: Vec<&CameraView> = cameras
+        .iter()
+        .filter(|view| view.is_active && view.is_offscreen() == OFFSCREEN)
+        .collect();
+    views.sort_by_key(|view| view.draw_order());
+    let fallback_view;
+    if !OFFSCREEN && cameras.iter().all(|view| !view.is_active) {
+        let (half_width, half_height) = (window.width / 2., window.height / 2.);
+        fallback_view = CameraView {
+            view_projection: Mat4::orthographic_rh_gl(
+                -half_width,
+                half_width,
+                -half_height,
+                half_height,
+                -1000.,
+                1000.,
+            ),
+            viewport: URect::new(0, 0, window.width as u32, window.height as u32),
+            target_size: UVec2::new(window.width as u32, window.height as u32),
+            is_active: true,
+            target_pass: main_pass.0,
+            ..Default::default()
+        };
+        views.push(&fallback_view);
+    }
+    if views.is_empty() {
+        return;
+    }
+
+    let mut texts: Vec<(&TextLayout, &Text2d, &GlobalTransform)> = texts
+        .iter()
+        .filter_map(|(entity, text, transform)| {
+            Some((layouts.text_2d.get(&entity)?, text, transform))
+        })
+        .collect();
+    texts.sort_by(|a, b| a.2.translation().z.total_cmp(&b.2.translation().z));
+
+    let atlas_size = atlas.size().as_vec2();
+    let renderer = &mut *renderer;
+    renderer.vertices.clear();
+    for (layout, text, transform) in texts {
+        let size = layout.size;
+        let origin = Vec2::new(-size.x / 2., size.y / 2.) - text.anchor.as_vec() * size;
+        push_glyph_quads(
+            &mut renderer.vertices,
+            layout,
+            atlas_size,
+            text.style.color,
+            |point| transform.transform_point((origin + point).extend(0.)),
+        );
+    }
+    if renderer.vertices.is_empty() {
+        return;
+    }
+
+    for view in views {
+        if !view.begin_pass(ctx, mq::PassAction::Nothing) {
+            continue;
+        }
+        ctx.apply_pipeline(&renderer.pipeline);
+        view.apply_viewport(ctx);
+        renderer.draw(ctx, view.view_projection, texture);
+        ctx.end_render_pass();
+    }
+}
+
+pub(crate) fn draw_text_labels(
+    mut ctx: NonSendMut<MiniquadContext>,
+    renderer: Option<ResMut<TextRenderer>>,
+    atlas: Res<FontAtlas>,
+    layouts: Res<TextLayouts>,
+    window: Res<Window>,
+    labels: Query<(Entity, &TextLabel)>,
+) {
+    let (Some(mut renderer), Some(texture)) = (renderer, atlas.texture()) else {
+        return;
+    };
+    let ctx = &mut *ctx.0;
+    let atlas_size = atlas.size().as_vec2();
+    let renderer = &mut *renderer;
+
+    renderer.vertices.clear();
+    for (entity, label) in labels.iter() {
+        let Some(layout) = layouts.labels.get(&entity) else {
+            continue;
+        };
+        push_glyph_quads(
+            &mut renderer.vertices,
+            layout,
+            atlas_size,
+            label.style.color,
+            |point| Vec3::new(label.position.x + point.x, label.position.y - point.y, 0.),
+        );
+    }
+    if renderer.vertices.is_empty() {
+        return;
+    }
+
+    let (width, height) = (window.width, window.height);
+    ctx.begin_default_pass(mq::PassAction::Nothing);
+    ctx.apply_pipeline(&renderer.pipeline);
+    ctx.apply_viewport(0, 0, width as i32, height as i32);
+    ctx.apply_scissor_rect(0, 0, width as i32, height as i32);
+    let projection = Mat4::orthographic_rh_gl(0., width, height, 0., -1., 1.);
+    renderer.draw(ctx, projection, texture);
+    ctx.end_render_pass();
+}
+
+mod shader {
+    use bevy_math::Mat4;
+
+    use crate::MiniquadUniforms;
+
+    pub const VERTEX: &str = r#"#version 100
+    attribute vec3 in_pos;
+    attribute vec2 in_uv;
+    attribute vec4 in_color;
+    uniform mat4 view_proj;
+    varying lowp vec4 color;
+    varying mediump vec2 uv;
+    void main() {
+        gl_Position = view_proj * vec4(in_pos, 1);
+        color = in_color;
+        uv = in_uv;
+    }"#;
+
+    pub const FRAGMENT: &str = r#"#version 100
+    varying lowp vec4 color;
+    varying mediump vec2 uv;
+    uniform sampler2D tex;
+    void main() {
+        gl_FragColor = vec4(color.rgb, color.a * texture2D(tex, uv).a);
+    }"#;
+
+    pub const METAL: &str = r#"
+    #include <metal_stdlib>
+    using namespace metal;
+
+    struct Uniforms {
+        float4x4 view_proj;
+    };
+
+    struct Vertex {
+        float3 in_pos [[attribute(0)]];
+        float2 in_uv [[attribute(1)]];
+        float4 in_color [[attribute(2)]];
+    };
+
+    struct RasterizerData {
+        float4 position [[position]];
+        float4 color [[user(locn0)]];
+        float2 uv [[user(locn1)]];
+    };
+
+    vertex RasterizerData vertexShader(Vertex v [[stage_in]], constant Uniforms& uniforms [[buffer(0)]]) {
+        RasterizerData out;
+        out.position = uniforms.view_proj * float4(v.in_pos, 1.0);
+        out.color = v.in_color;
+        out.uv = v.in_uv;
+        return out;
+    }
+
+    fragment float4 fragmentShader(RasterizerData in [[stage_in]], texture2d<float> tex [[texture(0)]], sampler texSmplr [[sampler(0)]]) {
+        return float4(in.color.rgb, in.color.a * tex.sample(texSmplr, in.uv).a);
+    }"#;
+
+    #[repr(C)]
+    #[derive(MiniquadUniforms)]
+    pub struct Uniforms {
+        pub view_proj: Mat4,
+    }
+}
@@ -0,0 +1,258 @@
+use bevy_app::{App, Plugin};
+use bevy_asset::{AssetApp, AssetEvent, AssetId, Assets, Handle};
+use bevy_ecs::prelude::*;
+use bevy_image::{
+    CompressedImageFormats, Image, ImageAddressMode, ImageFilterMode, ImageLoader, ImageSampler,
+    ImageSamplerDescriptor,
+};
+use miniquad as mq;
+use std::collections::HashMap;
+use wgpu_types::TextureFormat;
+
+use crate::{Draw, DrawSet, MiniquadContext};
+
+/// Uploads `Image` assets as miniquad textures and keeps them in sync,
+/// see `GpuTextures`.
+///
+/// Registers the `Image` asset and its loader when no other plugin did,
+/// bevy's `AssetPlugin` needs to be added first.
+pub struct MiniquadImagePlugin {
+    /// Sampler of images using `ImageSampler::Default`.
+    pub default_sampler: ImageSamplerDescriptor,
+    /// Generate mipmaps for every image, not only the ones with several mip levels.
+    pub generate_mipmaps: bool,
+}
+
+impl Default for MiniquadImagePlugin {
+    fn default() -> Self {
+        MiniquadImagePlugin {
+            default_sampler: ImageSamplerDescriptor::linear(),
+            generate_mipmaps: false,
+        }
+    }
+}
+
+impl Plugin for MiniquadImagePlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world().contains_resource::<Assets<Image>>() {
+            app.init_asset::<Image>()
+                .register_asset_loader(ImageLoader::new(CompressedImageFormats::NONE));
+        }
+
+        app.insert_resource(ImageTextureSettings {
+            default_sampler: self.default_sampler.clone(),
+            generate_mipmaps: self.generate_mipmaps,
+        })
+        .init_resource::<GpuTextures>()
+        .add_systems(Draw, prepare_image_textures.in_set(DrawSet::Prepare));
+    }
+}
+
+#[derive(Resource)]
+struct ImageTextureSettings {
+    default_sampler: ImageSamplerDescriptor,
+    generate_mipmaps: bool,
+}
+
+/// miniquad textures of the loaded `Image` assets.
+///
+/// Textures are created in `DrawSet::Prepare` of the frame an image is added,
+/// updated in place when it is modified and deleted when it is removed.
+#[derive(Debug, Default, Resource)]
+pub struct GpuTextures {
+    textures: HashMap<AssetId<Image>, mq::TextureId>,
+}
+
+impl GpuTextures {
+    pub fn get(&self, id: impl Into<AssetId<Image>>) -> Option<mq::TextureId> {
+        self.textures.get(&id.into()).copied()
+    }
+
+    pub fn contains(&self, handle: &Handle<Image>) -> bool {
+        self.textures.contains_key(&handle.id())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (AssetId<Image>, mq::TextureId)> + '_ {
+        self.textures.iter().map(|(id, texture)| (*id, *texture))
+    }
+
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+}
+
+fn convert_format(format: TextureFormat) -> Option<mq::TextureFormat> {
+    match format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => Some(mq::TextureFormat::RGBA8),
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => Some(mq::TextureFormat::RGBA8),
+        TextureFormat::R8Unorm => Some(mq::TextureFormat::Alpha),
+        TextureFormat::Rgba16Float => Some(mq::TextureFormat::RGBA16F),
+        _ => None,
+    }
+}
+
+fn convert_filter(filter: ImageFilterMode) -> mq::FilterMode {
+    match filter {
+        ImageFilterMode::Nearest => mq::FilterMode::Nearest,
+        ImageFilterMode::Linear => mq::FilterMode::Linear,
+    }
+}
+
+fn convert_mipmap_filter(filter: ImageFilterMode) -> mq::MipmapFilterMode {
+    match filter {
+        ImageFilterMode::Nearest => mq::MipmapFilterMode::Nearest,
+        ImageFilterMode::Linear => mq::MipmapFilterMode::Linear,
+    }
+}
+
+fn convert_wrap(address_mode: ImageAddressMode) -> mq::TextureWrap {
+    match address_mode {
+        ImageAddressMode::Repeat => mq::TextureWrap::Repeat,
+        ImageAddressMode::MirrorRepeat => mq::TextureWrap::Mirror,
+        // No border color support, clamp to the edge instead.
+        ImageAddressMode::ClampToEdge | ImageAddressMode::ClampToBorder => mq::TextureWrap::Clamp,
+    }
+}
+
+/// Texture parameters and the first mip level bytes of an image,
+/// `None` if miniquad has no matching texture format.
+fn texture_data<'a>(
+    image: &'a Image,
+    settings: &ImageTextureSettings,
+) -> Option<(mq::TextureParams, std::borrow::Cow<'a, [u8]>)> {
+    let descriptor = &image.texture_descriptor;
+    let format = convert_format(descriptor.format)?;
+    let (width, height) = (image.width(), image.height());
+
+    let sampler = match &image.sampler {
+        ImageSampler::Default => &settings.default_sampler,
+        ImageSampler::Descriptor(sampler) => sampler,
+    };
+    let mipmaps = settings.generate_mipmaps || descriptor.mip_level_count > 1;
+
+    let params = mq::TextureParams {
+        kind: mq::TextureKind::Texture2D,
+        format,
+        wrap: convert_wrap(sampler.address_mode_u),
+        min_filter: convert_filter(sampler.min_filter),
+        mag_filter: convert_filter(sampler.mag_filter),
+        mipmap_filter: if mipmaps {
+            convert_mipmap_filter(sampler.mipmap_filter)
+        } else {
+            mq::MipmapFilterMode::None
+        },
+        width,
+        height,
+        allocate_mipmaps: mipmaps,
+        sample_count: 1,
+    };
+
+    // Only the first mip level is uploaded, the others are generated.
+    let size = format.size(width, height) as usize;
+    let bytes = image.data.get(..size)?;
+    let bytes = match descriptor.format {
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => bytes
+            .chunks_exact(4)
+            .flat_map(|bgra| [bgra[2], bgra[1], bgra[0], bgra[3]])
+            .collect::<Vec<u8>>()
+            .into(),
+        _ => bytes.into(),
+    };
+
+    Some((params, bytes))
+}
+
+fn upload_image(
+    ctx: &mut mq::Context,
+    existing: Option<mq::TextureId>,
+    image: &Image,
+    settings: &ImageTextureSettings,
+) -> Option<mq::TextureId> {
+    let Some((params, bytes)) = texture_data(image, settings) else {
+        log::warn!(
+            "Image format {:?} is not supported by miniquad",
+            image.texture_descriptor.format
+        );
+        return None;
+    };
+
+    // Keep the texture id when possible, it may be stored by draw code already.
+    let texture = match existing {
+        Some(texture)
+            if ctx.texture_params(texture).format == params.format
+                && ctx.texture_params(texture).allocate_mipmaps == params.allocate_mipmaps =>
+        {
+            let (width, height) = ctx.texture_size(texture);
+            if (width, height) == (params.width, params.height) {
+                ctx.texture_update(texture, &bytes);
+            } else {
+                ctx.texture_resize(texture, params.width, params.height, Some(&bytes));
+            }
+            ctx.texture_set_filter(texture, params.min_filter, params.mipmap_filter);
+            ctx.texture_set_mag_filter(texture, params.mag_filter);
+            texture
+        }
+        _ => {
+            if let Some(texture) = existing {
+                ctx.delete_texture(texture);
+            }
+            ctx.new_texture_from_data_and_format(&bytes, params)
+        }
+    };
+
+    let sampler = match &image.sampler {
+        ImageSampler::Default => &settings.default_sampler,
+        ImageSampler::Descriptor(sampler) => sampler,
+    };
+    ctx.texture_set_wrap(
+        texture,
+        convert_wrap(sampler.address_mode_u),
+        convert_wrap(sampler.address_mode_v),
+    );
+    if params.allocate_mipmaps {
+        ctx.texture_generate_mipmaps(texture);
+    }
+
+    Some(texture)
+}
+
+fn prepare_image_textures(
+    mut ctx: NonSendMut<MiniquadContext>,
+    mut events: EventReader<AssetEvent<Image>>,
+    images: Res<Assets<Image>>,
+    settings: Res<ImageTextureSettings>,
+    mut gpu_textures: ResMut<GpuTextures>,
+) {
+    let ctx = &mut *ctx.0;
+
+    for event in events.read() {
+        match *event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } => {
+                let Some(image) = images.get(id) else {
+                    continue;
+                };
+                let existing = gpu_textures.textures.get(&id).copied();
+                match upload_image(ctx, existing, image, &settings) {
+                    Some(texture) => {
+                        gpu_textures.textures.insert(id, texture);
+                    }
+                    None => {
+                        if let Some(texture) = gpu_textures.textures.remove(&id) {
+                            ctx.delete_texture(texture);
+                        }
+                    }
+                }
+            }
+            AssetEvent::Removed { id } => {
+                if let Some(texture) = gpu_textures.textures.remove(&id) {
+                    ctx.delete_texture(texture);
+                }
+            }
+            AssetEvent::Unused { .. } | AssetEvent::LoadedWithDependencies { .. } => {}
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use bevy_math::{IVec2, IVec3, IVec4, Mat4, Vec2, Vec3, Vec4};
+use miniquad as mq;
+
+/// Uniforms struct of a shader, usually derived with `#[derive(MiniquadUniforms)]`.
+///
+/// The derive lists the fields of a `#[repr(C)]` struct as uniforms of the same name,
+/// with `[T; N]` fields as arrays. It fails to compile when the struct layout does not
+/// match the tightly packed one miniquad uploads, or a field is not aligned to
+/// 4 bytes for scalars, 8 for two components and 16 for the others,
+/// as Metal and std140 expect. Padding after the last field is allowed, it is
+/// not part of `uniforms_size`.
+///
+/// ```
+/// # use bevy::math::{Mat4, Vec2};
+/// # use bevy_miniquad::MiniquadUniforms;
+/// #[repr(C)]
+/// #[derive(MiniquadUniforms)]
+/// struct Uniforms {
+///     view_proj: Mat4,
+///     positions: [(f32, f32); 32],
+///     time: f32,
+///     count: i32,
+///     scale: Vec2,
+/// }
+///
+/// assert_eq!(Uniforms::uniforms().len(), 5);
+/// ```
+///
+/// A `Vec4` after a scalar is padded by `#[repr(C)]`:
+///
+/// ```compile_fail
+/// # use bevy::math::Vec4;
+/// # use bevy_miniquad::MiniquadUniforms;
+/// #[repr(C)]
+/// #[derive(MiniquadUniforms)]
+/// struct Uniforms {
+///     time: f32,
+///     color: Vec4,
+/// }
+/// ```
+///
+/// A two component array is packed after a scalar, but not aligned:
+///
+/// ```compile_fail
+/// # use bevy_miniquad::MiniquadUniforms;
+/// #[repr(C)]
+/// #[derive(MiniquadUniforms)]
+/// struct Uniforms {
+///     time: f32,
+///     offset: [f32; 2],
+/// }
+/// ```
+///
+/// Without `#[repr(C)]` the layout is unknown:
+///
+/// ```compile_fail
+/// # use bevy_miniquad::MiniquadUniforms;
+/// #[derive(MiniquadUniforms)]
+/// struct Uniforms {
+///     time: f32,
+/// }
+/// ```
+pub trait MiniquadUniforms: Sized {
+    fn uniforms() -> Vec<mq::UniformDesc>;
+
+    fn uniform_block_layout() -> mq::UniformBlockLayout {
+        mq::UniformBlockLayout {
+            uniforms: Self::uniforms(),
+        }
+    }
+
+    /// Shader meta of a shader using these uniforms and images.
+    fn shader_meta(images: &[&str]) -> mq::ShaderMeta {
+        mq::ShaderMeta {
+            uniforms: Self::uniform_block_layout(),
+            images: images.iter().map(|image| image.to_string()).collect(),
+        }
+    }
+
+    /// Bytes of the uniforms, without the trailing padding the struct may have.
+    fn uniforms_size() -> usize {
+        Self::uniforms()
+            .iter()
+            .map(|uniform| uniform_size(uniform.uniform_type) * uniform.array_count)
+            .sum()
+    }
+
+    fn as_uniforms_source(&self) -> mq::UniformsSource<'_> {
+        mq::UniformsSource::table(self)
+    }
+}
+
+/// Types usable as uniforms, or elements of uniform arrays.
+pub trait UniformElement {
+    const UNIFORM_TYPE: mq::UniformType;
+}
+
+macro_rules! uniform_elements {
+    ($($uniform_type:ident: $($ty:ty),*;)*) => {
+        $($(
+            impl UniformElement for $ty {
+                const UNIFORM_TYPE: mq::UniformType = mq::UniformType::$uniform_type;
+            }
+        )*)*
+    };
+}
+
+uniform_elements! {
+    Float1: f32;
+    Float2: [f32; 2], (f32, f32), Vec2;
+    Float3: [f32; 3], (f32, f32, f32), Vec3;
+    Float4: [f32; 4], (f32, f32, f32, f32), Vec4;
+    Int1: i32;
+    Int2: [i32; 2], (i32, i32), IVec2;
+    Int3: [i32; 3], (i32, i32, i32), IVec3;
+    Int4: [i32; 4], (i32, i32, i32, i32), IVec4;
+    Mat4: [f32; 16], Mat4;
+}
+
+/// Byte size of a uniform, as `UniformType::size` but usable in constants.
+pub const fn uniform_size(uniform_type: mq::UniformType) -> usize {
+    match uniform_type {
+        mq::UniformType::Float1 | mq::UniformType::Int1 => 4,
+        mq::UniformType::Float2 | mq::UniformType::Int2 => 8,
+        mq::UniformType::Float3 | mq::UniformType::Int3 => 12,
+        mq::UniformType::Float4 | mq::UniformType::Int4 => 16,
+        mq::UniformType::Mat4 => 64,
+    }
+}
+
+pub const fn uniform_alignment(uniform_type: mq::UniformType) -> usize {
+    match uniform_type {
+        mq::UniformType::Float1 | mq::UniformType::Int1 => 4,
+        mq::UniformType::Float2 | mq::UniformType::Int2 => 8,
+        _ => 16,
+    }
+}
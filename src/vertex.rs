@@ -0,0 +1,133 @@
+use bevy_math::{Mat4, UVec2, UVec3, UVec4, Vec2, Vec3, Vec4};
+use miniquad as mq;
+
+/// Vertex struct of a pipeline, usually derived with `#[derive(MiniquadVertex)]`.
+///
+/// The derive lists the fields of a `#[repr(C)]` struct as attributes named after
+/// the fields, or `#[vertex(name = "...")]`, and fails to compile when the struct
+/// has padding miniquad would not skip.
+///
+/// ```
+/// # use bevy::math::Vec2;
+/// # use bevy_miniquad::MiniquadVertex;
+/// #[repr(C)]
+/// #[derive(MiniquadVertex)]
+/// struct Vertex {
+///     #[vertex(name = "in_pos")]
+///     pos: Vec2,
+///     uv: [f32; 2],
+///     color: [u8; 4],
+/// }
+///
+/// assert_eq!(Vertex::ATTRIBUTES[0].name, "in_pos");
+/// assert_eq!(Vertex::ATTRIBUTES[2].name, "color");
+/// ```
+///
+/// A `Vec4` after 8 bytes is padded by `#[repr(C)]`:
+///
+/// ```compile_fail
+/// # use bevy::math::{Vec2, Vec4};
+/// # use bevy_miniquad::MiniquadVertex;
+/// #[repr(C)]
+/// #[derive(MiniquadVertex)]
+/// struct Vertex {
+///     pos: Vec2,
+///     color: Vec4,
+/// }
+/// ```
+///
+/// Vertices are limited to 255 bytes:
+///
+/// ```compile_fail
+/// # use bevy_miniquad::MiniquadVertex;
+/// #[repr(C)]
+/// #[derive(MiniquadVertex)]
+/// struct Vertex {
+///     a: [f32; 16],
+///     b: [f32; 16],
+///     c: [f32; 16],
+///     d: [f32; 16],
+/// }
+/// ```
+pub trait MiniquadVertex: Sized {
+    /// Attributes of the fields, read from the first vertex buffer.
+    const ATTRIBUTES: &'static [mq::VertexAttribute];
+
+    /// Attributes of the fields, read from vertex buffer `buffer_index`.
+    fn attributes(buffer_index: usize) -> Vec<mq::VertexAttribute> {
+        Self::ATTRIBUTES
+            .iter()
+            .map(|attribute| mq::VertexAttribute {
+                buffer_index,
+                ..*attribute
+            })
+            .collect()
+    }
+
+    fn buffer_layout() -> mq::BufferLayout {
+        mq::BufferLayout {
+            stride: std::mem::size_of::<Self>() as i32,
+            ..Default::default()
+        }
+    }
+
+    /// Layout of a buffer with one element per `step_rate` instances.
+    fn instance_buffer_layout(step_rate: i32) -> mq::BufferLayout {
+        mq::BufferLayout {
+            stride: std::mem::size_of::<Self>() as i32,
+            step_func: mq::VertexStep::PerInstance,
+            step_rate,
+        }
+    }
+}
+
+/// Types usable as vertex fields.
+pub trait VertexElement {
+    const VERTEX_FORMAT: mq::VertexFormat;
+}
+
+macro_rules! vertex_elements {
+    ($($vertex_format:ident: $($ty:ty),*;)*) => {
+        $($(
+            impl VertexElement for $ty {
+                const VERTEX_FORMAT: mq::VertexFormat = mq::VertexFormat::$vertex_format;
+            }
+        )*)*
+    };
+}
+
+vertex_elements! {
+    Float1: f32;
+    Float2: [f32; 2], (f32, f32), Vec2;
+    Float3: [f32; 3], (f32, f32, f32), Vec3;
+    Float4: [f32; 4], (f32, f32, f32, f32), Vec4;
+    Byte1: u8;
+    Byte2: [u8; 2];
+    Byte3: [u8; 3];
+    Byte4: [u8; 4];
+    Short1: u16;
+    Short2: [u16; 2];
+    Short3: [u16; 3];
+    Short4: [u16; 4];
+    Int1: u32;
+    Int2: [u32; 2], UVec2;
+    Int3: [u32; 3], UVec3;
+    Int4: [u32; 4], UVec4;
+    Mat4: [f32; 16], Mat4;
+}
+
+/// Byte size of a vertex format, as `VertexFormat::size_bytes` but usable in constants.
+pub const fn vertex_format_size(format: mq::VertexFormat) -> usize {
+    use mq::VertexFormat::*;
+    match format {
+        Byte1 => 1,
+        Byte2 | Short1 => 2,
+        Byte3 => 3,
+        Float1 | Byte4 | Short2 | Int1 => 4,
+        Short3 => 6,
+        Float2 | Short4 | Int2 => 8,
+        Float3 | Int3 => 12,
+        Float4 | Int4 => 16,
+        Mat4 => 64,
+    }
+}
@@ -0,0 +1,210 @@
+use bevy_ecs::prelude::*;
+use bevy_math::IVec2;
+use bevy_window::{PrimaryWindow, Window as WindowComponent, WindowMode, WindowPosition};
+use miniquad::{conf, window};
+
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+use bevy_window::WindowMoved;
+
+use crate::Window;
+
+/// Whether miniquad should run fullscreen for the given bevy `WindowMode`.
+///
+/// miniquad has a single fullscreen switch, with no display mode changes and
+/// no monitor selection, so modes are realised as follows:
+/// - `Windowed` leaves fullscreen and restores the last windowed size,
+/// - `BorderlessFullscreen` is the native miniquad fullscreen,
+/// - `SizedFullscreen` requests its `resolution` as the window size before
+///   entering fullscreen; every current backend (desktop, web, Android) still
+///   stretches the window to the monitor, which is reported back through
+///   `WindowResized`,
+/// - `Fullscreen` (exclusive) falls back to borderless fullscreen.
+///
+/// `MonitorSelection` is ignored, the window stays on its current monitor.
+pub fn window_mode_fullscreen(mode: WindowMode) -> bool {
+    match mode {
+        WindowMode::Windowed => false,
+        WindowMode::BorderlessFullscreen(_)
+        | WindowMode::SizedFullscreen(_)
+        | WindowMode::Fullscreen(_) => true,
+    }
+}
+
+/// Window settings last pushed to miniquad, to tell user changes of the
+/// window component apart from the ones made by the runner itself.
+#[derive(Debug, Resource)]
+pub(crate) struct AppliedWindowSettings {
+    pub mode: WindowMode,
+    /// Size to restore when leaving fullscreen, tracked on resize.
+    pub windowed_size: (u32, u32),
+    pub resize_pending: bool,
+    pub position: Option<IVec2>,
+    /// Size requested to satisfy resize constraints, accepted as is
+    /// if the platform refuses it.
+    pub constrained_size: Option<(u32, u32)>,
+}
+
+impl AppliedWindowSettings {
+    pub fn new(window: &WindowComponent) -> Self {
+        AppliedWindowSettings {
+            mode: window.mode,
+            windowed_size: (
+                window.resolution.physical_width(),
+                window.resolution.physical_height(),
+            ),
+            resize_pending: false,
+            position: match window.position {
+                WindowPosition::At(position) => Some(position),
+                _ => None,
+            },
+            constrained_size: None,
+        }
+    }
+}
+
+/// Physical window size clamped to the window's resize constraints.
+pub fn constrain_window_size(window: &WindowComponent, width: f32, height: f32) -> (f32, f32) {
+    let constraints = window.resize_constraints.check_constraints();
+    let scale = window.resolution.scale_factor();
+    (
+        width.clamp(constraints.min_width * scale, constraints.max_width * scale),
+        height.clamp(
+            constraints.min_height * scale,
+            constraints.max_height * scale,
+        ),
+    )
+}
+
+/// Sets up the miniquad configuration from the primary window component.
+///
+/// miniquad has no notion of window decorations, so `decorations: false` only
+/// disables the client-side decoration fallback on Wayland.
+pub(crate) fn configure(conf: &mut conf::Conf, window: &WindowComponent) {
+    let (width, height) = constrain_window_size(
+        window,
+        window.resolution.physical_width() as f32,
+        window.resolution.physical_height() as f32,
+    );
+    let scale = window.resolution.scale_factor();
+
+    conf.window_title = window.title.clone();
+    conf.window_width = (width / scale) as i32;
+    conf.window_height = (height / scale) as i32;
+    conf.fullscreen = window_mode_fullscreen(window.mode);
+    conf.window_resizable = window.resizable;
+    if !window.decorations {
+        conf.platform.wayland_decorations = conf::WaylandDecorations::ServerOnly;
+    }
+}
+
+/// Applies the settings miniquad can only change once the window exists.
+///
+/// `WindowPosition::Centered` is not supported and leaves the placement to the platform.
+pub(crate) fn configure_created(window: &WindowComponent) {
+    if let WindowPosition::At(position) = window.position {
+        set_window_position(position);
+    }
+}
+
+fn set_window_position(position: IVec2) {
+    // The X11 and Windows backends cast the coordinates back to `i32`, so negative
+    // ones, on monitors left of or above the primary one, survive the round trip.
+    window::set_window_position(position.x as u32, position.y as u32);
+}
+
+pub(crate) fn window_mode_system(
+    mut applied: ResMut<AppliedWindowSettings>,
+    windows: Query<&WindowComponent, (With<PrimaryWindow>, Changed<WindowComponent>)>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    if window.mode == applied.mode {
+        return;
+    }
+
+    match window.mode {
+        WindowMode::Windowed => {
+            let (width, height) = applied.windowed_size;
+            window::set_fullscreen(false);
+            window::set_window_size(width, height);
+        }
+        WindowMode::SizedFullscreen(_) => {
+            window::set_window_size(
+                window.resolution.physical_width(),
+                window.resolution.physical_height(),
+            );
+            window::set_fullscreen(true);
+        }
+        WindowMode::BorderlessFullscreen(_) | WindowMode::Fullscreen(_) => {
+            window::set_fullscreen(true);
+        }
+    }
+
+    log::debug!("Window mode {:?} -> {:?}", applied.mode, window.mode);
+    applied.mode = window.mode;
+    // Not every backend reports a resize after a fullscreen switch,
+    // so the runner polls the screen size until it changes.
+    applied.resize_pending = true;
+}
+
+pub(crate) fn window_position_system(
+    mut applied: ResMut<AppliedWindowSettings>,
+    windows: Query<&WindowComponent, (With<PrimaryWindow>, Changed<WindowComponent>)>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let WindowPosition::At(position) = window.position else {
+        return;
+    };
+    if applied.position == Some(position) {
+        return;
+    }
+
+    set_window_position(position);
+    applied.position = Some(position);
+}
+
+// miniquad has no move event, the position is only readable on some platforms.
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+pub(crate) fn poll_window_position(world: &mut World, entity: Entity) {
+    let (x, y) = window::get_window_position();
+    let position = IVec2::new(x as i32, y as i32);
+
+    let mut applied = world.resource_mut::<AppliedWindowSettings>();
+    if applied.position == Some(position) {
+        return;
+    }
+    applied.position = Some(position);
+
+    if let Some(mut window) = world.get_mut::<WindowComponent>(entity) {
+        window.position = WindowPosition::At(position);
+    }
+    world.send_event(WindowMoved {
+        window: entity,
+        position,
+    });
+}
+
+pub(crate) fn window_size_system(
+    window_size: Res<Window>,
+    applied: Res<AppliedWindowSettings>,
+    windows: Query<&WindowComponent, (With<PrimaryWindow>, Changed<WindowComponent>)>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    if window_mode_fullscreen(window.mode) || applied.resize_pending {
+        return;
+    }
+
+    let width = window.resolution.physical_width() as f32;
+    let height = window.resolution.physical_height() as f32;
+    if width == window_size.width && height == window_size.height {
+        return;
+    }
+
+    let (width, height) = constrain_window_size(window, width, height);
+    window::set_window_size(width as u32, height as u32);
+}
@@ -0,0 +1,57 @@
+use bevy::{input::keyboard::KeyboardInput, prelude::*};
+use bevy_miniquad::{
+    input_inject::InputInjector,
+    input_record::{InputRecorder, InputRecording, InputReplay},
+    miniquad::{KeyCode, KeyMods},
+    MiniquadPlugin,
+};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+#[test]
+fn injected_events_are_handled_once_and_not_recorded() {
+    let (keys, recorded) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins)
+        .add_plugins(MiniquadPlugin)
+        .insert_resource(InputRecorder::new())
+        .insert_resource(
+            InputReplay::new(InputRecording {
+                width: 64.,
+                height: 64.,
+                dpi_scale: 1.,
+                events: Vec::new(),
+            })
+            .with_extra_frames(3),
+        )
+        .add_systems(
+            Update,
+            |mut injector: ResMut<InputInjector>, mut injected: Local<bool>| {
+                if !*injected {
+                    *injected = true;
+                    let modifiers = KeyMods::default();
+                    // Neither skipped nor translated events may panic.
+                    injector.key_up(KeyCode::A, modifiers);
+                    injector.char('a', modifiers);
+                    injector.key_press(KeyCode::Unknown, None, modifiers);
+                    injector.key_press(KeyCode::Escape, None, modifiers);
+                }
+            },
+        )
+        .add_systems(Last, {
+            let (keys, recorded) = (keys.clone(), recorded.clone());
+            move |mut events: EventReader<KeyboardInput>, recorder: Res<InputRecorder>| {
+                keys.fetch_add(events.read().count(), Ordering::Relaxed);
+                recorded.store(recorder.recording().events.len(), Ordering::Relaxed);
+            }
+        });
+
+    assert_eq!(app.run(), AppExit::Success);
+
+    // The char, unknown key down and up, and escape down and up, the key up
+    // without a typed character is skipped.
+    assert_eq!(keys.load(Ordering::Relaxed), 5);
+    assert_eq!(recorded.load(Ordering::Relaxed), 0);
+}
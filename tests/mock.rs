@@ -0,0 +1,386 @@
+use bevy::prelude::*;
+use bevy_miniquad::{
+    buffer_source,
+    gpu::{GpuBuffer, GpuDeletionQueue},
+    miniquad as mq,
+    mock::{MockRenderingBackend, RenderCall, RenderLog},
+    Draw, MiniquadContext, MiniquadPlugin,
+};
+
+fn texture(ctx: &mut MockRenderingBackend) -> mq::TextureId {
+    mq::RenderingBackend::new_render_texture(
+        ctx,
+        mq::TextureParams {
+            width: 4,
+            height: 4,
+            ..Default::default()
+        },
+    )
+}
+
+fn deleted_textures(log: &RenderLog) -> Vec<mq::TextureId> {
+    log.calls()
+        .into_iter()
+        .filter_map(|call| match call {
+            RenderCall::DeleteTexture(texture) => Some(texture),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn gl_render_pass_deletes_its_attachments() {
+    let mut ctx = MockRenderingBackend::new();
+    let log = ctx.log();
+    let (color, depth) = (texture(&mut ctx), texture(&mut ctx));
+    let pass = mq::RenderingBackend::new_render_pass(&mut ctx, color, Some(depth));
+
+    mq::RenderingBackend::delete_render_pass(&mut ctx, pass);
+
+    assert_eq!(deleted_textures(&log), [color, depth]);
+}
+
+#[test]
+fn metal_render_pass_keeps_its_attachments() {
+    let mut ctx = MockRenderingBackend::with_backend(mq::Backend::Metal);
+    let log = ctx.log();
+    let color = texture(&mut ctx);
+    let pass = mq::RenderingBackend::new_render_pass(&mut ctx, color, None);
+
+    mq::RenderingBackend::delete_render_pass(&mut ctx, pass);
+
+    assert!(deleted_textures(&log).is_empty());
+    assert_eq!(
+        mq::RenderingBackend::texture_params(&ctx, color).width,
+        4,
+        "the texture must still exist"
+    );
+}
+
+#[test]
+fn texture_wrap_records_both_axes() {
+    let mut ctx = MockRenderingBackend::new();
+    let log = ctx.log();
+    let texture = texture(&mut ctx);
+    log.clear();
+
+    mq::RenderingBackend::texture_set_wrap(
+        &mut ctx,
+        texture,
+        mq::TextureWrap::Repeat,
+        mq::TextureWrap::Mirror,
+    );
+
+    assert!(matches!(
+        log.calls()[..],
+        [RenderCall::SetTextureWrap {
+            wrap_x: mq::TextureWrap::Repeat,
+            wrap_y: mq::TextureWrap::Mirror,
+            ..
+        }]
+    ));
+    assert_eq!(
+        mq::RenderingBackend::texture_params(&ctx, texture).wrap,
+        mq::TextureWrap::Repeat
+    );
+}
+
+#[test]
+fn buffer_uploads_record_their_size() {
+    let mut ctx = MockRenderingBackend::new();
+    let log = ctx.log();
+    let data = [0f32; 6];
+    let buffer = mq::RenderingBackend::new_buffer(
+        &mut ctx,
+        mq::BufferType::VertexBuffer,
+        mq::BufferUsage::Stream,
+        buffer_source::slice(&data),
+    );
+    mq::RenderingBackend::buffer_update(&mut ctx, buffer, buffer_source::slice(&data[..2]));
+    mq::RenderingBackend::buffer_update(&mut ctx, buffer, mq::BufferSource::slice(&data));
+
+    let sizes: Vec<_> = log
+        .calls()
+        .into_iter()
+        .filter_map(|call| match call {
+            RenderCall::NewBuffer { size, .. } | RenderCall::UpdateBuffer { size, .. } => {
+                Some(size)
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(sizes, [Some(24), Some(8), None]);
+    assert_eq!(mq::RenderingBackend::buffer_size(&mut ctx, buffer), 24);
+}
+
+fn mock_app(mut app: App) -> (App, RenderLog) {
+    let backend = MockRenderingBackend::new();
+    let log = backend.log();
+    app.add_plugins(MiniquadPlugin)
+        .insert_non_send_resource(MiniquadContext(Box::new(backend)))
+        .init_resource::<bevy_miniquad::Window>();
+    (app, log)
+}
+
+#[test]
+fn dropped_objects_are_deleted_by_their_own_app() {
+    let (mut first, first_log) = mock_app(App::new());
+    let (mut second, second_log) = mock_app(App::new());
+    let buffer = {
+        let queue = first.world().resource::<GpuDeletionQueue>().clone();
+        let mut ctx = first.world_mut().non_send_resource_mut::<MiniquadContext>();
+        let buffer = ctx.0.new_buffer(
+            mq::BufferType::VertexBuffer,
+            mq::BufferUsage::Immutable,
+            mq::BufferSource::empty::<f32>(4),
+        );
+        GpuBuffer::new(&queue, buffer)
+    };
+    let id = *buffer;
+    drop(buffer);
+
+    second.world_mut().run_schedule(Draw);
+    first.world_mut().run_schedule(Draw);
+
+    let deleted = |log: &RenderLog| {
+        log.calls()
+            .iter()
+            .any(|call| matches!(call, RenderCall::DeleteBuffer(buffer) if *buffer == id))
+    };
+    assert!(deleted(&first_log));
+    assert!(!deleted(&second_log));
+}
+
+#[cfg(feature = "sprite")]
+#[test]
+fn replay_draws_sprites_with_the_mock() {
+    use bevy_miniquad::{
+        camera::MiniquadCamera2d,
+        input_record::{InputRecording, InputReplay},
+        sprite::{Sprite, SpritePlugin},
+    };
+
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    let (mut app, log) = mock_app(app);
+    app.add_plugins(SpritePlugin)
+        .insert_resource(
+            InputReplay::new(InputRecording {
+                width: 64.,
+                height: 64.,
+                dpi_scale: 1.,
+                events: Vec::new(),
+            })
+            .with_extra_frames(2),
+        )
+        .add_systems(Startup, |mut commands: Commands| {
+            commands.spawn(MiniquadCamera2d::default());
+            commands.spawn(Sprite {
+                custom_size: Some(Vec2::splat(16.)),
+                ..Default::default()
+            });
+        });
+
+    assert_eq!(app.run(), AppExit::Success);
+
+    let calls = log.calls();
+    let frames = calls
+        .iter()
+        .filter(|call| matches!(call, RenderCall::CommitFrame))
+        .count();
+    assert_eq!(frames, 2);
+    assert_eq!(log.draw_calls(), 2);
+}
+
+#[cfg(feature = "diagnostics")]
+#[test]
+fn diagnostics_count_render_pass_attachments_deleted_by_gl() {
+    use bevy::diagnostic::DiagnosticsStore;
+    use bevy_miniquad::{
+        diagnostics::MiniquadDiagnosticsPlugin,
+        input_record::{InputRecording, InputReplay},
+    };
+    use std::sync::{Arc, Mutex};
+
+    let textures = Arc::new(Mutex::new(Vec::new()));
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    let (mut app, _) = mock_app(app);
+    app.add_plugins(MiniquadDiagnosticsPlugin)
+        .insert_resource(
+            InputReplay::new(InputRecording {
+                width: 64.,
+                height: 64.,
+                dpi_scale: 1.,
+                events: Vec::new(),
+            })
+            .with_extra_frames(3),
+        )
+        // A render pass with a color and a depth texture, deleted a frame later.
+        .add_systems(
+            Update,
+            |mut ctx: NonSendMut<MiniquadContext>,
+             mut frame: Local<u32>,
+             mut pass: Local<Option<mq::RenderPass>>| {
+                *frame += 1;
+                if *frame == 1 {
+                    let params = mq::TextureParams {
+                        width: 4,
+                        height: 4,
+                        ..Default::default()
+                    };
+                    let color = ctx.0.new_render_texture(params);
+                    let depth = ctx.0.new_render_texture(mq::TextureParams {
+                        format: mq::TextureFormat::Depth,
+                        ..params
+                    });
+                    *pass = Some(ctx.0.new_render_pass(color, Some(depth)));
+                } else if let Some(pass) = pass.take() {
+                    ctx.0.delete_render_pass(pass);
+                }
+            },
+        )
+        .add_systems(Last, {
+            let textures = textures.clone();
+            move |store: Res<DiagnosticsStore>| {
+                let value = store
+                    .get(&MiniquadDiagnosticsPlugin::TEXTURES)
+                    .and_then(|diagnostic| diagnostic.value());
+                textures.lock().unwrap().push(value);
+            }
+        });
+
+    assert_eq!(app.run(), AppExit::Success);
+
+    // Counted at the start of each frame, from the second one.
+    assert_eq!(*textures.lock().unwrap(), [None, Some(2.), Some(0.)]);
+}
+
+#[cfg(feature = "post-process")]
+#[test]
+fn built_in_post_process_effects_run_on_metal() {
+    use bevy_miniquad::post_process::{
+        Bloom, ColorGrading, Crt, PostProcessEffects, PostProcessPlugin, Vignette,
+    };
+
+    let mut app = App::new();
+    app.add_plugins((MiniquadPlugin, PostProcessPlugin))
+        .insert_non_send_resource(MiniquadContext(Box::new(
+            MockRenderingBackend::with_backend(mq::Backend::Metal),
+        )))
+        .insert_resource(bevy_miniquad::Window {
+            width: 64.,
+            height: 64.,
+            ..Default::default()
+        })
+        .insert_resource(Bloom::default())
+        .insert_resource(ColorGrading::default())
+        .insert_resource(Vignette::default())
+        .insert_resource(Crt::default());
+
+    app.world_mut().run_schedule(Draw);
+
+    let effects = app.world().resource::<PostProcessEffects>();
+    assert_eq!(effects.active().count(), 4);
+}
+
+#[cfg(feature = "text")]
+#[test]
+fn font_atlas_uploads_only_new_glyph_rows() {
+    use bevy_asset::{AssetPlugin, Assets};
+    use bevy_miniquad::text::{Font, FontAtlas, TextLabel, TextPlugin, TextStyle};
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()));
+    let (mut app, log) = mock_app(app);
+    app.add_plugins(TextPlugin);
+    let font = Font::from_bytes(include_bytes!("../assets/fonts/DejaVuSans.ttf")).unwrap();
+    let font = app.world_mut().resource_mut::<Assets<Font>>().add(font);
+    let style = TextStyle {
+        font,
+        ..Default::default()
+    };
+    let label = app
+        .world_mut()
+        .spawn(TextLabel::new("ab", style, Vec2::ZERO))
+        .id();
+
+    app.world_mut().run_schedule(Draw);
+    let texture = app.world().resource::<FontAtlas>().texture().unwrap();
+    assert!(log.calls().iter().any(|call| matches!(
+        call,
+        RenderCall::NewTexture { texture: new, params, .. }
+            if *new == texture && params.format == mq::TextureFormat::Alpha
+    )));
+
+    // Laid out once, nothing to upload while the text is unchanged.
+    log.clear();
+    app.world_mut().run_schedule(Draw);
+    assert!(!log
+        .calls()
+        .iter()
+        .any(|call| matches!(call, RenderCall::UpdateTexture { .. })));
+    let glyphs = app.world().resource::<FontAtlas>().len();
+
+    app.world_mut()
+        .get_mut::<TextLabel>(label)
+        .unwrap()
+        .text
+        .push('c');
+    app.world_mut().run_schedule(Draw);
+    assert_eq!(app.world().resource::<FontAtlas>().len(), glyphs + 1);
+    let updates: Vec<_> = log
+        .calls()
+        .into_iter()
+        .filter_map(|call| match call {
+            RenderCall::UpdateTexture { height, .. } => Some(height),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(updates.len(), 1);
+    assert!((updates[0] as u32) < app.world().resource::<FontAtlas>().size().y);
+}
+
+#[cfg(feature = "shader")]
+#[test]
+fn shader_pipelines_are_deleted_with_their_shader() {
+    use bevy_asset::{AssetPlugin, Assets};
+    use bevy_miniquad::shader::{
+        MiniquadShader, MiniquadShaderPlugin, ShaderPipelineDescriptor, ShaderPipelines,
+    };
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()));
+    let (mut app, log) = mock_app(app);
+    app.add_plugins(MiniquadShaderPlugin);
+    let shader = MiniquadShader::parse(include_str!("../assets/shaders/waves.mqshader")).unwrap();
+    let shader = app
+        .world_mut()
+        .resource_mut::<Assets<MiniquadShader>>()
+        .add(shader);
+    let id = app
+        .world_mut()
+        .resource_mut::<ShaderPipelines>()
+        .add(ShaderPipelineDescriptor {
+            shader: shader.clone(),
+            buffer_layouts: vec![mq::BufferLayout::default()],
+            attributes: vec![mq::VertexAttribute::new("pos", mq::VertexFormat::Float2)],
+            params: Default::default(),
+        });
+
+    app.world_mut().run_schedule(Draw);
+    let pipeline = app.world().resource::<ShaderPipelines>().get(id).unwrap();
+
+    app.world_mut()
+        .resource_mut::<Assets<MiniquadShader>>()
+        .remove(&shader);
+    app.world_mut().run_schedule(Last);
+    app.world_mut().run_schedule(Draw);
+    assert_eq!(app.world().resource::<ShaderPipelines>().get(id), None);
+    // Deleted at the start of the next draw.
+    app.world_mut().run_schedule(Draw);
+    assert!(log
+        .calls()
+        .iter()
+        .any(|call| matches!(call, RenderCall::DeletePipeline(deleted) if *deleted == pipeline)));
+}